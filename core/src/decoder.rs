@@ -0,0 +1,124 @@
+//! Turning a marker's samples into its value. Each kind of marker has its own
+//! decoder, built from the marker's settings, so that new kinds only need a
+//! new decoder and a case in [`Marker::decoder`].
+
+use std::borrow::Cow;
+
+use serde_json::Value;
+
+use crate::marker::{read_seven_segment, Marker, Output, Quality, Retry, SegmentPattern};
+
+pub trait Decoder: Send + Sync {
+    /// Decodes the samples at about the given threshold. Seven segment
+    /// displays are read as the characters in `patterns`.
+    fn decode(
+        &self,
+        samples: &[f32],
+        threshold: f32,
+        patterns: &[SegmentPattern],
+    ) -> (Value, Quality);
+}
+
+/// On when the single sample is above the threshold.
+pub struct Point;
+
+impl Decoder for Point {
+    fn decode(&self, samples: &[f32], threshold: f32, _: &[SegmentPattern]) -> (Value, Quality) {
+        let quality = Quality {
+            retries: 0,
+            threshold,
+        };
+
+        let Some(value) = samples.first() else {
+            return (Value::Null, quality);
+        };
+
+        (Value::Bool(*value > threshold), quality)
+    }
+}
+
+/// Like [`Point`], but the sample is already 0 or 1, so the threshold is
+/// ignored.
+pub struct Gpio;
+
+impl Decoder for Gpio {
+    fn decode(&self, samples: &[f32], _: f32, patterns: &[SegmentPattern]) -> (Value, Quality) {
+        Point.decode(samples, 0.5, patterns)
+    }
+}
+
+pub struct SevenSegment {
+    pub retry: Retry,
+    pub output: Output,
+    pub reverse_digits: bool,
+    pub mirror_segments: bool,
+}
+
+impl SevenSegment {
+    pub fn new(marker: &Marker) -> Self {
+        Self {
+            retry: marker.retry,
+            output: marker.output.clone(),
+            reverse_digits: marker.reverse_digits,
+            mirror_segments: marker.mirror_segments,
+        }
+    }
+
+    /// The samples in reading order, as set by [`Self::reverse_digits`] and
+    /// [`Self::mirror_segments`].
+    pub fn oriented<'a>(&self, samples: &'a [f32]) -> Cow<'a, [f32]> {
+        if !self.reverse_digits && !self.mirror_segments {
+            return Cow::Borrowed(samples);
+        }
+
+        let mut digits = samples.chunks(7).map(<[f32]>::to_vec).collect::<Vec<_>>();
+
+        if self.reverse_digits {
+            digits.reverse();
+        }
+
+        if self.mirror_segments {
+            for digit in digits.iter_mut().filter(|digit| digit.len() == 7) {
+                // b and f, c and e.
+                digit.swap(1, 5);
+                digit.swap(2, 4);
+            }
+        }
+
+        Cow::Owned(digits.concat())
+    }
+}
+
+impl Decoder for SevenSegment {
+    /// Retries at thresholds oscillating around the given one until the
+    /// display decodes, as set by [`Self::retry`].
+    fn decode(
+        &self,
+        samples: &[f32],
+        mut threshold: f32,
+        patterns: &[SegmentPattern],
+    ) -> (Value, Quality) {
+        let samples = self.oriented(samples);
+        let retry = &self.retry;
+        let mut threshold_change = retry.step;
+        let mut retries = 0;
+
+        loop {
+            let quality = Quality { retries, threshold };
+
+            if let Some(text) = read_seven_segment(&samples, threshold, patterns) {
+                return (self.output.format(Value::String(text)), quality);
+            }
+
+            threshold += threshold_change;
+            threshold_change *= -retry.factor;
+            retries += 1;
+
+            if retries > retry.max_retries
+                || !(retry.min_threshold..=retry.max_threshold).contains(&threshold)
+            {
+                return (self.output.format(Value::Null), quality);
+            }
+        }
+    }
+}
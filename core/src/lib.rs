@@ -0,0 +1,19 @@
+//! The frontend-independent parts of the pipeline: marker geometry, sampling,
+//! decoding and turning the decoded values into MQTT messages.
+
+pub mod decoder;
+pub mod marker;
+pub mod publish;
+pub mod stats;
+
+pub use self::{
+    decoder::Decoder,
+    marker::{
+        decode_seven_segment, luminance, pixel_at, read_seven_segment, DigitGroup, Interpolation,
+        Marker, MarkerType, Output, Point, Quality, Retry, SegmentPattern,
+    },
+    publish::{
+        marker_topic, messages, meta, meta_messages, named_values, time_remaining, Message,
+        PayloadFormat, TimeRemaining, TimeUnit,
+    },
+};
@@ -0,0 +1,855 @@
+use std::borrow::Cow;
+
+use ecolor::Color32;
+use emath::{Pos2, Vec2};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::decoder::{self, Decoder};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Marker {
+    pub name: String,
+    pub ty: MarkerType,
+    /// Sample this marker at its own interval instead of the global one.
+    #[serde(default)]
+    pub refresh_secs: Option<u64>,
+    /// Overrides the profile's luminance threshold for this marker.
+    #[serde(default)]
+    pub threshold: Option<f32>,
+    /// Publish under the name as typed, even if it has whitespace or topic
+    /// separators in it.
+    #[serde(default)]
+    pub raw_name: bool,
+    /// How seven segment displays are retried at other thresholds when they
+    /// don't decode.
+    #[serde(default)]
+    pub retry: Retry,
+    /// What a seven segment display's digits are published as.
+    #[serde(default)]
+    pub output: Output,
+    /// Read a seven segment display's digits from right to left, for
+    /// displays seen through a mirror.
+    #[serde(default)]
+    pub reverse_digits: bool,
+    /// Swap the left and right segments of each digit, for the same.
+    #[serde(default)]
+    pub mirror_segments: bool,
+    /// Published in place of a value that couldn't be decoded, such as
+    /// `"unknown"`, rather than the value being left out.
+    #[serde(default)]
+    pub unknown: Option<Value>,
+    /// The display blinks while the machine is paused, so blank samples in
+    /// between keep the value shown and mark the profile as paused.
+    #[serde(default)]
+    pub blinks_when_paused: bool,
+    /// Unit of the value, such as `min`, described in its meta topic.
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+impl Marker {
+    pub fn new(ty: MarkerType) -> Self {
+        Self {
+            name: Default::default(),
+            ty,
+            refresh_secs: None,
+            threshold: None,
+            raw_name: false,
+            retry: Default::default(),
+            output: Default::default(),
+            reverse_digits: false,
+            mirror_segments: false,
+            unknown: None,
+            blinks_when_paused: false,
+            unit: None,
+        }
+    }
+
+    /// The luminance threshold for this marker, given the profile's.
+    pub fn threshold_or(&self, threshold: f32) -> f32 {
+        self.threshold.unwrap_or(threshold)
+    }
+
+    /// The decoder for this marker's type and settings.
+    pub fn decoder(&self) -> Box<dyn Decoder> {
+        match self.ty {
+            MarkerType::Point { .. } => Box::new(decoder::Point),
+            MarkerType::SevenSegment { .. } => Box::new(decoder::SevenSegment::new(self)),
+            MarkerType::Gpio { .. } => Box::new(decoder::Gpio),
+        }
+    }
+
+    /// Decodes the samples at about the given threshold into the value for
+    /// [`Self::output`], using [`Self::decoder`].
+    pub fn decode(
+        &self,
+        samples: &[f32],
+        threshold: f32,
+        patterns: &[SegmentPattern],
+    ) -> (Value, Quality) {
+        self.decoder().decode(samples, threshold, patterns)
+    }
+
+    /// A seven segment display's samples in reading order, as set by
+    /// [`Self::reverse_digits`] and [`Self::mirror_segments`].
+    pub fn oriented<'a>(&self, samples: &'a [f32]) -> Cow<'a, [f32]> {
+        match self.ty {
+            MarkerType::SevenSegment { .. } => decoder::SevenSegment::new(self).oriented(samples),
+            _ => Cow::Borrowed(samples),
+        }
+    }
+
+    /// The names this marker's value is published under, which are those of
+    /// its digit groups if it's split.
+    pub fn value_names(&self) -> Vec<&str> {
+        match (&self.ty, &self.output) {
+            (MarkerType::SevenSegment { .. }, Output::Split { groups }) => {
+                groups.iter().map(|group| group.name.as_str()).collect()
+            }
+            _ => vec![self.name.as_str()],
+        }
+    }
+
+    /// Whether this marker should be shown for the given (lowercased) filter
+    /// string, matching either its name or its type.
+    pub fn matches(&self, filter: &str) -> bool {
+        filter.is_empty()
+            || self.name.to_lowercase().contains(filter)
+            || self.ty.name().contains(filter)
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum MarkerType {
+    Point {
+        pos: Pos2,
+        size: f32,
+    },
+    SevenSegment {
+        start: Pos2,
+        end: Pos2,
+        bottom: Pos2,
+        digits: usize,
+        spacing: f32,
+        size: f32,
+    },
+    /// A sensor on a GPIO input rather than something in the frame, which is
+    /// read whenever the frame is sampled. Its sample is 1 while it's active.
+    Gpio {
+        /// BCM pin number.
+        pin: u8,
+        #[serde(default)]
+        active_low: bool,
+        #[serde(default)]
+        pull_up: bool,
+    },
+}
+
+/// What a seven segment display's digits are published as.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Output {
+    /// A single number, or a string if the display shows anything but
+    /// digits.
+    #[default]
+    Integer,
+    /// A string with as many characters as the display, keeping leading
+    /// zeros.
+    Padded,
+    /// Groups of digits from the left as separate numbers, each published
+    /// under its own name, like `hour` and `minute` for a clock.
+    Split { groups: Vec<DigitGroup> },
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct DigitGroup {
+    pub name: String,
+    pub digits: usize,
+}
+
+impl Output {
+    /// Formats the characters read from a display, as numbers where they
+    /// are. A split display is an object with a value per group.
+    pub fn format(&self, value: Value) -> Value {
+        let number = |text: &str| {
+            text.parse::<i64>()
+                .map_or_else(|_| Value::String(text.to_owned()), Value::from)
+        };
+
+        let Value::String(text) = value else {
+            return match self {
+                Output::Split { groups } => groups
+                    .iter()
+                    .map(|group| (group.name.clone(), Value::Null))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into(),
+                _ => value,
+            };
+        };
+
+        match self {
+            Output::Integer => number(&text),
+            Output::Padded => Value::String(text),
+            Output::Split { groups } => {
+                let characters = text.chars().collect::<Vec<_>>();
+                let mut rest = characters.as_slice();
+
+                groups
+                    .iter()
+                    .map(|group| {
+                        let (digits, after) = rest.split_at(group.digits.min(rest.len()));
+                        rest = after;
+
+                        let value = match digits {
+                            [] => Value::Null,
+                            digits => number(&digits.iter().collect::<String>()),
+                        };
+                        (group.name.clone(), value)
+                    })
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            }
+        }
+    }
+}
+
+/// Thresholds to try for a seven segment display that doesn't decode at the
+/// first one, alternating above and below it.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Retry {
+    /// How many other thresholds to try, or 0 to give up right away.
+    pub max_retries: usize,
+    /// How far from the threshold the first retry is.
+    pub step: f32,
+    /// How many times further each retry is than the one before.
+    pub factor: f32,
+    /// Give up instead of going below or above these.
+    pub min_threshold: f32,
+    pub max_threshold: f32,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_retries: 32,
+            step: 0.01,
+            factor: 1.5,
+            min_threshold: 0.,
+            max_threshold: 1.,
+        }
+    }
+}
+
+/// How a value was decoded. Many retries are an early warning that the
+/// threshold no longer suits the display.
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Quality {
+    /// How many other thresholds were tried first.
+    pub retries: usize,
+    /// The threshold the value was decoded at, or the last one tried if it
+    /// couldn't be decoded.
+    pub threshold: f32,
+}
+
+/// How a point between pixel centers is read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Interpolation {
+    /// The pixel the point is in.
+    #[default]
+    Nearest,
+    /// The four pixels around the point, weighted by how close it is to
+    /// each, which keeps points on small segments from landing in the gaps.
+    Bilinear,
+}
+
+impl Interpolation {
+    pub const ALL: [Self; 2] = [Self::Nearest, Self::Bilinear];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Nearest => "nearest",
+            Self::Bilinear => "bilinear",
+        }
+    }
+}
+
+pub struct Point {
+    pub pos: Pos2,
+    pub size: f32,
+}
+
+impl Point {
+    /// The column and row of the pixel this point reads, in an image of the
+    /// given size. Points past an edge, such as after the crop shifted, read
+    /// the pixel on that edge.
+    pub fn pixel(&self, width: usize, height: usize) -> (usize, usize) {
+        let (x, y) = pixel_at(self.pos, width, height);
+
+        (clamp_pixel(x, width), clamp_pixel(y, height))
+    }
+
+    /// The columns and rows of the pixels this point reads, as `(left, top,
+    /// right, bottom)` with the right and bottom excluded.
+    pub fn pixels_read(
+        &self,
+        width: usize,
+        height: usize,
+        interpolation: Interpolation,
+    ) -> (usize, usize, usize, usize) {
+        match interpolation {
+            Interpolation::Nearest => {
+                let (x, y) = self.pixel(width, height);
+                (x, y, x + 1, y + 1)
+            }
+            Interpolation::Bilinear => {
+                let (x, y) = self.pixel_space(width, height);
+                let (x, y) = (x.floor() as i64, y.floor() as i64);
+
+                (
+                    clamp_pixel(x, width),
+                    clamp_pixel(y, height),
+                    clamp_pixel(x + 1, width) + 1,
+                    clamp_pixel(y + 1, height) + 1,
+                )
+            }
+        }
+    }
+
+    /// Where the point is relative to the pixel centers, which are half a
+    /// pixel in from the pixel edges.
+    fn pixel_space(&self, width: usize, height: usize) -> (f32, f32) {
+        (
+            self.pos.x * width as f32 - 0.5,
+            self.pos.y * height as f32 - 0.5,
+        )
+    }
+
+    /// The luminance at this point moved by whole pixels. The image can't be
+    /// empty.
+    fn luminance(
+        &self,
+        pixels: &[Color32],
+        width: usize,
+        height: usize,
+        (dx, dy): (i64, i64),
+        interpolation: Interpolation,
+    ) -> f32 {
+        let at = |x: i64, y: i64| {
+            luminance(pixels[clamp_pixel(y, height) * width + clamp_pixel(x, width)])
+        };
+
+        match interpolation {
+            Interpolation::Nearest => {
+                let (x, y) = pixel_at(self.pos, width, height);
+                at(x + dx, y + dy)
+            }
+            Interpolation::Bilinear => {
+                let (x, y) = self.pixel_space(width, height);
+                let (left, top) = (x.floor(), y.floor());
+                let (tx, ty) = (x - left, y - top);
+                let (x, y) = (left as i64 + dx, top as i64 + dy);
+
+                let upper = at(x, y) * (1. - tx) + at(x + 1, y) * tx;
+                let lower = at(x, y + 1) * (1. - tx) + at(x + 1, y + 1) * tx;
+                upper * (1. - ty) + lower * ty
+            }
+        }
+    }
+
+    /// The pixel this point reads, if the image has any.
+    pub fn sample<T: Copy>(&self, pixels: &[T], width: usize, height: usize) -> Option<T> {
+        let (x, y) = self.pixel(width, height);
+
+        pixels.get(y * width + x).copied()
+    }
+}
+
+/// A column or row moved onto the nearest edge of an image of the given
+/// size, if it's past one.
+fn clamp_pixel(n: i64, size: usize) -> usize {
+    n.clamp(0, size.saturating_sub(1) as i64) as usize
+}
+
+/// The column and row of the pixel a normalized position is in, in an image
+/// of the given size. Pixel `x` spans `x / width` to `(x + 1) / width`, the
+/// same as where it's drawn, so a point reads the pixel it's drawn over.
+pub fn pixel_at(pos: Pos2, width: usize, height: usize) -> (i64, i64) {
+    (
+        (pos.x * width as f32).floor() as i64,
+        (pos.y * height as f32).floor() as i64,
+    )
+}
+
+/// Relative luminance of a color, from 0 to 1, as compared against
+/// thresholds.
+pub fn luminance(color: Color32) -> f32 {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+
+    let r = r as f32 / 255.;
+    let g = g as f32 / 255.;
+    let b = b as f32 / 255.;
+
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+impl MarkerType {
+    /// The luminance at each of this marker's points, in an image of the
+    /// given size.
+    pub fn sample(&self, pixels: &[Color32], width: usize, height: usize) -> Vec<f32> {
+        self.sample_with_search(pixels, width, height, 0, Interpolation::Nearest)
+    }
+
+    /// Like [`Self::sample`], but takes the brightest pixel within `search`
+    /// pixels of each point, to make up for camera shake, and reads between
+    /// pixels as set by `interpolation`.
+    pub fn sample_with_search(
+        &self,
+        pixels: &[Color32],
+        width: usize,
+        height: usize,
+        search: usize,
+        interpolation: Interpolation,
+    ) -> Vec<f32> {
+        let points = self.get_points();
+
+        // Nothing to sample in an empty image, or one smaller than its size.
+        if width == 0 || height == 0 || pixels.len() < width * height {
+            return vec![0.; points.len()];
+        }
+
+        let search = search as i64;
+        let offsets = (-search..=search)
+            .flat_map(|dy| (-search..=search).map(move |dx| (dx, dy)))
+            .collect::<Vec<_>>();
+
+        points
+            .into_iter()
+            .map(|point| {
+                offsets
+                    .iter()
+                    .map(|&offset| point.luminance(pixels, width, height, offset, interpolation))
+                    .fold(0., f32::max)
+            })
+            .collect()
+    }
+
+    /// Moves the marker by a normalized offset.
+    pub fn translate(&mut self, offset: Vec2) {
+        match self {
+            MarkerType::Point { pos, .. } => *pos += offset,
+            MarkerType::SevenSegment {
+                start, end, bottom, ..
+            } => {
+                *start += offset;
+                *end += offset;
+                *bottom += offset;
+            }
+            MarkerType::Gpio { .. } => {}
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarkerType::Point { .. } => "point",
+            MarkerType::SevenSegment { .. } => "seven segment",
+            MarkerType::Gpio { .. } => "gpio",
+        }
+    }
+
+    pub fn get_points(&self) -> Vec<Point> {
+        match *self {
+            MarkerType::Point { pos, size } => vec![Point { pos, size }],
+            MarkerType::SevenSegment {
+                start,
+                end,
+                bottom,
+                digits,
+                spacing,
+                size,
+            } => {
+                let length = (end - start).length();
+                let direction = (end - start).normalized();
+                let tangent = bottom - start;
+
+                let segment_length = (length - spacing * (digits as f32 - 1.)) / digits as f32;
+
+                //  aa
+                // f  b
+                //  gg
+                // e  c
+                //  dd
+
+                (0..digits)
+                    .flat_map(|n| {
+                        let start = start + direction * (n as f32 * (segment_length + spacing));
+                        let center = start + direction * segment_length / 2.;
+                        let end = start + direction * segment_length;
+
+                        let a = center - tangent;
+                        let b = end - tangent / 2.;
+                        let c = end + tangent / 2.;
+                        let d = center + tangent;
+                        let e = start + tangent / 2.;
+                        let f = start - tangent / 2.;
+                        let g = center;
+
+                        vec![a, b, c, d, e, f, g]
+                    })
+                    .map(|pos| Point { pos, size })
+                    .collect()
+            }
+            MarkerType::Gpio { .. } => vec![],
+        }
+    }
+}
+
+/// Reads the characters on a seven segment display at exactly the given
+/// threshold, without retrying.
+pub fn read_seven_segment(
+    samples: &[f32],
+    threshold: f32,
+    patterns: &[SegmentPattern],
+) -> Option<String> {
+    samples
+        .chunks(7)
+        .map(|segments| {
+            if segments.len() < 7 {
+                return None;
+            }
+
+            let lit = segments
+                .iter()
+                .enumerate()
+                .filter(|(_, &value)| value > threshold)
+                .fold(0, |mask, (segment, _)| mask | 1 << segment);
+
+            patterns
+                .iter()
+                .find(|pattern| pattern.mask() == Some(lit))
+                .map(|pattern| pattern.character)
+        })
+        .collect()
+}
+
+/// Decodes the samples of a seven segment marker at exactly the given
+/// threshold, without retrying, using the built-in digits.
+pub fn decode_seven_segment(samples: &[f32], threshold: f32) -> Option<i32> {
+    read_seven_segment(samples, threshold, &SegmentPattern::defaults())?
+        .parse()
+        .ok()
+}
+
+//  aa
+// f  b
+//  gg
+// e  c
+//  dd
+
+/// The character shown when exactly these segments of a digit are lit.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentPattern {
+    /// The letters of the lit segments, `a` at the top going clockwise to
+    /// `f`, and `g` in the middle.
+    pub segments: String,
+    pub character: char,
+}
+
+impl SegmentPattern {
+    pub const SEGMENTS: &'static str = "abcdefg";
+
+    /// The digits, with a blank digit read as 0.
+    pub fn defaults() -> Vec<Self> {
+        [
+            ("abcdef", '0'),
+            ("bc", '1'),
+            ("abdeg", '2'),
+            ("abcdg", '3'),
+            ("bcfg", '4'),
+            ("acdfg", '5'),
+            ("acdefg", '6'),
+            ("abc", '7'),
+            ("abcdefg", '8'),
+            ("abcdfg", '9'),
+            ("", '0'),
+        ]
+        .into_iter()
+        .map(|(segments, character)| Self {
+            segments: segments.to_owned(),
+            character,
+        })
+        .collect()
+    }
+
+    /// A bit per lit segment, `a` being the lowest, or `None` if
+    /// [`Self::segments`] has anything but segment letters.
+    pub fn mask(&self) -> Option<u8> {
+        self.segments.chars().try_fold(0, |mask, segment| {
+            Some(mask | 1 << Self::SEGMENTS.find(segment)?)
+        })
+    }
+
+    pub fn set_mask(&mut self, mask: u8) {
+        self.segments = Self::SEGMENTS
+            .chars()
+            .enumerate()
+            .filter(|(bit, _)| mask & 1 << bit != 0)
+            .map(|(_, segment)| segment)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The samples of a digit with the given segments lit.
+    fn digit(segments: &str) -> Vec<f32> {
+        SegmentPattern::SEGMENTS
+            .chars()
+            .map(|segment| if segments.contains(segment) { 1. } else { 0. })
+            .collect()
+    }
+
+    fn display(digits: &[&str]) -> Vec<f32> {
+        digits.iter().flat_map(|segments| digit(segments)).collect()
+    }
+
+    #[test]
+    fn reads_every_default_digit() {
+        for pattern in SegmentPattern::defaults() {
+            let samples = digit(&pattern.segments);
+            assert_eq!(
+                read_seven_segment(&samples, 0.5, &SegmentPattern::defaults()),
+                Some(pattern.character.to_string()),
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_digits_left_to_right() {
+        let samples = display(&["bc", "abdeg", "abcdg"]);
+        assert_eq!(decode_seven_segment(&samples, 0.5), Some(123));
+    }
+
+    #[test]
+    fn decodes_blank_digits_as_zero() {
+        let samples = display(&["", "bcfg", "abcdef"]);
+        assert_eq!(decode_seven_segment(&samples, 0.5), Some(40));
+    }
+
+    #[test]
+    fn unknown_patterns_dont_decode() {
+        let samples = display(&["bc", "ag"]);
+        assert_eq!(decode_seven_segment(&samples, 0.5), None);
+    }
+
+    #[test]
+    fn partial_digits_dont_decode() {
+        let mut samples = display(&["bc"]);
+        samples.extend([1., 1.]);
+        assert_eq!(
+            read_seven_segment(&samples, 0.5, &SegmentPattern::defaults()),
+            None
+        );
+    }
+
+    #[test]
+    fn threshold_is_exclusive() {
+        let samples = digit("abc").iter().map(|lit| lit * 0.5).collect::<Vec<_>>();
+        assert_eq!(decode_seven_segment(&samples, 0.5), Some(0));
+        assert_eq!(decode_seven_segment(&samples, 0.4), Some(7));
+    }
+
+    #[test]
+    fn reads_custom_patterns() {
+        let mut patterns = SegmentPattern::defaults();
+        patterns.push(SegmentPattern {
+            segments: "adefg".to_owned(),
+            character: 'E',
+        });
+
+        let samples = display(&["adefg", "bc"]);
+        assert_eq!(
+            read_seven_segment(&samples, 0.5, &patterns),
+            Some("E1".to_owned())
+        );
+        assert_eq!(decode_seven_segment(&samples, 0.5), None);
+    }
+
+    fn seven_segment(digits: usize) -> Marker {
+        Marker::new(MarkerType::SevenSegment {
+            start: Pos2::new(0.1, 0.5),
+            end: Pos2::new(0.9, 0.5),
+            bottom: Pos2::new(0.1, 0.6),
+            digits,
+            spacing: 0.,
+            size: 1.,
+        })
+    }
+
+    fn decode(marker: &Marker, samples: &[f32]) -> Value {
+        marker.decode(samples, 0.5, &SegmentPattern::defaults()).0
+    }
+
+    #[test]
+    fn mirrors_segments() {
+        let mut marker = seven_segment(1);
+        marker.mirror_segments = true;
+
+        let samples = digit("abdeg");
+        assert_eq!(marker.oriented(&samples).as_ref(), digit("acdfg"));
+        assert_eq!(decode(&marker, &samples), Value::from(5));
+    }
+
+    #[test]
+    fn reverses_digits() {
+        let mut marker = seven_segment(2);
+        marker.reverse_digits = true;
+
+        let samples = display(&["bc", "abdeg"]);
+        assert_eq!(
+            marker.oriented(&samples).as_ref(),
+            display(&["abdeg", "bc"])
+        );
+        assert_eq!(decode(&marker, &samples), Value::from(21));
+    }
+
+    #[test]
+    fn leaves_samples_as_they_are_by_default() {
+        let marker = seven_segment(2);
+
+        let samples = display(&["bc", "abdeg"]);
+        assert!(matches!(marker.oriented(&samples), Cow::Borrowed(_)));
+        assert_eq!(decode(&marker, &samples), Value::from(12));
+    }
+
+    fn at(x: f32, y: f32) -> Point {
+        Point {
+            pos: Pos2::new(x, y),
+            size: 1.,
+        }
+    }
+
+    /// A grey image with a different shade per pixel, in reading order.
+    fn image(width: usize, height: usize) -> Vec<Color32> {
+        (0..width * height)
+            .map(|idx| Color32::from_gray(idx as u8 * 30))
+            .collect()
+    }
+
+    #[test]
+    fn clamps_to_single_pixel() {
+        for (x, y) in [(-0.5, -0.5), (0., 0.), (1., 1.), (1.5, -2.), (3., 3.)] {
+            assert_eq!(at(x, y).pixel(1, 1), (0, 0), "at {x}, {y}");
+            assert_eq!(at(x, y).sample(&[7], 1, 1), Some(7), "at {x}, {y}");
+        }
+    }
+
+    #[test]
+    fn clamps_to_nearest_edge() {
+        let pixels = (0..6).collect::<Vec<_>>();
+
+        for ((x, y), pixel) in [
+            ((-0.1, -5.), (0, 0)),
+            ((1., 1.), (2, 1)),
+            ((1., 0.2), (2, 0)),
+            ((0.5, 1.), (1, 1)),
+            ((-1., 0.9), (0, 1)),
+            ((5., -1.), (2, 0)),
+            ((0.5, 0.25), (1, 0)),
+        ] {
+            let point = at(x, y);
+            assert_eq!(point.pixel(3, 2), pixel, "at {x}, {y}");
+            assert_eq!(
+                point.sample(&pixels, 3, 2),
+                Some(pixel.1 * 3 + pixel.0),
+                "at {x}, {y}",
+            );
+        }
+    }
+
+    #[test]
+    fn samples_edge_pixels_past_the_edge() {
+        let pixels = image(3, 2);
+
+        for interpolation in Interpolation::ALL {
+            for ((x, y), idx) in [
+                ((-1., -1.), 0),
+                ((1., 1.), 5),
+                ((2., -0.5), 2),
+                ((-0.5, 3.), 3),
+            ] {
+                let ty = MarkerType::Point {
+                    pos: Pos2::new(x, y),
+                    size: 1.,
+                };
+
+                assert_eq!(
+                    ty.sample_with_search(&pixels, 3, 2, 1, interpolation),
+                    [luminance(pixels[idx])],
+                    "at {x}, {y} with {interpolation:?}",
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn samples_nothing_in_empty_images() {
+        assert_eq!(at(0.5, 0.5).sample::<u8>(&[], 0, 0), None);
+
+        let ty = MarkerType::Point {
+            pos: Pos2::new(-1., 2.),
+            size: 1.,
+        };
+        assert_eq!(ty.sample(&[], 0, 0), [0.]);
+        assert_eq!(ty.sample(&[], 3, 2), [0.]);
+    }
+
+    fn bilinear(pixels: &[Color32], width: usize, height: usize, x: f32, y: f32) -> f32 {
+        let ty = MarkerType::Point {
+            pos: Pos2::new(x, y),
+            size: 1.,
+        };
+        ty.sample_with_search(pixels, width, height, 0, Interpolation::Bilinear)[0]
+    }
+
+    #[test]
+    fn bilinear_reads_pixel_centers_exactly() {
+        let pixels = image(4, 2);
+
+        for (idx, &pixel) in pixels.iter().enumerate() {
+            let (x, y) = ((idx % 4) as f32 + 0.5, (idx / 4) as f32 + 0.5);
+            assert_eq!(
+                bilinear(&pixels, 4, 2, x / 4., y / 2.),
+                luminance(pixel),
+                "pixel {idx}",
+            );
+        }
+    }
+
+    #[test]
+    fn bilinear_blends_between_pixels() {
+        let pixels = [Color32::BLACK, Color32::WHITE];
+
+        assert!((bilinear(&pixels, 2, 1, 0.5, 0.5) - 0.5).abs() < 1e-3);
+        assert!((bilinear(&pixels, 2, 1, 0.375, 0.5) - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn bilinear_clamps_at_edges() {
+        let pixels = image(4, 2);
+
+        assert_eq!(bilinear(&pixels, 4, 2, 0., 0.), luminance(pixels[0]));
+        assert_eq!(bilinear(&pixels, 4, 2, 1., 1.), luminance(pixels[7]));
+        assert_eq!(bilinear(&pixels, 4, 2, 1., 0.), luminance(pixels[3]));
+        assert_eq!(bilinear(&pixels, 4, 2, 0., 1.), luminance(pixels[4]));
+
+        let read = |x, y| at(x, y).pixels_read(4, 2, Interpolation::Bilinear);
+        assert_eq!(read(0., 0.), (0, 0, 1, 1));
+        assert_eq!(read(1., 1.), (3, 1, 4, 2));
+        assert_eq!(read(0.5, 0.5), (1, 0, 3, 2));
+    }
+}
@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::marker::{Marker, MarkerType, Output};
+
+/// A single MQTT message to publish.
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// How values are written in payloads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PayloadFormat {
+    /// JSON over multiple lines.
+    #[default]
+    Pretty,
+    /// JSON on a single line.
+    Compact,
+    /// Strings without quotes and nothing for null, otherwise like
+    /// `Compact`.
+    Raw,
+}
+
+impl PayloadFormat {
+    pub const ALL: [Self; 3] = [Self::Pretty, Self::Compact, Self::Raw];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pretty => "pretty",
+            Self::Compact => "compact",
+            Self::Raw => "raw",
+        }
+    }
+
+    pub fn format(self, value: &Value) -> String {
+        match (self, value) {
+            (Self::Pretty, value) => serde_json::to_string_pretty(value).unwrap(),
+            (Self::Raw, Value::String(text)) => text.clone(),
+            (Self::Raw, Value::Null) => String::new(),
+            (_, value) => value.to_string(),
+        }
+    }
+}
+
+/// The value combining a display's hours and minutes into the time
+/// remaining, which is published instead of them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeRemaining {
+    /// The names of the values with the hours and the minutes.
+    pub hour: String,
+    pub minute: String,
+    /// The topic under the topic prefix.
+    pub topic: String,
+    pub unit: TimeUnit,
+}
+
+impl Default for TimeRemaining {
+    fn default() -> Self {
+        Self {
+            hour: "hour".to_owned(),
+            minute: "minute".to_owned(),
+            topic: "time-remaining".to_owned(),
+            unit: TimeUnit::Seconds,
+        }
+    }
+}
+
+impl TimeRemaining {
+    /// Whether the value with the given name is one of the inputs.
+    pub fn reads(&self, name: &str) -> bool {
+        name == self.hour || name == self.minute
+    }
+
+    /// The value to publish for the given number of seconds.
+    pub fn value(&self, seconds: u64) -> Value {
+        match self.unit {
+            TimeUnit::Seconds => seconds.into(),
+            TimeUnit::Minutes => (seconds / 60).into(),
+            TimeUnit::Iso8601 => {
+                let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+                if hours > 0 {
+                    format!("PT{}H{}M", hours, minutes).into()
+                } else {
+                    format!("PT{}M", minutes).into()
+                }
+            }
+        }
+    }
+}
+
+/// What the time remaining is published in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeUnit {
+    #[default]
+    Seconds,
+    Minutes,
+    /// A duration like `PT1H25M`.
+    Iso8601,
+}
+
+impl TimeUnit {
+    pub const ALL: [Self; 3] = [Self::Seconds, Self::Minutes, Self::Iso8601];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Seconds => "seconds",
+            Self::Minutes => "minutes",
+            Self::Iso8601 => "ISO 8601",
+        }
+    }
+
+    /// The unit's symbol, for numbers.
+    pub fn symbol(self) -> Option<&'static str> {
+        match self {
+            Self::Seconds => Some("s"),
+            Self::Minutes => Some("min"),
+            Self::Iso8601 => None,
+        }
+    }
+}
+
+/// The topic a marker's value is published to. The values the time
+/// remaining is read from share its topic.
+pub fn marker_topic(topic_prefix: &str, name: &str, time_remaining: &TimeRemaining) -> String {
+    if time_remaining.reads(name) {
+        format!("{}/{}", topic_prefix, time_remaining.topic)
+    } else {
+        format!("{}/{}", topic_prefix, name)
+    }
+}
+
+/// The messages to publish for the values of the markers with the given
+/// indices, under `topic_prefix`, written as `format`.
+///
+/// The hour and minute values are combined into a single message with the
+/// time remaining, as set by `composite`. Values that couldn't be decoded are
+/// replaced by their marker's [`Marker::unknown`], if set.
+pub fn messages(
+    topic_prefix: &str,
+    markers: &[Marker],
+    values: &[Value],
+    indices: &[usize],
+    format: PayloadFormat,
+    composite: &TimeRemaining,
+) -> Vec<Message> {
+    let mut messages = vec![];
+
+    let time_remaining = time_remaining(markers, values, composite);
+
+    let named = named_values(markers, values);
+
+    let included = named
+        .iter()
+        .filter(|(idx, ..)| indices.contains(idx))
+        .map(|&(_, name, _)| name)
+        .collect::<Vec<_>>();
+
+    // For when hour or minute couldn't be decoded.
+    let unknown_time_remaining = named
+        .iter()
+        .filter(|(_, name, value)| composite.reads(name) && value.is_null())
+        .find_map(|&(idx, ..)| markers[idx].unknown.as_ref());
+
+    let mut values = named
+        .into_iter()
+        .map(|(idx, name, value)| match (value, &markers[idx].unknown) {
+            (Value::Null, Some(unknown)) => (name, unknown),
+            _ => (name, value),
+        })
+        .collect::<HashMap<&str, &Value>>();
+
+    let time_remaining_due = included.iter().any(|name| composite.reads(name));
+
+    if let (Some(_), Some(_)) = (
+        values.remove(composite.hour.as_str()),
+        values.remove(composite.minute.as_str()),
+    ) {
+        let payload = time_remaining
+            .map(|seconds| format.format(&composite.value(seconds)))
+            .or_else(|| unknown_time_remaining.map(|unknown| format.format(unknown)));
+
+        if let (true, Some(payload)) = (time_remaining_due, payload) {
+            messages.push(Message {
+                topic: marker_topic(topic_prefix, &composite.hour, composite),
+                payload,
+            });
+        }
+    }
+
+    for (name, value) in values {
+        if !included.contains(&name) {
+            continue;
+        }
+
+        messages.push(Message {
+            topic: marker_topic(topic_prefix, name, composite),
+            payload: format.format(value),
+        });
+    }
+
+    messages
+}
+
+/// Retained messages on `<topic>/meta` describing what's published on each
+/// of the topics [`messages`] publishes for the markers with the given
+/// indices, for tools that don't know the config.
+pub fn meta_messages(
+    topic_prefix: &str,
+    markers: &[Marker],
+    indices: &[usize],
+    composite: &TimeRemaining,
+) -> Vec<Message> {
+    meta(markers, indices, composite)
+        .into_iter()
+        .map(|(name, meta)| Message {
+            topic: format!("{}/meta", marker_topic(topic_prefix, &name, composite)),
+            payload: Value::Object(meta).to_string(),
+        })
+        .collect()
+}
+
+/// A description of each value [`messages`] publishes for the markers with
+/// the given indices, by the name it's published under: its type, unit and
+/// range.
+pub fn meta(
+    markers: &[Marker],
+    indices: &[usize],
+    composite: &TimeRemaining,
+) -> Vec<(String, Map<String, Value>)> {
+    let named = indices
+        .iter()
+        .filter_map(|&idx| markers.get(idx))
+        .flat_map(|marker| {
+            marker
+                .value_names()
+                .into_iter()
+                .map(move |name| (marker, name))
+        })
+        .collect::<Vec<_>>();
+
+    let time_remaining = [&composite.hour, &composite.minute]
+        .iter()
+        .all(|name| named.iter().any(|(_, other)| other == name));
+
+    let mut meta = named
+        .iter()
+        .filter(|(_, name)| !time_remaining || !composite.reads(name))
+        .map(|&(marker, name)| {
+            let mut meta = value_meta(marker, name);
+            meta.insert("marker".into(), marker.name.clone().into());
+            meta.insert("type".into(), marker.ty.name().into());
+            if let Some(unit) = &marker.unit {
+                meta.insert("unit".into(), unit.clone().into());
+            }
+            if let Some(unknown) = &marker.unknown {
+                meta.insert("unknown".into(), unknown.clone());
+            }
+
+            (name.to_owned(), meta)
+        })
+        .collect::<Vec<_>>();
+
+    if time_remaining {
+        let mut time_remaining = Map::new();
+        time_remaining.insert("type".into(), "time remaining".into());
+        match composite.unit.symbol() {
+            Some(unit) => {
+                time_remaining.insert("value".into(), "integer".into());
+                time_remaining.insert("unit".into(), unit.into());
+                time_remaining.insert("min".into(), 0.into());
+            }
+            None => {
+                time_remaining.insert("value".into(), "string".into());
+            }
+        }
+        meta.push((composite.topic.clone(), time_remaining));
+    }
+
+    meta
+}
+
+/// What kind of value is published under the given name of a marker, and
+/// the range it's in.
+fn value_meta(marker: &Marker, name: &str) -> Map<String, Value> {
+    let mut meta = Map::new();
+
+    let digits = match (&marker.ty, &marker.output) {
+        (MarkerType::Point { .. } | MarkerType::Gpio { .. }, _) => {
+            meta.insert("value".into(), "boolean".into());
+            return meta;
+        }
+        (MarkerType::SevenSegment { digits, .. }, Output::Padded) => {
+            meta.insert("value".into(), "string".into());
+            meta.insert("length".into(), (*digits).into());
+            return meta;
+        }
+        (MarkerType::SevenSegment { digits, .. }, Output::Integer) => *digits,
+        (MarkerType::SevenSegment { .. }, Output::Split { groups }) => groups
+            .iter()
+            .find(|group| group.name == name)
+            .map_or(0, |group| group.digits),
+    };
+
+    // Displays that show anything but digits are published as strings
+    // instead, which the range doesn't cover.
+    meta.insert("value".into(), "integer".into());
+    meta.insert("min".into(), 0.into());
+    meta.insert(
+        "max".into(),
+        (10u64.saturating_pow(digits as u32) - 1).into(),
+    );
+    meta
+}
+
+/// Each value by the name it's published under, with the index of the
+/// marker it's from. Split seven segment displays have a value per digit
+/// group.
+pub fn named_values<'a>(
+    markers: &'a [Marker],
+    values: &'a [Value],
+) -> Vec<(usize, &'a str, &'a Value)> {
+    markers
+        .iter()
+        .zip(values)
+        .enumerate()
+        .flat_map(|(idx, (marker, value))| {
+            marker.value_names().into_iter().map(move |name| {
+                let value = match value {
+                    Value::Object(groups) => groups.get(name).unwrap_or(&Value::Null),
+                    value => value,
+                };
+                (idx, name, value)
+            })
+        })
+        .collect()
+}
+
+/// Seconds remaining in the current cycle, from the hour and minute values
+/// named in `composite`, if both were decoded.
+pub fn time_remaining(
+    markers: &[Marker],
+    values: &[Value],
+    composite: &TimeRemaining,
+) -> Option<u64> {
+    let named = named_values(markers, values);
+    let value = |name: &str| {
+        named
+            .iter()
+            .find(|&&(_, other, _)| other == name)
+            .and_then(|(_, _, value)| value.as_u64())
+    };
+
+    Some((value(&composite.hour)? * 60 + value(&composite.minute)?) * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use emath::pos2;
+    use serde_json::json;
+
+    use super::*;
+    use crate::marker::DigitGroup;
+
+    fn point(name: &str) -> Marker {
+        let mut marker = Marker::new(MarkerType::Point {
+            pos: pos2(0.5, 0.5),
+            size: 1.,
+        });
+        marker.name = name.to_owned();
+        marker
+    }
+
+    /// A display with the hours and minutes remaining, split into groups
+    /// with the given names.
+    fn clock(hour: &str, minute: &str) -> Marker {
+        let mut marker = Marker::new(MarkerType::SevenSegment {
+            start: pos2(0.1, 0.5),
+            end: pos2(0.9, 0.5),
+            bottom: pos2(0.1, 0.6),
+            digits: 3,
+            spacing: 0.,
+            size: 1.,
+        });
+        marker.name = "clock".to_owned();
+        marker.output = Output::Split {
+            groups: vec![
+                DigitGroup {
+                    name: hour.to_owned(),
+                    digits: 1,
+                },
+                DigitGroup {
+                    name: minute.to_owned(),
+                    digits: 2,
+                },
+            ],
+        };
+        marker
+    }
+
+    /// The payload of each message by topic.
+    fn payloads(messages: Vec<Message>) -> HashMap<String, String> {
+        messages
+            .into_iter()
+            .map(|message| (message.topic, message.payload))
+            .collect()
+    }
+
+    #[test]
+    fn formats_payloads() {
+        let value = json!({ "a": 1 });
+        assert_eq!(PayloadFormat::Pretty.format(&value), "{\n  \"a\": 1\n}");
+        assert_eq!(PayloadFormat::Compact.format(&value), "{\"a\":1}");
+        assert_eq!(PayloadFormat::Raw.format(&value), "{\"a\":1}");
+
+        let text = json!("on");
+        assert_eq!(PayloadFormat::Pretty.format(&text), "\"on\"");
+        assert_eq!(PayloadFormat::Compact.format(&text), "\"on\"");
+        assert_eq!(PayloadFormat::Raw.format(&text), "on");
+
+        assert_eq!(PayloadFormat::Compact.format(&Value::Null), "null");
+        assert_eq!(PayloadFormat::Raw.format(&Value::Null), "");
+        assert_eq!(PayloadFormat::Raw.format(&json!(42)), "42");
+    }
+
+    #[test]
+    fn names_split_values_by_group() {
+        let markers = [clock("hour", "minute"), point("door")];
+        let values = [json!({ "hour": 1, "minute": 5 }), json!(true)];
+
+        assert_eq!(
+            named_values(&markers, &values),
+            [
+                (0, "hour", &json!(1)),
+                (0, "minute", &json!(5)),
+                (1, "door", &json!(true)),
+            ],
+        );
+    }
+
+    #[test]
+    fn names_undecoded_split_values_as_null() {
+        let markers = [clock("hour", "minute")];
+        let values = [Value::Null];
+
+        assert_eq!(
+            named_values(&markers, &values),
+            [(0, "hour", &Value::Null), (0, "minute", &Value::Null)],
+        );
+    }
+
+    #[test]
+    fn combines_hours_and_minutes() {
+        let markers = [clock("hour", "minute"), point("door")];
+        let values = [json!({ "hour": 1, "minute": 5 }), json!(true)];
+        let composite = TimeRemaining::default();
+
+        let messages = messages(
+            "laundry",
+            &markers,
+            &values,
+            &[0, 1],
+            PayloadFormat::Compact,
+            &composite,
+        );
+
+        assert_eq!(
+            payloads(messages),
+            HashMap::from([
+                ("laundry/time-remaining".to_owned(), "3900".to_owned()),
+                ("laundry/door".to_owned(), "true".to_owned()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn leaves_out_values_not_due() {
+        let markers = [clock("hour", "minute"), point("door")];
+        let values = [json!({ "hour": 1, "minute": 5 }), json!(true)];
+        let composite = TimeRemaining::default();
+
+        let messages = messages(
+            "laundry",
+            &markers,
+            &values,
+            &[1],
+            PayloadFormat::Compact,
+            &composite,
+        );
+
+        assert_eq!(
+            payloads(messages),
+            HashMap::from([("laundry/door".to_owned(), "true".to_owned())]),
+        );
+    }
+
+    #[test]
+    fn publishes_unknown_for_undecoded_values() {
+        let mut door = point("door");
+        door.unknown = Some(json!("unknown"));
+        let mut clock = clock("hour", "minute");
+        clock.unknown = Some(json!("unknown"));
+
+        let markers = [clock, door];
+        let values = [json!({ "hour": null, "minute": 5 }), Value::Null];
+        let composite = TimeRemaining::default();
+
+        let messages = messages(
+            "laundry",
+            &markers,
+            &values,
+            &[0, 1],
+            PayloadFormat::Raw,
+            &composite,
+        );
+
+        assert_eq!(
+            payloads(messages),
+            HashMap::from([
+                ("laundry/time-remaining".to_owned(), "unknown".to_owned()),
+                ("laundry/door".to_owned(), "unknown".to_owned()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn publishes_undecoded_values_as_null_without_unknown() {
+        let markers = [clock("hour", "minute"), point("door")];
+        let values = [json!({ "hour": null, "minute": 5 }), Value::Null];
+        let composite = TimeRemaining::default();
+
+        let published = |format| {
+            payloads(messages(
+                "laundry",
+                &markers,
+                &values,
+                &[0, 1],
+                format,
+                &composite,
+            ))
+        };
+
+        // Only the time remaining, which can't be worked out, is left out.
+        assert_eq!(
+            published(PayloadFormat::Compact),
+            HashMap::from([("laundry/door".to_owned(), "null".to_owned())]),
+        );
+        // Raw payloads have nothing for null.
+        assert_eq!(
+            published(PayloadFormat::Raw),
+            HashMap::from([("laundry/door".to_owned(), String::new())]),
+        );
+    }
+
+    fn composite(unit: TimeUnit) -> TimeRemaining {
+        TimeRemaining {
+            unit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn time_remaining_in_seconds() {
+        let composite = composite(TimeUnit::Seconds);
+        assert_eq!(composite.value(0), json!(0));
+        assert_eq!(composite.value(3900), json!(3900));
+    }
+
+    #[test]
+    fn time_remaining_in_whole_minutes() {
+        let composite = composite(TimeUnit::Minutes);
+        assert_eq!(composite.value(0), json!(0));
+        assert_eq!(composite.value(59), json!(0));
+        assert_eq!(composite.value(3900), json!(65));
+        assert_eq!(composite.value(3959), json!(65));
+    }
+
+    #[test]
+    fn time_remaining_as_iso_8601() {
+        let composite = composite(TimeUnit::Iso8601);
+        assert_eq!(composite.value(0), json!("PT0M"));
+        assert_eq!(composite.value(300), json!("PT5M"));
+        assert_eq!(composite.value(3600), json!("PT1H0M"));
+        assert_eq!(composite.value(3900), json!("PT1H5M"));
+        assert_eq!(composite.value(3959), json!("PT1H5M"));
+    }
+
+    #[test]
+    fn topics_with_custom_names() {
+        let composite = TimeRemaining {
+            hour: "uren".to_owned(),
+            minute: "minuten".to_owned(),
+            topic: "resterend".to_owned(),
+            unit: TimeUnit::Minutes,
+        };
+
+        assert_eq!(
+            marker_topic("wasmachine", "uren", &composite),
+            "wasmachine/resterend"
+        );
+        assert_eq!(
+            marker_topic("wasmachine", "minuten", &composite),
+            "wasmachine/resterend"
+        );
+        assert_eq!(
+            marker_topic("wasmachine", "hour", &composite),
+            "wasmachine/hour"
+        );
+        assert_eq!(
+            marker_topic("wasmachine", "deur", &composite),
+            "wasmachine/deur"
+        );
+    }
+
+    #[test]
+    fn combines_custom_names() {
+        let markers = [clock("uren", "minuten"), point("hour")];
+        let values = [json!({ "uren": 2, "minuten": 30 }), json!(false)];
+        let composite = TimeRemaining {
+            hour: "uren".to_owned(),
+            minute: "minuten".to_owned(),
+            topic: "resterend".to_owned(),
+            unit: TimeUnit::Iso8601,
+        };
+
+        assert_eq!(time_remaining(&markers, &values, &composite), Some(9000));
+
+        let messages = messages(
+            "wasmachine",
+            &markers,
+            &values,
+            &[0, 1],
+            PayloadFormat::Raw,
+            &composite,
+        );
+
+        assert_eq!(
+            payloads(messages),
+            HashMap::from([
+                ("wasmachine/resterend".to_owned(), "PT2H30M".to_owned()),
+                ("wasmachine/hour".to_owned(), "false".to_owned()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn publishes_unknown_for_undecoded_custom_names() {
+        let mut clock = clock("uren", "minuten");
+        clock.unknown = Some(json!("onbekend"));
+
+        let markers = [clock];
+        let values = [json!({ "uren": 1, "minuten": null })];
+        let composite = TimeRemaining {
+            hour: "uren".to_owned(),
+            minute: "minuten".to_owned(),
+            topic: "resterend".to_owned(),
+            unit: TimeUnit::Minutes,
+        };
+
+        assert_eq!(time_remaining(&markers, &values, &composite), None);
+
+        let messages = messages(
+            "wasmachine",
+            &markers,
+            &values,
+            &[0],
+            PayloadFormat::Compact,
+            &composite,
+        );
+
+        assert_eq!(
+            payloads(messages),
+            HashMap::from([("wasmachine/resterend".to_owned(), "\"onbekend\"".to_owned())]),
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Luminance statistics over runs of pixels, for regions that cover many of
+//! them. With the `simd` feature these work on eight values at a time.
+
+#[cfg(not(feature = "simd"))]
+pub use self::scalar::{edges, luminances, sum};
+#[cfg(feature = "simd")]
+pub use self::simd::{edges, luminances, sum};
+
+#[cfg(any(not(feature = "simd"), test))]
+mod scalar {
+    use ecolor::Color32;
+
+    use crate::marker::luminance;
+
+    /// Replaces what's in `out` with the luminance of each pixel, as
+    /// [`luminance`](crate::luminance) gives it.
+    pub fn luminances(pixels: &[Color32], out: &mut Vec<f32>) {
+        out.clear();
+        out.extend(pixels.iter().map(|&pixel| luminance(pixel)));
+    }
+
+    /// The sum of the values.
+    pub fn sum(values: &[f32]) -> f32 {
+        values.iter().sum()
+    }
+
+    /// The sum of the differences between neighbouring values, which is
+    /// higher for sharper images.
+    pub fn edges(values: &[f32]) -> f32 {
+        values
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .sum()
+    }
+}
+
+#[cfg(feature = "simd")]
+mod simd {
+    use ecolor::Color32;
+    use wide::{f32x8, i32x8, CmpEq};
+
+    use crate::marker::luminance;
+
+    const LANES: usize = 8;
+
+    pub fn luminances(pixels: &[Color32], out: &mut Vec<f32>) {
+        out.clear();
+        out.reserve(pixels.len());
+
+        let chunks = pixels.chunks_exact(LANES);
+        let rest = chunks.remainder();
+        let byte = i32x8::splat(0xff);
+
+        for chunk in chunks {
+            // Each pixel as one word, with red in the lowest byte.
+            let chunk = <[Color32; LANES]>::try_from(chunk).unwrap();
+            let words = i32x8::from(chunk.map(|pixel| i32::from_le_bytes(pixel.to_array())));
+
+            // The stored bytes are premultiplied, which only matches the
+            // unmultiplied ones for opaque pixels, as in every frame.
+            let alpha: i32x8 = (words >> 24_i32) & byte;
+            if alpha.cmp_eq(byte).move_mask() != 0xff {
+                out.extend(chunk.iter().map(|&pixel| luminance(pixel)));
+                continue;
+            }
+
+            let channel = |shift: i32| {
+                let channel: i32x8 = (words >> shift) & byte;
+                channel.round_float()
+            };
+            let lum = channel(0) * f32x8::splat(0.2126 / 255.)
+                + channel(8) * f32x8::splat(0.7152 / 255.)
+                + channel(16) * f32x8::splat(0.0722 / 255.);
+            out.extend_from_slice(&lum.to_array());
+        }
+
+        out.extend(rest.iter().map(|&pixel| luminance(pixel)));
+    }
+
+    pub fn sum(values: &[f32]) -> f32 {
+        let chunks = values.chunks_exact(LANES);
+        let rest = chunks.remainder().iter().sum::<f32>();
+
+        chunks
+            .map(|chunk| f32x8::from(<[f32; LANES]>::try_from(chunk).unwrap()))
+            .fold(f32x8::ZERO, |acc, lanes| acc + lanes)
+            .reduce_add()
+            + rest
+    }
+
+    pub fn edges(values: &[f32]) -> f32 {
+        if values.len() < 2 {
+            return 0.;
+        }
+
+        // Each value against the next one.
+        let (left, right) = (&values[..values.len() - 1], &values[1..]);
+        let lanes = left.len() / LANES * LANES;
+
+        let simd = left[..lanes]
+            .chunks_exact(LANES)
+            .zip(right[..lanes].chunks_exact(LANES))
+            .map(|(a, b)| {
+                let a = f32x8::from(<[f32; LANES]>::try_from(a).unwrap());
+                let b = f32x8::from(<[f32; LANES]>::try_from(b).unwrap());
+                (b - a).abs()
+            })
+            .fold(f32x8::ZERO, |acc, lanes| acc + lanes)
+            .reduce_add();
+
+        let rest = left[lanes..]
+            .iter()
+            .zip(&right[lanes..])
+            .map(|(a, b)| (b - a).abs())
+            .sum::<f32>();
+
+        simd + rest
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod tests {
+    use ecolor::Color32;
+
+    use super::{scalar, simd};
+
+    const LENGTHS: [usize; 6] = [0, 1, 7, 8, 9, 17];
+
+    fn pixels(len: usize) -> Vec<Color32> {
+        (0..len)
+            .map(|i| {
+                let i = i as u8;
+                Color32::from_rgb(i.wrapping_mul(37), i.wrapping_mul(91), i.wrapping_mul(13))
+            })
+            .collect()
+    }
+
+    fn values(len: usize) -> Vec<f32> {
+        (0..len).map(|i| (i * 7 % 11) as f32 / 10.).collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn luminances_match_scalar() {
+        for len in LENGTHS {
+            let mut pixels = pixels(len);
+            let (mut a, mut b) = (vec![], vec![]);
+
+            simd::luminances(&pixels, &mut a);
+            scalar::luminances(&pixels, &mut b);
+            assert_eq!(a.len(), len);
+            assert_eq!(b.len(), len);
+            a.iter().zip(&b).for_each(|(&a, &b)| assert_close(a, b));
+
+            // A translucent pixel takes its chunk off the fast path.
+            if let Some(first) = pixels.first_mut() {
+                *first = Color32::from_rgba_premultiplied(40, 80, 20, 128);
+            }
+            simd::luminances(&pixels, &mut a);
+            scalar::luminances(&pixels, &mut b);
+            a.iter().zip(&b).for_each(|(&a, &b)| assert_close(a, b));
+        }
+    }
+
+    #[test]
+    fn sum_matches_scalar() {
+        for len in LENGTHS {
+            let values = values(len);
+            assert_close(simd::sum(&values), scalar::sum(&values));
+        }
+    }
+
+    #[test]
+    fn edges_match_scalar() {
+        for len in LENGTHS {
+            let values = values(len);
+            assert_close(simd::edges(&values), scalar::edges(&values));
+        }
+    }
+}
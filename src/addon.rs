@@ -0,0 +1,66 @@
+//! Running as a Home Assistant add-on, where the config comes from the
+//! add-on options and the MQTT broker from the supervisor.
+
+use std::{error::Error, fs, time::Duration};
+
+use serde::Deserialize;
+
+use crate::config::{Config, MqttConfig};
+
+/// Where the supervisor puts the add-on's options, which use the same
+/// structure as the config file.
+const OPTIONS_PATH: &str = "/data/options.json";
+
+const SUPERVISOR_MQTT_URL: &str = "http://supervisor/services/mqtt";
+
+/// Home Assistant's own API, proxied by the supervisor.
+pub const SUPERVISOR_CORE_URL: &str = "http://supervisor/core";
+
+pub fn load_config() -> Result<Config, Box<dyn Error>> {
+    let mut config: Config = serde_json::from_str(&fs::read_to_string(OPTIONS_PATH)?)?;
+    config.migrate();
+
+    if config.mqtt.host.is_empty() {
+        let remote_config = config.mqtt.remote_config;
+        config.mqtt = supervisor_mqtt()?;
+        config.mqtt.remote_config = remote_config;
+    }
+
+    Ok(config)
+}
+
+#[derive(Deserialize)]
+struct ServiceResponse {
+    data: MqttService,
+}
+
+#[derive(Deserialize)]
+struct MqttService {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// The broker provided by the supervisor, such as the Mosquitto add-on.
+fn supervisor_mqtt() -> Result<MqttConfig, Box<dyn Error>> {
+    let token = std::env::var("SUPERVISOR_TOKEN")
+        .map_err(|_| "SUPERVISOR_TOKEN isn't set, is this running as an add-on?")?;
+
+    let response = ureq::get(SUPERVISOR_MQTT_URL)
+        .set("Authorization", &format!("Bearer {}", token))
+        .timeout(Duration::from_secs(10))
+        .call()?
+        .into_string()?;
+    let response: ServiceResponse = serde_json::from_str(&response)?;
+
+    let service = response.data;
+
+    Ok(MqttConfig {
+        host: service.host,
+        port: service.port,
+        username: service.username,
+        password: service.password,
+        ..Default::default()
+    })
+}
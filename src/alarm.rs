@@ -0,0 +1,82 @@
+//! A local chime and window flash when a cycle finishes, for when the
+//! computer running this is closer than the phone.
+
+use std::{
+    error::Error,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alarm {
+    /// A sound file to play, or the terminal bell if unset.
+    #[serde(default)]
+    pub sound: Option<PathBuf>,
+    /// Flash the window to get attention.
+    #[serde(default = "default_flash")]
+    pub flash: bool,
+}
+
+impl Default for Alarm {
+    fn default() -> Self {
+        Self {
+            sound: None,
+            flash: default_flash(),
+        }
+    }
+}
+
+fn default_flash() -> bool {
+    true
+}
+
+impl Alarm {
+    /// Plays the sound, blocking until it's done.
+    pub fn play(&self) -> Result<(), Box<dyn Error>> {
+        match &self.sound {
+            Some(path) => play_file(path),
+            None => {
+                io::stderr().write_all(b"\x07")?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Plays a sound file with whatever the platform has for it.
+fn play_file(path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("afplay");
+        command.arg(path);
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command"]).arg(format!(
+            "(New-Object Media.SoundPlayer '{}').PlaySync()",
+            path.display().to_string().replace('\'', "''")
+        ));
+        command
+    } else {
+        // PulseAudio or PipeWire, falling back to ALSA.
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", r#"paplay "$0" || aplay -q "$0""#])
+            .arg(path);
+        command
+    };
+
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    if !status.success() {
+        return Err(format!("playing {} failed: {}", path.display(), status).into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,379 @@
+//! A small HTTP API exposing the current state, for consumers that can't
+//! speak MQTT and for debugging, and a calibration page for moving markers
+//! from a browser.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+#[cfg(feature = "http")]
+use std::{io::Cursor, thread};
+
+use epaint::{Color32, ColorImage};
+use image::{codecs::jpeg::JpegEncoder, RgbImage};
+#[cfg(feature = "http")]
+use serde_json::json;
+use serde_json::{Map, Value};
+#[cfg(feature = "http")]
+use tiny_http::{Header, Method, Request, Response, Server};
+#[cfg(feature = "http")]
+use tracing::{error, info};
+
+use laundry_machine_core::pixel_at;
+
+#[cfg(feature = "http")]
+use crate::remote;
+use crate::{
+    backend::Timings,
+    config::{Config, Profile},
+    recording::crop_rect,
+};
+
+/// The latest state of the backend, kept up to date as it runs.
+// Most of it is only read by the GUI, the terminal UI and the HTTP API.
+#[cfg_attr(
+    not(any(feature = "gui", feature = "tui", feature = "http")),
+    allow(dead_code)
+)]
+#[derive(Default)]
+pub struct Status {
+    pub config: Config,
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    pub refresh_rate: Duration,
+    pub frame: Option<ColorImage>,
+    pub frame_time: Option<Instant>,
+    /// When the latest frame was captured, as read from the camera's
+    /// timestamp.
+    pub frame_captured: Option<SystemTime>,
+    /// When a value was last published to MQTT.
+    pub publish_time: Option<Instant>,
+    /// Luminance at each point of each marker in the latest frame.
+    pub samples: Vec<Vec<f32>>,
+    pub values: Vec<Value>,
+    pub mqtt_connected: bool,
+    /// Topic prefixes of the profiles that are paused.
+    pub paused: HashSet<String>,
+    /// Whether each person is home, by name, as far as presence topics
+    /// have said.
+    pub home: HashMap<String, bool>,
+    /// How long each stage of the latest sample took.
+    pub timings: Option<Timings>,
+}
+
+pub type SharedStatus = Arc<Mutex<Status>>;
+
+/// Serves the API on `addr` from a background thread, passing config changes
+/// posted to `/api/config` to `on_change` once they've been checked.
+#[cfg(feature = "http")]
+pub fn serve(
+    addr: &str,
+    status: SharedStatus,
+    on_change: impl Fn(Vec<u8>) + Send + 'static,
+) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(addr).map_err(|e| e.to_string())?;
+    info!("Serving HTTP API on http://{}", addr);
+
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let response = if *request.method() == Method::Post && request.url() == "/api/config" {
+                match change_config(&mut request, &status.lock().unwrap().config) {
+                    Ok(payload) => {
+                        on_change(payload);
+                        Response::from_string("accepted").with_status_code(202)
+                    }
+                    Err(e) => Response::from_string(e).with_status_code(400),
+                }
+            } else {
+                handle(&request, &status.lock().unwrap())
+            };
+
+            if let Err(e) = request.respond(response) {
+                error!("Error responding to HTTP request: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+pub fn serve(
+    _: &str,
+    _: SharedStatus,
+    _: impl Fn(Vec<u8>) + Send + 'static,
+) -> Result<(), Box<dyn Error>> {
+    Err("built without the http feature".into())
+}
+
+#[cfg(feature = "http")]
+fn handle(request: &Request, status: &Status) -> Response<Cursor<Vec<u8>>> {
+    if *request.method() != Method::Get {
+        return Response::from_string("method not allowed").with_status_code(405);
+    }
+
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+
+    // How old the last frame and publish may be, defaulting to a few sample
+    // intervals.
+    let max_age = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("max_age="))
+        .and_then(|secs| secs.parse().ok())
+        .map_or(
+            status.config.refresh_rate(status.refresh_rate) * 4,
+            Duration::from_secs,
+        );
+
+    match path {
+        "/" | "/calibrate" => Response::from_string(include_str!("calibrate.html"))
+            .with_header(header("text/html; charset=utf-8")),
+        "/api/values" => json_response(&values(status)),
+        "/api/markers" => json_response(&json!(status.config.profile().markers)),
+        "/api/health" => {
+            let (healthy, health) = health(status, max_age);
+            json_response(&health).with_status_code(if healthy { 200 } else { 503 })
+        }
+        // For container health probes, which only look at the status code.
+        "/healthz" => match health(status, max_age) {
+            (true, _) => Response::from_string("ok"),
+            (false, _) => Response::from_string("unhealthy").with_status_code(503),
+        },
+        "/api/frame.jpg" => match status.frame.as_ref().map(|frame| {
+            let overlay = Overlay {
+                profile: status.config.profile(),
+                samples: &status.samples,
+                values: &status.values,
+            };
+            frame_jpeg(frame, Some(overlay))
+        }) {
+            Some(Ok(jpeg)) => Response::from_data(jpeg).with_header(header("image/jpeg")),
+            Some(Err(e)) => Response::from_string(e.to_string()).with_status_code(500),
+            None => Response::from_string("no frame yet").with_status_code(404),
+        },
+        _ => Response::from_string("not found").with_status_code(404),
+    }
+}
+
+/// Reads a config change in the same format as remote config messages, and
+/// checks that it applies to the current config.
+#[cfg(feature = "http")]
+fn change_config(request: &mut Request, config: &Config) -> Result<Vec<u8>, String> {
+    let mut payload = vec![];
+    request
+        .as_reader()
+        .read_to_end(&mut payload)
+        .map_err(|e| e.to_string())?;
+
+    remote::apply(&mut config.clone(), &payload)?;
+    Ok(payload)
+}
+
+/// Decoded values of the active profile's markers, by name.
+pub fn values(status: &Status) -> Value {
+    status
+        .config
+        .profile()
+        .markers
+        .iter()
+        .zip(&status.values)
+        .map(|(marker, value)| (marker.name.clone(), value.clone()))
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+/// Healthy while connected to MQTT, and frames keep coming in and values
+/// keep being published.
+#[cfg(feature = "http")]
+fn health(status: &Status, max_age: Duration) -> (bool, Value) {
+    // A frame from a caching proxy arrives long after it was captured.
+    let frame_age = status.frame_time.map(|time| {
+        let age = time.elapsed();
+        status
+            .frame_captured
+            .and_then(|time| time.elapsed().ok())
+            .map_or(age, |captured| age.max(captured))
+    });
+    let publish_age = status.publish_time.map(|time| time.elapsed());
+    let recent = |age: Option<Duration>| age.is_some_and(|age| age < max_age);
+    let paused = status
+        .paused
+        .contains(&status.config.profile().topic_prefix);
+    // Nothing is sampled or published while paused.
+    // Unchanged values are only published with the heartbeat.
+    let publish_max_age = status.config.heartbeat_secs.map_or(max_age, |secs| {
+        max_age.max(Duration::from_secs(secs) + status.config.refresh_rate(status.refresh_rate))
+    });
+    let recent_publish = publish_age.is_some_and(|age| age < publish_max_age);
+    let healthy = status.mqtt_connected && (paused || recent(frame_age) && recent_publish);
+
+    let health = json!({
+        "healthy": healthy,
+        "mqtt_connected": status.mqtt_connected,
+        "paused": paused,
+        "frame_age_secs": frame_age.map(|age| age.as_secs_f64()),
+        "publish_age_secs": publish_age.map(|age| age.as_secs_f64()),
+    });
+
+    (healthy, health)
+}
+
+/// What to draw over a frame.
+pub struct Overlay<'a> {
+    pub profile: &'a Profile,
+    /// Luminance at each point of each marker.
+    pub samples: &'a [Vec<f32>],
+    pub values: &'a [Value],
+}
+
+/// The frame as a JPEG, with an optional overlay of a box around each
+/// sampled point that's green if it was lit and red if it wasn't, and each
+/// marker's value above it.
+pub fn frame_jpeg(frame: &ColorImage, overlay: Option<Overlay>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let [width, height] = frame.size;
+    let pixel = |x: u32, y: u32| frame.pixels[y as usize * width + x as usize];
+
+    let mut image = RgbImage::from_fn(width as u32, height as u32, |x, y| {
+        let [r, g, b, _] = pixel(x, y).to_srgba_unmultiplied();
+        image::Rgb([r, g, b])
+    });
+
+    if let Some(Overlay {
+        profile,
+        samples,
+        values,
+    }) = overlay
+    {
+        let scale = (height as i64 / 240).max(1);
+
+        for (idx, marker) in profile.markers.iter().enumerate() {
+            let samples = samples.get(idx);
+
+            for (n, point) in marker.ty.get_points().into_iter().enumerate() {
+                let lit = samples
+                    .and_then(|samples| samples.get(n))
+                    .map(|&sample| sample > marker.threshold_or(profile.luminance_threshold));
+
+                let color = match lit {
+                    Some(true) => Color32::GREEN,
+                    Some(false) => Color32::RED,
+                    None => Color32::YELLOW,
+                };
+
+                let (x, y) = pixel_at(point.pos, width, height);
+                let radius = ((point.size * width as f32 / 2.) as i64).max(2);
+
+                draw_box(&mut image, x, y, radius, color);
+            }
+
+            let text = match values.get(idx) {
+                Some(Value::Bool(value)) => u8::from(*value).to_string(),
+                Some(Value::String(value)) => value.clone(),
+                Some(Value::Null) | None => continue,
+                Some(value) => value.to_string(),
+            };
+
+            if let Some((x, y, ..)) = crop_rect(marker, width as u32, height as u32) {
+                let y = y as i64 - (GLYPH_HEIGHT + 2) * scale;
+                draw_text(&mut image, x as i64, y, scale, &text);
+            }
+        }
+    }
+
+    let mut jpeg = vec![];
+    JpegEncoder::new_with_quality(&mut jpeg, 85).encode_image(&image)?;
+    Ok(jpeg)
+}
+
+const GLYPH_WIDTH: i64 = 3;
+const GLYPH_HEIGHT: i64 = 5;
+
+/// Rows of a 3×5 glyph, most significant bit on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        _ => [0; 5],
+    }
+}
+
+/// Draws white text on a black background, with only digits and a few
+/// symbols being legible.
+fn draw_text(image: &mut RgbImage, x: i64, y: i64, scale: i64, text: &str) {
+    let advance = (GLYPH_WIDTH + 1) * scale;
+    let width = advance * text.chars().count() as i64 + scale;
+    let height = (GLYPH_HEIGHT + 2) * scale;
+
+    let mut put = |px: i64, py: i64, color: image::Rgb<u8>| {
+        if (0..image.width() as i64).contains(&px) && (0..image.height() as i64).contains(&py) {
+            image.put_pixel(px as u32, py as u32, color);
+        }
+    };
+
+    for py in y..y + height {
+        for px in x..x + width {
+            put(px, py, image::Rgb([0, 0, 0]));
+        }
+    }
+
+    for (n, c) in text.chars().enumerate() {
+        let left = x + scale + advance * n as i64;
+
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        put(
+                            left + col * scale + dx,
+                            y + scale + row as i64 * scale + dy,
+                            image::Rgb([255, 255, 255]),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn draw_box(image: &mut RgbImage, x: i64, y: i64, radius: i64, color: Color32) {
+    let color = image::Rgb([color.r(), color.g(), color.b()]);
+
+    for d in -radius..=radius {
+        for (px, py) in [
+            (x + d, y - radius),
+            (x + d, y + radius),
+            (x - radius, y + d),
+            (x + radius, y + d),
+        ] {
+            if (0..image.width() as i64).contains(&px) && (0..image.height() as i64).contains(&py) {
+                image.put_pixel(px as u32, py as u32, color);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn json_response(value: &Value) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(value.to_string()).with_header(header("application/json"))
+}
+
+#[cfg(feature = "http")]
+fn header(content_type: &str) -> Header {
+    Header::from_bytes("Content-Type", content_type).expect("valid header")
+}
@@ -0,0 +1,249 @@
+//! Listening to the machine through a microphone, for machines whose display
+//! can't be read but whose buzzer is loud.
+
+use std::{
+    error::Error,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+#[cfg(feature = "audio")]
+use std::{f32::consts::TAU, thread};
+
+#[cfg(feature = "audio")]
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    SampleFormat, Stream,
+};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "audio")]
+use tracing::warn;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Audio {
+    /// Name of the input device, or the default one.
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Frequencies to listen to, in Hz, which leave out the rumble of the
+    /// drum or voices in the room.
+    pub low_hz: f32,
+    pub high_hz: f32,
+    /// Level from 0 to 1 above which it counts as loud.
+    pub threshold: f32,
+    /// Seconds to stay loud after the level drops, so that the gaps between
+    /// beeps don't count as quiet.
+    pub hold_secs: f32,
+    /// Lengths in ms of the alternating beeps and gaps the machine plays when
+    /// it finishes, as recorded in training, or empty to not listen for it.
+    #[serde(default)]
+    pub pattern: Vec<u32>,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self {
+            device: None,
+            low_hz: 1500.,
+            high_hz: 5000.,
+            threshold: 0.05,
+            hold_secs: 2.,
+            pattern: vec![],
+        }
+    }
+}
+
+/// How long each reported level is measured over.
+pub const BLOCK: Duration = Duration::from_millis(100);
+
+/// Keeps the microphone open until it's dropped.
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+pub struct Listener {
+    _stop: mpsc::Sender<()>,
+}
+
+/// Opens the microphone, reporting the level in the band every [`BLOCK`].
+#[cfg(feature = "audio")]
+pub fn listen(
+    audio: &Audio,
+    report: impl FnMut(f32) + Send + 'static,
+) -> Result<Listener, Box<dyn Error>> {
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    let (opened_tx, opened_rx) = mpsc::channel();
+    let audio = audio.clone();
+
+    // Streams can't be sent between threads on every platform, so this one
+    // stays on its own.
+    thread::spawn(move || match open(&audio, report) {
+        Ok(stream) => {
+            opened_tx.send(Ok(())).ok();
+            stop_rx.recv().ok();
+            drop(stream);
+        }
+        Err(e) => {
+            opened_tx.send(Err(e.to_string())).ok();
+        }
+    });
+
+    opened_rx
+        .recv()
+        .map_err(|_| "audio thread exited")?
+        .map_err(|e| e.into())
+        .map(|()| Listener { _stop: stop_tx })
+}
+
+#[cfg(not(feature = "audio"))]
+pub fn listen(_: &Audio, _: impl FnMut(f32) + Send + 'static) -> Result<Listener, Box<dyn Error>> {
+    Err("built without the audio feature".into())
+}
+
+#[cfg(feature = "audio")]
+fn open(audio: &Audio, report: impl FnMut(f32) + Send + 'static) -> Result<Stream, Box<dyn Error>> {
+    let host = cpal::default_host();
+    let device = match &audio.device {
+        Some(name) => host
+            .input_devices()?
+            .find(|device| device.name().is_ok_and(|own| &own == name))
+            .ok_or_else(|| format!("no input device {}", name))?,
+        None => host
+            .default_input_device()
+            .ok_or("no default input device")?,
+    };
+
+    let config = device.default_input_config()?;
+    let format = config.sample_format();
+    let mut band = Band::new(
+        audio,
+        config.sample_rate().0 as f32,
+        config.channels() as usize,
+        report,
+    );
+    let config = config.into();
+    let on_error = |e| warn!("Error reading from microphone: {}", e);
+
+    let stream = match format {
+        SampleFormat::F32 => device.build_input_stream(
+            &config,
+            move |data: &[f32], _: &_| band.feed(data.iter().copied()),
+            on_error,
+            None,
+        )?,
+        SampleFormat::I16 => device.build_input_stream(
+            &config,
+            move |data: &[i16], _: &_| {
+                band.feed(data.iter().map(|&sample| sample as f32 / i16::MAX as f32))
+            },
+            on_error,
+            None,
+        )?,
+        SampleFormat::U16 => device.build_input_stream(
+            &config,
+            move |data: &[u16], _: &_| {
+                band.feed(data.iter().map(|&sample| sample as f32 / 32768. - 1.))
+            },
+            on_error,
+            None,
+        )?,
+        format => return Err(format!("unsupported sample format {:?}", format).into()),
+    };
+
+    stream.play()?;
+    Ok(stream)
+}
+
+/// A band-pass filter out of a high-pass and a low-pass one, and the RMS
+/// level of what passes over each block.
+#[cfg(feature = "audio")]
+struct Band<F> {
+    /// Filter coefficients.
+    high_pass: f32,
+    low_pass: f32,
+    previous_input: f32,
+    high_passed: f32,
+    low_passed: f32,
+    /// Interleaved channels are mixed down.
+    channels: usize,
+    channel: usize,
+    mixed: f32,
+    squares: f32,
+    frames: usize,
+    block: usize,
+    report: F,
+}
+
+#[cfg(feature = "audio")]
+impl<F: FnMut(f32)> Band<F> {
+    fn new(audio: &Audio, sample_rate: f32, channels: usize, report: F) -> Self {
+        let dt = 1. / sample_rate;
+        let rc = |hz: f32| 1. / (TAU * hz.max(1.));
+
+        Self {
+            high_pass: rc(audio.low_hz) / (rc(audio.low_hz) + dt),
+            low_pass: dt / (rc(audio.high_hz) + dt),
+            previous_input: 0.,
+            high_passed: 0.,
+            low_passed: 0.,
+            channels: channels.max(1),
+            channel: 0,
+            mixed: 0.,
+            squares: 0.,
+            frames: 0,
+            block: ((sample_rate * BLOCK.as_secs_f32()) as usize).max(1),
+            report,
+        }
+    }
+
+    fn feed(&mut self, samples: impl Iterator<Item = f32>) {
+        for sample in samples {
+            self.mixed += sample;
+            self.channel += 1;
+            if self.channel < self.channels {
+                continue;
+            }
+
+            let input = self.mixed / self.channels as f32;
+            self.mixed = 0.;
+            self.channel = 0;
+
+            self.high_passed = self.high_pass * (self.high_passed + input - self.previous_input);
+            self.previous_input = input;
+            self.low_passed += self.low_pass * (self.high_passed - self.low_passed);
+
+            self.squares += self.low_passed * self.low_passed;
+            self.frames += 1;
+
+            if self.frames == self.block {
+                (self.report)((self.squares / self.frames as f32).sqrt());
+                self.squares = 0.;
+                self.frames = 0;
+            }
+        }
+    }
+}
+
+/// Follows a single profile's levels.
+#[derive(Default)]
+pub struct Detector {
+    loud: bool,
+    last_loud: Option<Instant>,
+}
+
+impl Detector {
+    /// Takes in the latest level, returning the new state if it flipped.
+    pub fn update(&mut self, audio: &Audio, level: f32) -> Option<bool> {
+        let now = Instant::now();
+        if level > audio.threshold {
+            self.last_loud = Some(now);
+        }
+
+        let hold = Duration::from_secs_f32(audio.hold_secs.max(0.));
+        let loud = self.last_loud.is_some_and(|time| now - time <= hold);
+
+        (loud != self.loud).then(|| {
+            self.loud = loud;
+            loud
+        })
+    }
+
+    pub fn loud(&self) -> bool {
+        self.loud
+    }
+}
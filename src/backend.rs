@@ -0,0 +1,1974 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{mpsc as std_mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use chrono::Local;
+use epaint::{vec2, ColorImage};
+use rayon::prelude::*;
+use rumqttc::{AsyncClient, Event, LastWill, Outgoing, Packet, QoS};
+use serde_json::{json, Value};
+use tokio::{
+    sync::mpsc,
+    task::JoinHandle,
+    time::{self, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+use laundry_machine_core::{Interpolation, Marker, MarkerType, Quality, SegmentPattern};
+
+use crate::{
+    api::{self, SharedStatus},
+    audio, beep, blink, bridge,
+    cli::Args,
+    config::{Config, Profile},
+    cycle,
+    door::{self, Door},
+    event::EventKind,
+    lighting::{self, AmbientReference, Lighting},
+    machine, ptz, recording, region,
+    registration::Reference,
+    reminder, remote, sampler,
+    schedule::Schedule,
+    sink::{self, Batch, Sinks},
+    source::{self, FrameSource},
+    vibration::{self, Vibration},
+};
+
+/// Something for the backend to do, sent from a frontend.
+pub enum Command {
+    /// Use a new config, reconnecting to MQTT if its settings changed.
+    Config(Box<Config>),
+    /// Fetch a frame for a profile and sample the markers with the given
+    /// indices.
+    Sample { profile: usize, markers: Vec<usize> },
+    /// Fetch a frame for a profile without sampling anything.
+    #[cfg(feature = "gui")]
+    Refresh(usize),
+    /// Publish all current values of all profiles again.
+    #[cfg(feature = "gui")]
+    Publish,
+    Message {
+        topic: String,
+        payload: String,
+        retain: bool,
+    },
+    /// Pause or resume sampling and publishing of a profile, through its
+    /// retained enable topic.
+    #[cfg(feature = "gui")]
+    SetPaused { profile: usize, paused: bool },
+    /// Acknowledge the finished laundry of a profile, like a message on its
+    /// ack topic.
+    #[cfg(feature = "gui")]
+    Ack(usize),
+    /// Stop the backend.
+    Shutdown,
+}
+
+/// Something that happened in the backend, for a frontend to show. Headless
+/// mode only looks at a few of them.
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub enum Update {
+    /// A new frame for the profile with the given index.
+    Frame(usize, ColorImage),
+    /// Profile index, marker index, luminance at each point, and decoded
+    /// value of a marker.
+    Sampled(usize, usize, Vec<f32>, Value, Quality),
+    MqttConnected,
+    /// A message on one of the subscribed topics.
+    Message {
+        topic: String,
+        payload: Vec<u8>,
+    },
+    /// A config change from the HTTP API, in the same format as remote
+    /// config messages.
+    ConfigChange(Vec<u8>),
+    /// The markers of the profile with the given index were moved by this
+    /// many pixels to follow the camera.
+    Shift(usize, [i64; 2]),
+    /// The ambient luminance around the markers of the profile with the
+    /// given index, and the lighting whose threshold was used, if any.
+    Lighting(usize, f32, Option<String>),
+    /// The luminance of the ambient reference of the profile with the given
+    /// index.
+    Ambient(usize, f32),
+    /// How much the vibration region of the profile with the given index
+    /// changed since the previous frame, and whether that counts as
+    /// vibrating yet.
+    Vibration(usize, f32, Option<bool>),
+    /// The microphone level of the profile with the given index, and whether
+    /// it's loud.
+    Sound(usize, f32, bool),
+    /// How much the door region of the profile with the given index differs
+    /// from closed, and whether that counts as open.
+    Door(usize, f32, Option<bool>),
+    /// When the latest frame of the profile with the given index was
+    /// captured, as read from the camera's timestamp.
+    FrameTime(usize, SystemTime),
+    /// The profile with the given topic prefix was paused or resumed.
+    Paused {
+        prefix: String,
+        paused: bool,
+    },
+    /// A cycle finished on the named profile, which has its alarm set to
+    /// flash the window.
+    Alarm(String),
+    /// How long each stage of the latest sample of the profile with the
+    /// given index took.
+    Timings(usize, Timings),
+    Error(String),
+}
+
+/// How long each stage of a sample took.
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(not(any(feature = "gui", feature = "tui")), allow(dead_code))]
+pub struct Timings {
+    /// Fetching the frame, without decoding it.
+    pub fetch: Duration,
+    /// Decoding the frame's image.
+    pub decode: Duration,
+    /// Sampling and decoding the markers and regions.
+    pub sample: Duration,
+    /// Passing the values to the sinks, which queue them.
+    pub publish: Duration,
+}
+
+/// Runs the MQTT connection, frame fetching and sampling schedule on a tokio
+/// runtime, driven by commands from a frontend.
+pub struct Backend {
+    commands: mpsc::UnboundedSender<Command>,
+    updates: std_mpsc::Receiver<Update>,
+    updates_tx: Arc<Updates>,
+    status: SharedStatus,
+    thread: Option<thread::JoinHandle<()>>,
+    #[cfg(target_os = "linux")]
+    dbus: std::sync::OnceLock<crate::dbus::Service>,
+}
+
+impl Backend {
+    /// Starts the backend, calling `notify` whenever an update is available.
+    /// Without `full_frames`, large frames may only have the regions around
+    /// the markers decoded.
+    pub fn start(
+        config: Config,
+        refresh_rate: Duration,
+        full_frames: bool,
+        notify: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (update_tx, updates) = std_mpsc::channel();
+
+        let status = SharedStatus::new(Mutex::new(api::Status {
+            config: config.clone(),
+            refresh_rate,
+            ..Default::default()
+        }));
+
+        let updates_tx = Arc::new(Updates {
+            tx: Mutex::new(Some(update_tx)),
+            notify: Box::new(notify),
+            status: status.clone(),
+        });
+
+        let run_updates = updates_tx.clone();
+        let thread = thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("failed to start tokio runtime");
+
+            runtime.block_on(run(
+                config,
+                refresh_rate,
+                full_frames,
+                command_rx,
+                run_updates.clone(),
+            ));
+            run_updates.close();
+
+            // Don't wait for a hanging frame fetch or webhook.
+            runtime.shutdown_timeout(Duration::from_secs(5));
+        });
+
+        Self {
+            commands,
+            updates,
+            updates_tx,
+            status,
+            thread: Some(thread),
+            #[cfg(target_os = "linux")]
+            dbus: Default::default(),
+        }
+    }
+
+    /// Stops sampling, flushes queued messages, marks the machine offline and
+    /// disconnects from MQTT, blocking until done.
+    pub fn shutdown(&mut self) {
+        self.send(Command::Shutdown);
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+
+    pub fn send(&self, command: Command) {
+        self.commands.send(command).ok();
+    }
+
+    /// A function that starts stopping the backend, for use from other threads
+    /// such as signal handlers. The update channel closes once it's done.
+    pub fn shutdown_handle(&self) -> impl Fn() + Send + 'static {
+        let commands = self.commands.clone();
+        move || {
+            commands.send(Command::Shutdown).ok();
+        }
+    }
+
+    /// The latest state, as the HTTP API shows it.
+    #[cfg(feature = "tui")]
+    pub fn status(&self) -> SharedStatus {
+        self.status.clone()
+    }
+
+    /// Starts the HTTP API, if an address was given.
+    pub fn serve_api(&self, args: &Args) {
+        if let Some(addr) = &args.http {
+            let updates = self.updates_tx.clone();
+            let on_change = move |payload| {
+                updates.send(Update::ConfigChange(payload));
+            };
+
+            if let Err(e) = api::serve(addr, self.status.clone(), on_change) {
+                error!("Error starting HTTP API on {}: {}", addr, e);
+            }
+        }
+    }
+
+    /// Registers the D-Bus service, if a bus was given.
+    #[cfg(target_os = "linux")]
+    pub fn serve_dbus(&self, args: &Args) {
+        let Some(bus) = args.dbus else {
+            return;
+        };
+
+        let commands = self.commands.clone();
+        let status = self.status.clone();
+        let sample = move || {
+            let config = &status.lock().unwrap().config;
+            commands
+                .send(Command::Sample {
+                    profile: config.active_profile,
+                    markers: (0..config.profile().markers.len()).collect(),
+                })
+                .ok();
+        };
+
+        match crate::dbus::serve(bus, self.status.clone(), sample) {
+            Ok(service) => {
+                tracing::info!("Serving D-Bus interface as {}", crate::dbus::NAME);
+                self.dbus.set(service).ok();
+            }
+            Err(e) => error!("Error starting D-Bus interface: {}", e),
+        }
+    }
+
+    /// Updates that arrived since the last call.
+    #[cfg(feature = "gui")]
+    pub fn poll(&self) -> impl Iterator<Item = Update> + '_ {
+        self.updates.try_iter()
+    }
+
+    /// Blocks until the next update.
+    pub fn recv(&self) -> Option<Update> {
+        self.updates.recv().ok()
+    }
+}
+
+struct Updates {
+    /// Taken when the backend stops, so that `Backend::recv` returns `None`
+    /// even while background requests are still finishing.
+    tx: Mutex<Option<std_mpsc::Sender<Update>>>,
+    notify: Box<dyn Fn() + Send + Sync>,
+    status: SharedStatus,
+}
+
+impl Updates {
+    fn send(&self, update: Update) {
+        self.update_status(&update);
+        if let Some(tx) = &*self.tx.lock().unwrap() {
+            tx.send(update).ok();
+        }
+        (self.notify)();
+    }
+
+    fn close(&self) {
+        self.tx.lock().unwrap().take();
+    }
+
+    fn update_status(&self, update: &Update) {
+        let mut status = self.status.lock().unwrap();
+
+        match update {
+            // The API only shows the active profile.
+            Update::Frame(profile, _)
+            | Update::Sampled(profile, ..)
+            | Update::FrameTime(profile, _)
+            | Update::Timings(profile, _)
+                if *profile != status.config.active_profile => {}
+            Update::Frame(_, image) => {
+                status.frame = Some(image.clone());
+                status.frame_time = Some(std::time::Instant::now());
+            }
+            Update::Sampled(_, idx, samples, value, _) => {
+                let markers = status.config.profile().markers.len();
+                if *idx < markers {
+                    status.samples.resize(markers, vec![]);
+                    status.values.resize(markers, Value::Null);
+                    status.samples[*idx].clone_from(samples);
+                    status.values[*idx] = value.clone();
+                }
+            }
+            Update::FrameTime(_, time) => status.frame_captured = Some(*time),
+            Update::Timings(_, timings) => status.timings = Some(*timings),
+            Update::MqttConnected => status.mqtt_connected = true,
+            Update::Message { .. }
+            | Update::ConfigChange(_)
+            | Update::Shift(..)
+            | Update::Lighting(..)
+            | Update::Ambient(..)
+            | Update::Vibration(..)
+            | Update::Sound(..)
+            | Update::Door(..)
+            | Update::Paused { .. }
+            | Update::Alarm(_)
+            | Update::Error(_) => {}
+        }
+    }
+
+    fn set_config(&self, config: &Config) {
+        let mut status = self.status.lock().unwrap();
+
+        if config.active_profile != status.config.active_profile
+            || config.profile().markers.len() != status.config.profile().markers.len()
+        {
+            status.samples.clear();
+            status.values.clear();
+            status.frame_captured = None;
+            status.timings = None;
+        }
+
+        status.config = config.clone();
+    }
+}
+
+struct Mqtt {
+    client: AsyncClient,
+    event_loop: JoinHandle<()>,
+    availability: Vec<String>,
+    /// The bridge state topic, when following Zigbee2MQTT's conventions.
+    bridge_state: Option<String>,
+}
+
+impl Mqtt {
+    /// Connects to the broker, passing the topic prefix and new state of
+    /// messages on enable topics to `enabled`, and the topic prefix of
+    /// messages on ack topics to `acks`.
+    fn connect(
+        config: &Config,
+        updates: Arc<Updates>,
+        enabled: mpsc::UnboundedSender<(String, bool)>,
+        acks: mpsc::UnboundedSender<String>,
+    ) -> Self {
+        let availability = availability_topics(config);
+
+        let bridge = config.mqtt.bridge_topic.clone();
+        let bridge_state = bridge.as_deref().map(bridge::state_topic);
+
+        // A connection only has a single last will, so only the bridge or
+        // else the first profile is marked offline if the app dies without
+        // disconnecting.
+        let mut options = config.mqtt.options("laundry-machine-mqtt");
+        if let Some(low_power) = &config.low_power {
+            options.set_keep_alive(Duration::from_secs(low_power.keep_alive_secs));
+        }
+        if let Some(topic) = &bridge_state {
+            options.set_last_will(LastWill::new(
+                topic,
+                bridge::state_payload(false),
+                QoS::AtLeastOnce,
+                true,
+            ));
+        } else if let Some(topic) = availability.first() {
+            options.set_last_will(LastWill::new(topic, "offline", QoS::AtLeastOnce, true));
+        }
+
+        let prefixes = config
+            .profiles
+            .iter()
+            .map(|profile| profile.topic_prefix.clone())
+            .collect::<Vec<_>>();
+
+        let (client, mut event_loop) = AsyncClient::new(options, 100);
+
+        let presences = config
+            .profiles
+            .iter()
+            .filter_map(|profile| profile.presence.clone())
+            .collect::<Vec<_>>();
+
+        // Each device's set topic, with its profile's topic prefix.
+        let bridge_sets = bridge
+            .iter()
+            .flat_map(|base| {
+                config.profiles.iter().map(|profile| {
+                    (
+                        bridge::set_topic(base, profile),
+                        profile.topic_prefix.clone(),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let subscriptions = remote::set_topic(config)
+            .into_iter()
+            .chain(prefixes.iter().map(|prefix| enable_topic(prefix)))
+            .chain(prefixes.iter().map(|prefix| ack_topic(prefix)))
+            .chain(presences.iter().map(|presence| presence.topic.clone()))
+            .chain(bridge_sets.iter().map(|(topic, _)| topic.clone()))
+            .collect::<Vec<_>>();
+        let subscriber = client.clone();
+        let online_topic = bridge_state.clone();
+
+        let event_loop = tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        if let Some(topic) = &online_topic {
+                            if let Err(e) = subscriber
+                                .publish(topic, QoS::AtLeastOnce, true, bridge::state_payload(true))
+                                .await
+                            {
+                                updates.send(Update::Error(format!(
+                                    "Error publishing bridge state: {}",
+                                    e
+                                )));
+                            }
+                        }
+
+                        for prefix in &prefixes {
+                            let paused = updates.status.lock().unwrap().paused.contains(prefix);
+                            if let Err(e) = subscriber
+                                .publish(
+                                    availability_topic(prefix),
+                                    QoS::AtLeastOnce,
+                                    true,
+                                    if paused { "paused" } else { "online" },
+                                )
+                                .await
+                            {
+                                updates.send(Update::Error(format!(
+                                    "Error publishing availability: {}",
+                                    e
+                                )));
+                            }
+                        }
+
+                        // Subscriptions don't survive reconnects.
+                        for topic in &subscriptions {
+                            if let Err(e) = subscriber.subscribe(topic, QoS::AtLeastOnce).await {
+                                updates.send(Update::Error(format!(
+                                    "Error subscribing to {}: {}",
+                                    topic, e
+                                )));
+                            }
+                        }
+
+                        updates.send(Update::MqttConnected);
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        if let Some((_, prefix)) = bridge_sets
+                            .iter()
+                            .find(|(topic, _)| *topic == publish.topic)
+                        {
+                            match bridge::parse_set(&publish.payload) {
+                                Ok(commands) => {
+                                    for command in commands {
+                                        match command {
+                                            bridge::SetCommand::Enabled(state) => {
+                                                enabled.send((prefix.clone(), state)).ok();
+                                            }
+                                            bridge::SetCommand::Ack => {
+                                                acks.send(prefix.clone()).ok();
+                                            }
+                                        }
+                                    }
+                                }
+                                Err(e) => updates.send(Update::Error(format!(
+                                    "Invalid payload on {}: {}",
+                                    publish.topic, e
+                                ))),
+                            }
+                            continue;
+                        }
+
+                        if let Some(prefix) = publish
+                            .topic
+                            .strip_suffix("/enable")
+                            .filter(|prefix| prefixes.iter().any(|p| p == prefix))
+                        {
+                            match parse_enabled(&publish.payload) {
+                                Some(state) => {
+                                    enabled.send((prefix.to_owned(), state)).ok();
+                                }
+                                None => updates.send(Update::Error(format!(
+                                    "Invalid payload on {}, expected true or false",
+                                    publish.topic
+                                ))),
+                            }
+                            continue;
+                        }
+
+                        if let Some(prefix) = publish
+                            .topic
+                            .strip_suffix("/ack")
+                            .filter(|prefix| prefixes.iter().any(|p| p == prefix))
+                        {
+                            acks.send(prefix.to_owned()).ok();
+                            continue;
+                        }
+
+                        if let Some((presence, person)) = presences
+                            .iter()
+                            .find_map(|presence| Some((presence, presence.person(&publish.topic)?)))
+                        {
+                            let home = String::from_utf8_lossy(&publish.payload).trim()
+                                == presence.home_payload;
+                            debug!("{} is {}", person, if home { "home" } else { "away" });
+                            updates
+                                .status
+                                .lock()
+                                .unwrap()
+                                .home
+                                .insert(person.to_owned(), home);
+                            continue;
+                        }
+
+                        updates.send(Update::Message {
+                            topic: publish.topic,
+                            payload: publish.payload.to_vec(),
+                        });
+                    }
+                    // Everything queued before the disconnect has been sent.
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        updates.status.lock().unwrap().mqtt_connected = false;
+                        updates.send(Update::Error(format!("MQTT connection error: {}", e)));
+                        time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        Self {
+            client,
+            event_loop,
+            availability,
+            bridge_state,
+        }
+    }
+
+    /// Marks the machine offline and disconnects once everything queued so
+    /// far has been sent.
+    async fn shutdown(&mut self) {
+        for topic in &self.availability {
+            self.client
+                .publish(topic, QoS::AtLeastOnce, true, "offline")
+                .await
+                .ok();
+        }
+
+        if let Some(topic) = &self.bridge_state {
+            self.client
+                .publish(topic, QoS::AtLeastOnce, true, bridge::state_payload(false))
+                .await
+                .ok();
+        }
+
+        if self.client.disconnect().await.is_ok() {
+            time::timeout(Duration::from_secs(5), &mut self.event_loop)
+                .await
+                .ok();
+        }
+    }
+
+    /// Pauses or resumes a profile, marking it as paused or online.
+    async fn set_paused(&self, prefix: String, paused: bool, updates: &Updates) {
+        {
+            let mut status = updates.status.lock().unwrap();
+            let changed = if paused {
+                status.paused.insert(prefix.clone())
+            } else {
+                status.paused.remove(&prefix)
+            };
+
+            if !changed {
+                return;
+            }
+        }
+
+        info!("{} {}", if paused { "Paused" } else { "Resumed" }, prefix);
+
+        let state = if paused { "paused" } else { "online" };
+        self.publish(availability_topic(&prefix), state.to_owned(), true, updates)
+            .await;
+
+        updates.send(Update::Paused { prefix, paused });
+    }
+
+    async fn publish(&self, topic: String, payload: String, retain: bool, updates: &Updates) {
+        match self
+            .client
+            .publish(&topic, QoS::AtLeastOnce, retain, payload.clone())
+            .await
+        {
+            Ok(()) => {
+                debug!("Published {}: {}", topic, payload);
+                updates.status.lock().unwrap().publish_time = Some(std::time::Instant::now());
+            }
+            Err(e) => updates.send(Update::Error(format!("Error publishing {}: {}", topic, e))),
+        }
+    }
+}
+
+impl Drop for Mqtt {
+    fn drop(&mut self) {
+        self.event_loop.abort();
+    }
+}
+
+/// A profile's reference frame, loaded when it's first needed.
+pub type SharedReference = Arc<std::sync::OnceLock<Option<Reference>>>;
+
+/// A profile's frame source, kept open so that streams aren't reconnected for
+/// every frame.
+type SharedSource = Arc<Mutex<Box<dyn FrameSource>>>;
+
+/// What a marker last decoded to, from which samples at which threshold.
+pub struct Decoded {
+    samples: Vec<f32>,
+    threshold: f32,
+    value: Value,
+    quality: Quality,
+}
+
+impl Decoded {
+    /// Whether the samples decode to the same value at the threshold. That's
+    /// so if every sample is on the same side of it as before, unless
+    /// decoding took retries at other thresholds.
+    fn reads_same(&self, samples: &[f32], threshold: f32) -> bool {
+        if self.threshold != threshold || self.samples.len() != samples.len() {
+            return false;
+        }
+
+        let lit = |sample: &f32| *sample > threshold;
+        self.samples == samples
+            || (self.quality.retries == 0
+                && self.samples.iter().map(lit).eq(samples.iter().map(lit)))
+    }
+}
+
+/// A profile's last decode of each marker by index, so that markers whose
+/// samples didn't change aren't decoded again.
+pub type SharedDecodes = Arc<Mutex<Vec<Option<Decoded>>>>;
+
+/// A sampled frame of a profile.
+pub struct Sampled {
+    pub profile: usize,
+    pub image: ColorImage,
+    pub time: SystemTime,
+    pub markers: Vec<(usize, Vec<f32>, Value, Quality)>,
+    /// The cells of the vibration region, if there is one.
+    pub vibration: Vec<f32>,
+    /// The cells of the door region, if there is one.
+    pub door: Vec<f32>,
+    /// The fetch and decode times are filled in by whoever fetched the
+    /// frame, and the publish time after this is received.
+    pub timings: Timings,
+}
+
+/// Sampling and decoding some of a profile's markers and its regions, with
+/// what's needed taken from the profile so that it can run on another
+/// thread.
+pub struct Pipeline {
+    threshold: f32,
+    lightings: Vec<Lighting>,
+    patterns: Vec<SegmentPattern>,
+    all_markers: Vec<Marker>,
+    peak_search: usize,
+    interpolation: Interpolation,
+    /// Sampled with every frame, but never published.
+    ambient_reference: Option<(AmbientReference, usize, MarkerType)>,
+    markers: Vec<(usize, Marker)>,
+    registration: Option<(PathBuf, SharedReference)>,
+    max_shift: usize,
+    decodes: SharedDecodes,
+    vibration: Option<Vibration>,
+    door: Option<Door>,
+}
+
+impl Pipeline {
+    /// For the markers with the given indices. `reference` is loaded on the
+    /// first run if the profile lines frames up with one.
+    pub fn new(
+        profile: &Profile,
+        indices: Vec<usize>,
+        reference: SharedReference,
+        decodes: SharedDecodes,
+    ) -> Self {
+        let ambient_reference = profile.ambient_reference.clone().and_then(|reference| {
+            let idx = reference.find(&profile.markers)?;
+            Some((reference, idx, profile.markers[idx].ty.clone()))
+        });
+
+        let markers = indices
+            .into_iter()
+            .filter(|&idx| {
+                ambient_reference
+                    .as_ref()
+                    .is_none_or(|(_, own, _)| idx != *own)
+            })
+            .filter_map(|idx| Some((idx, profile.markers.get(idx)?.clone())))
+            .collect::<Vec<_>>();
+
+        Self {
+            threshold: profile.luminance_threshold,
+            lightings: profile.lighting.clone(),
+            patterns: profile.segment_patterns.clone(),
+            all_markers: profile.markers.clone(),
+            peak_search: profile.peak_search,
+            interpolation: profile.interpolation,
+            ambient_reference,
+            markers,
+            registration: profile
+                .reference_frame
+                .clone()
+                .filter(|_| profile.max_shift > 0)
+                .map(|path| (path, reference)),
+            max_shift: profile.max_shift,
+            decodes,
+            vibration: profile.vibration.clone(),
+            door: profile.door.clone(),
+        }
+    }
+
+    /// Samples and decodes a frame of the profile with the given index,
+    /// passing what's found along the way to `report`.
+    pub fn run(
+        &self,
+        profile_idx: usize,
+        image: ColorImage,
+        time: SystemTime,
+        report: &dyn Fn(Update),
+    ) -> Sampled {
+        let sampling = Instant::now();
+        let mut markers = self.markers.clone();
+        let mut ambient_reference = self.ambient_reference.clone();
+        let (peak_search, interpolation) = (self.peak_search, self.interpolation);
+
+        let threshold = if self.lightings.is_empty() {
+            self.threshold
+        } else {
+            let (lighting, ambient) = lighting::current(&self.lightings, &image, &self.all_markers);
+            report(Update::Lighting(
+                profile_idx,
+                ambient,
+                lighting.map(|lighting| lighting.name.clone()),
+            ));
+            lighting.map_or(self.threshold, |lighting| lighting.luminance_threshold)
+        };
+
+        if let Some((path, reference)) = &self.registration {
+            let reference = reference.get_or_init(|| {
+                Reference::load(path)
+                    .map_err(|e| {
+                        report(Update::Error(format!(
+                            "Error loading reference frame {}: {}",
+                            path.display(),
+                            e
+                        )))
+                    })
+                    .ok()
+            });
+
+            let shift = reference.as_ref().and_then(|reference| {
+                reference.find_shift(&image, &self.all_markers, self.max_shift)
+            });
+
+            if let Some(shift @ [dx, dy]) = shift {
+                let offset = vec2(
+                    dx as f32 / image.width() as f32,
+                    dy as f32 / image.height() as f32,
+                );
+                for (_, marker) in &mut markers {
+                    marker.ty.translate(offset);
+                }
+                if let Some((_, _, ty)) = &mut ambient_reference {
+                    ty.translate(offset);
+                }
+
+                report(Update::Shift(profile_idx, shift));
+            }
+        }
+
+        let ambient = ambient_reference.as_ref().map(|(reference, _, ty)| {
+            let ambient = ty.sample_with_search(
+                &image.pixels,
+                image.width(),
+                image.height(),
+                peak_search,
+                interpolation,
+            )[0];
+            report(Update::Ambient(profile_idx, ambient));
+            (reference, ambient)
+        });
+
+        // Markers are independent, so they're sampled on all cores.
+        let mut decodes = self.decodes.lock().unwrap();
+        let sampled = markers
+            .into_par_iter()
+            .map(|(idx, marker)| {
+                let samples = sampler::sample(&marker.ty, &image, peak_search, interpolation);
+                let threshold = marker.threshold_or(threshold);
+                let threshold = match ambient {
+                    Some((reference, ambient)) => reference.adjust(threshold, ambient),
+                    None => threshold,
+                };
+
+                let unchanged = decodes
+                    .get(idx)
+                    .and_then(Option::as_ref)
+                    .filter(|decoded| decoded.reads_same(&samples, threshold));
+                let (value, quality, fresh) = match unchanged {
+                    Some(decoded) => (decoded.value.clone(), decoded.quality, false),
+                    None => {
+                        let (value, quality) = marker.decode(&samples, threshold, &self.patterns);
+                        (value, quality, true)
+                    }
+                };
+                (idx, samples, threshold, value, quality, fresh)
+            })
+            .collect::<Vec<_>>();
+
+        let markers = sampled
+            .into_iter()
+            .map(|(idx, samples, threshold, value, quality, fresh)| {
+                if fresh {
+                    if decodes.len() <= idx {
+                        decodes.resize_with(idx + 1, || None);
+                    }
+                    decodes[idx] = Some(Decoded {
+                        samples: samples.clone(),
+                        threshold,
+                        value: value.clone(),
+                        quality,
+                    });
+                }
+                (idx, samples, value, quality)
+            })
+            .collect();
+        drop(decodes);
+
+        let vibration = self
+            .vibration
+            .as_ref()
+            .map_or(vec![], |vibration| region::cells(&image, &vibration.region));
+        let door = self
+            .door
+            .as_ref()
+            .map_or(vec![], |door| region::cells(&image, &door.region));
+
+        Sampled {
+            profile: profile_idx,
+            image,
+            time,
+            markers,
+            vibration,
+            door,
+            timings: Timings {
+                sample: sampling.elapsed(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The markers to decode only the regions of from large frames, or `None`
+/// to decode whole frames.
+pub fn crop(config: &Config, profile: &Profile, full_frames: bool) -> Option<Vec<Marker>> {
+    // Recordings need the whole frame, and lining up with the reference and
+    // looking at the drum or door need more than just the marker regions.
+    (!full_frames
+        && config.record_directory.is_none()
+        && !(profile.reference_frame.is_some() && profile.max_shift > 0)
+        && profile.vibration.is_none()
+        && profile.door.is_none())
+    .then(|| profile.markers.clone())
+}
+
+async fn run(
+    mut config: Config,
+    refresh_rate: Duration,
+    full_frames: bool,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    updates: Arc<Updates>,
+) {
+    let (enabled_tx, mut enabled_rx) = mpsc::unbounded_channel();
+    let (ack_tx, mut ack_rx) = mpsc::unbounded_channel();
+    let mut mqtt = Mqtt::connect(&config, updates.clone(), enabled_tx.clone(), ack_tx.clone());
+
+    let is_paused = |profile: &Profile| {
+        updates
+            .status
+            .lock()
+            .unwrap()
+            .paused
+            .contains(&profile.topic_prefix)
+    };
+
+    let report: sink::Report = {
+        let updates = updates.clone();
+        Arc::new(move |e| updates.send(Update::Error(e)))
+    };
+    let alarm: sink::Report = {
+        let updates = updates.clone();
+        Arc::new(move |profile| updates.send(Update::Alarm(profile)))
+    };
+    let make_sinks = |config: &Config, mqtt: &Mqtt| {
+        Sinks::new(
+            config,
+            mqtt.client.clone(),
+            updates.status.clone(),
+            report.clone(),
+            alarm.clone(),
+        )
+    };
+    let mut sinks = make_sinks(&config, &mqtt);
+
+    let (sampled_tx, mut sampled_rx) = mpsc::unbounded_channel::<Sampled>();
+
+    // Per profile, per marker.
+    let mut next_sample: Vec<Vec<Instant>> = vec![];
+    let mut values: Vec<Vec<Value>> = vec![];
+    let mut samples: Vec<Vec<Vec<f32>>> = vec![];
+    let mut quality: Vec<Vec<Quality>> = vec![];
+    let mut blinks: Vec<Vec<blink::Tracker>> = vec![];
+    // Per profile, for the heartbeat.
+    let mut published_all: Vec<Option<Instant>> = vec![];
+    let mut published_frame: Vec<Option<Instant>> = vec![];
+    let mut published_debug: Vec<Option<Instant>> = vec![];
+    // Per profile, whether a display blinks as it does while paused.
+    let mut blinking: Vec<bool> = vec![];
+    // Per profile, for the watchdog.
+    let mut last_sampled: Vec<Instant> = vec![];
+    // Per profile, how long it had stalled when its source was reopened,
+    // until it samples again.
+    let mut restarted: Vec<Option<Duration>> = vec![];
+    let mut cycles: Vec<cycle::Tracker> = vec![];
+    let mut machines: Vec<machine::Tracker> = vec![];
+    let mut vibrations: Vec<vibration::Detector> = vec![];
+    let mut doors: Vec<door::Tracker> = vec![];
+    let mut reminders: Vec<reminder::Tracker> = vec![];
+    let mut sounds: Vec<audio::Detector> = vec![];
+    let mut beeps: Vec<beep::Matcher> = vec![];
+    // Per profile.
+    let mut sources: Vec<Option<SharedSource>> = vec![];
+    let mut references: Vec<SharedReference> = vec![];
+    let mut decodes: Vec<SharedDecodes> = vec![];
+
+    let (level_tx, mut level_rx) = mpsc::unbounded_channel::<(usize, f32)>();
+    let listen = |profile_idx: usize, profile: &Profile| {
+        let level_tx = level_tx.clone();
+        audio::listen(profile.audio.as_ref()?, move |level| {
+            level_tx.send((profile_idx, level)).ok();
+        })
+        .map_err(|e| {
+            updates.send(Update::Error(format!(
+                "Error opening microphone for {}: {}",
+                profile.name, e
+            )))
+        })
+        .ok()
+    };
+    let listen_all = |config: &Config| {
+        config
+            .profiles
+            .iter()
+            .enumerate()
+            .map(|(idx, profile)| listen(idx, profile))
+            .collect::<Vec<_>>()
+    };
+    // Per profile, kept open while the profile has audio.
+    let mut listeners = listen_all(&config);
+
+    // Payload of each meta topic as last published.
+    let mut published_meta = HashMap::new();
+    publish_meta(&mqtt, &config, &mut published_meta, &updates).await;
+
+    // Fetches and samples a frame on the blocking thread pool, so that
+    // commands keep being handled in the meantime.
+    let fetch = |config: &Config,
+                 sources: &mut Vec<Option<SharedSource>>,
+                 references: &[SharedReference],
+                 decodes: &[SharedDecodes],
+                 profile_idx: usize,
+                 indices: Vec<usize>| {
+        let Some(profile) = config.profiles.get(profile_idx) else {
+            return;
+        };
+
+        let source = match &sources[profile_idx] {
+            Some(source) => source.clone(),
+            None => match source::open(
+                &profile.webcam,
+                config
+                    .low_power
+                    .as_ref()
+                    .map(|low_power| low_power.max_width),
+            ) {
+                Ok(source) => sources[profile_idx]
+                    .insert(Arc::new(Mutex::new(source)))
+                    .clone(),
+                Err(e) => {
+                    updates.send(Update::Error(format!(
+                        "Error opening webcam for {}: {}",
+                        profile.name, e
+                    )));
+                    return;
+                }
+            },
+        };
+
+        let pipeline = Pipeline::new(
+            profile,
+            indices,
+            references[profile_idx].clone(),
+            decodes[profile_idx].clone(),
+        );
+        let crop = crop(config, profile, full_frames);
+
+        let webcam = profile.webcam.clone();
+        let sampled_tx = sampled_tx.clone();
+        let updates = updates.clone();
+        let name = profile.name.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let start = Instant::now();
+            let frame = {
+                // Held while the camera is at the preset, so that other
+                // fetches for this profile don't move it back in between.
+                let mut source = source.lock().unwrap();
+                ptz::at_preset(&webcam, || source.latest(crop.as_deref()))
+            };
+            match frame {
+                Ok(source::Frame {
+                    image,
+                    time,
+                    decode_time,
+                }) => {
+                    let fetch = start.elapsed().saturating_sub(decode_time);
+                    let mut sampled =
+                        pipeline.run(profile_idx, image, time, &|update| updates.send(update));
+                    sampled.timings.fetch = fetch;
+                    sampled.timings.decode = decode_time;
+                    sampled_tx.send(sampled).ok();
+                }
+                Err(e) => updates.send(Update::Error(format!(
+                    "Error fetching frame for {}: {}",
+                    name, e
+                ))),
+            }
+        });
+    };
+
+    loop {
+        next_sample.resize(config.profiles.len(), vec![]);
+        values.resize(config.profiles.len(), vec![]);
+        samples.resize(config.profiles.len(), vec![]);
+        quality.resize(config.profiles.len(), vec![]);
+        blinks.resize_with(config.profiles.len(), Vec::new);
+        published_all.resize(config.profiles.len(), None);
+        published_frame.resize(config.profiles.len(), None);
+        published_debug.resize(config.profiles.len(), None);
+        blinking.resize(config.profiles.len(), false);
+        last_sampled.resize(config.profiles.len(), Instant::now());
+        restarted.resize(config.profiles.len(), None);
+        cycles.resize_with(config.profiles.len(), Default::default);
+        machines.resize_with(config.profiles.len(), Default::default);
+        vibrations.resize_with(config.profiles.len(), Default::default);
+        doors.resize_with(config.profiles.len(), Default::default);
+        reminders.resize_with(config.profiles.len(), Default::default);
+        sounds.resize_with(config.profiles.len(), Default::default);
+        beeps.resize_with(config.profiles.len(), Default::default);
+        sources.resize(config.profiles.len(), None);
+        references.resize_with(config.profiles.len(), Default::default);
+        decodes.resize_with(config.profiles.len(), Default::default);
+
+        for (idx, profile) in config.profiles.iter().enumerate() {
+            next_sample[idx].resize(profile.markers.len(), Instant::now());
+            values[idx].resize(profile.markers.len(), Value::Null);
+            samples[idx].resize(profile.markers.len(), vec![]);
+            quality[idx].resize(profile.markers.len(), Default::default());
+            blinks[idx].resize_with(profile.markers.len(), Default::default);
+        }
+
+        let next = next_sample
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+            .unwrap_or_else(|| Instant::now() + config.refresh_rate(refresh_rate));
+
+        tokio::select! {
+            command = commands.recv() => match command {
+                None | Some(Command::Shutdown) => break,
+                Some(Command::Config(new)) => {
+                    if new.mqtt != config.mqtt
+                        || presence_topics(&new) != presence_topics(&config)
+                        || new.low_power.as_ref().map(|low_power| low_power.keep_alive_secs)
+                            != config.low_power.as_ref().map(|low_power| low_power.keep_alive_secs)
+                        || remote::set_topic(&new) != remote::set_topic(&config)
+                        || availability_topics(&new) != availability_topics(&config)
+                    {
+                        // Disconnect cleanly first, so the old connection's
+                        // last will doesn't mark the machine offline.
+                        mqtt.shutdown().await;
+                        mqtt = Mqtt::connect(&new, updates.clone(), enabled_tx.clone(), ack_tx.clone());
+                        // The broker may be another one.
+                        published_meta.clear();
+                    }
+
+                    // Profiles are identified by index, which shifts when
+                    // one is removed.
+                    let max_width = |config: &Config| {
+                        config.low_power.as_ref().map(|low_power| low_power.max_width)
+                    };
+                    if max_width(&new) != max_width(&config) {
+                        sources.iter_mut().for_each(|source| *source = None);
+                    }
+
+                    if new.profiles.len() != config.profiles.len() {
+                        next_sample.clear();
+                        values.clear();
+                        samples.clear();
+                        quality.clear();
+                        blinks.clear();
+                        published_all.clear();
+                        published_frame.clear();
+                        published_debug.clear();
+                        blinking.clear();
+                        last_sampled.clear();
+                        restarted.clear();
+                        cycles.clear();
+                        machines.clear();
+                        vibrations.clear();
+                        doors.clear();
+                        reminders.clear();
+                        sounds.clear();
+                        beeps.clear();
+                        sources.clear();
+                        references.clear();
+                        decodes.clear();
+
+                        // Devices may only be open once, so the old ones are
+                        // closed first.
+                        listeners.clear();
+                        listeners = listen_all(&new);
+                    } else {
+                        for (idx, (new, old)) in new.profiles.iter().zip(&config.profiles).enumerate() {
+                            if new.markers.len() != old.markers.len() {
+                                next_sample[idx].clear();
+                                values[idx].clear();
+                                samples[idx].clear();
+                                quality[idx].clear();
+                                blinks[idx].clear();
+                            }
+
+                            if new.webcam != old.webcam {
+                                sources[idx] = None;
+                            }
+
+                            if new.reference_frame != old.reference_frame {
+                                references[idx] = Default::default();
+                            }
+
+                            // Anything in the profile may decode differently.
+                            if new != old {
+                                decodes[idx] = Default::default();
+                            }
+
+                            if new.machine != old.machine {
+                                machines[idx] = Default::default();
+                            }
+
+                            if new.vibration != old.vibration {
+                                vibrations[idx] = Default::default();
+                            }
+
+                            if new.door != old.door {
+                                doors[idx] = Default::default();
+                            }
+
+                            if new.reminders != old.reminders {
+                                reminders[idx] = Default::default();
+                            }
+
+                            if new.audio != old.audio {
+                                sounds[idx] = Default::default();
+                                beeps[idx] = Default::default();
+                                listeners[idx] = None;
+                                listeners[idx] = listen(idx, new);
+                            }
+                        }
+                    }
+
+                    sinks = make_sinks(&new, &mqtt);
+                    updates.set_config(&new);
+                    config = *new;
+                    publish_meta(&mqtt, &config, &mut published_meta, &updates).await;
+                }
+                Some(Command::Sample { profile, markers }) => fetch(&config, &mut sources, &references, &decodes, profile, markers),
+                #[cfg(feature = "gui")]
+                Some(Command::Refresh(profile)) => fetch(&config, &mut sources, &references, &decodes, profile, vec![]),
+                #[cfg(feature = "gui")]
+                Some(Command::Publish) => {
+                    for ((profile, values), quality) in config.profiles.iter().zip(&values).zip(&quality) {
+                        if is_paused(profile) {
+                            continue;
+                        }
+
+                        let indices = published_indices(profile)
+                            .into_iter()
+                            .filter(|&idx| idx < values.len())
+                            .collect::<Vec<_>>();
+
+                        sinks.write(&Batch {
+                            profile,
+                            previous: values,
+                            values,
+                            quality,
+                            indices: &indices,
+                            transition: None,
+                            reminder: None,
+                        });
+                    }
+                }
+                #[cfg(feature = "gui")]
+                Some(Command::SetPaused { profile, paused }) => {
+                    if let Some(profile) = config.profiles.get(profile) {
+                        // Retained, so it survives restarts, and comes back to
+                        // set the state.
+                        let topic = enable_topic(&profile.topic_prefix);
+                        mqtt.publish(topic, (!paused).to_string(), true, &updates).await;
+                        mqtt.set_paused(profile.topic_prefix.clone(), paused, &updates).await;
+                    }
+                }
+                #[cfg(feature = "gui")]
+                Some(Command::Ack(profile)) => {
+                    if let Some(profile) = config.profiles.get(profile) {
+                        ack_tx.send(profile.topic_prefix.clone()).ok();
+                    }
+                }
+                Some(Command::Message { topic, payload, retain }) => {
+                    mqtt.publish(topic, payload, retain, &updates).await;
+                }
+            },
+            Some((prefix, enabled)) = enabled_rx.recv() => {
+                mqtt.set_paused(prefix, !enabled, &updates).await;
+            },
+            Some(prefix) = ack_rx.recv() => {
+                for (idx, profile) in config.profiles.iter().enumerate() {
+                    if profile.topic_prefix != prefix {
+                        continue;
+                    }
+
+                    info!("Finished laundry of {} acknowledged", profile.name);
+
+                    // Also keeps a washer waiting for its door to unlock
+                    // from finishing after all.
+                    reminders[idx].stop();
+                    machines[idx] = Default::default();
+
+                    if let Some(door) = &profile.door {
+                        doors[idx].acknowledge();
+                        if let Some(unretrieved) = doors[idx].unretrieved(door) {
+                            let topic = format!("{}/unretrieved", profile.topic_prefix);
+                            mqtt.publish(topic, unretrieved.to_string(), true, &updates).await;
+                        }
+                    }
+                }
+            },
+            Some((profile_idx, level)) = level_rx.recv() => {
+                // Levels of removed profiles may still be queued.
+                let Some(profile) = config.profiles.get(profile_idx) else {
+                    continue;
+                };
+                let Some(audio) = &profile.audio else {
+                    continue;
+                };
+
+                let detector = &mut sounds[profile_idx];
+                let flipped = detector.update(audio, level);
+                updates.send(Update::Sound(profile_idx, level, detector.loud()));
+
+                let paused = is_paused(profile);
+
+                // Retained, like whether the drum is vibrating.
+                if let Some(loud) = flipped.filter(|_| !paused) {
+                    let topic = format!("{}/sound", profile.topic_prefix);
+                    mqtt.publish(topic, loud.to_string(), true, &updates).await;
+                }
+
+                if beeps[profile_idx].update(&audio.pattern, audio.threshold, level) && !paused {
+                    info!("Heard {} finish", profile.name);
+
+                    let topic = format!("{}/cycle_finished", profile.topic_prefix);
+                    let payload = json!({
+                        "source": "beep",
+                        "time": Local::now().to_rfc3339(),
+                    });
+                    mqtt.publish(topic, payload.to_string(), false, &updates).await;
+
+                    if profile.door.is_some() {
+                        doors[profile_idx].finished();
+                    }
+                    reminders[profile_idx].start();
+
+                    if let Some(summary) = cycles[profile_idx].finish(profile) {
+                        publish_summary(&mqtt, profile, summary, &updates).await;
+                    }
+                }
+            },
+            Some(sampled) = sampled_rx.recv() => {
+                let profile = sampled.profile;
+
+                // Profiles may have been removed while the frame was fetched.
+                let Some(values) = values.get_mut(profile) else {
+                    continue;
+                };
+
+                if let Some(dir) = &config.record_directory {
+                    record(dir.clone(), config.profiles[profile].clone(), &sampled, &updates);
+                }
+
+                let image = sampled.image;
+                let previous = values.clone();
+
+                let (indices, changed) = take_markers(
+                    &config.profiles[profile],
+                    sampled.markers,
+                    &mut samples[profile],
+                    values,
+                    &mut quality[profile],
+                    &mut blinks[profile],
+                    &|idx, samples, value, quality| {
+                        updates.send(Update::Sampled(profile, idx, samples, value, quality))
+                    },
+                );
+
+                // With a heartbeat, unchanged markers are only published
+                // when it's due.
+                let indices = match config.heartbeat_secs {
+                    Some(secs)
+                        if published_all[profile]
+                            .is_some_and(|time| time.elapsed() < Duration::from_secs(secs)) =>
+                    {
+                        changed
+                    }
+                    Some(_) => {
+                        published_all[profile] = Some(Instant::now());
+                        indices
+                    }
+                    None => indices,
+                };
+
+                // The camera's clock is only read, never published.
+                let indices = indices
+                    .into_iter()
+                    .filter(|&idx| !reads_clock(&config.profiles[profile], idx))
+                    .collect::<Vec<_>>();
+
+                let captured = config.profiles[profile]
+                    .frame_clock
+                    .as_ref()
+                    .and_then(|clock| clock.time(&config.profiles[profile].markers, values));
+                if let Some(time) = captured {
+                    updates.send(Update::FrameTime(profile, time));
+                }
+
+                // A frame captured long ago, like one cached by a proxy,
+                // doesn't count as sampled for the watchdog.
+                let interval = sample_interval(&config.profiles[profile], config.refresh_rate(refresh_rate));
+                match captured.and_then(|time| time.elapsed().ok()) {
+                    Some(age) if config.stall_intervals > 0 && age > interval * config.stall_intervals => {
+                        warn!(
+                            "Frame for {} was captured {} s ago",
+                            config.profiles[profile].name,
+                            age.as_secs()
+                        );
+                    }
+                    _ => {
+                        last_sampled[profile] = Instant::now();
+
+                        if let Some(stalled) = restarted[profile].take() {
+                            info!("Sampling {} again after restarting", config.profiles[profile].name);
+
+                            let payload = json!({
+                                "event": "restarted",
+                                "stalled_secs": stalled.as_secs(),
+                            });
+                            mqtt.publish(
+                                format!("{}/watchdog", config.profiles[profile].topic_prefix),
+                                payload.to_string(),
+                                false,
+                                &updates,
+                            )
+                            .await;
+                        }
+                    }
+                }
+
+                let transition = machines[profile].update(&config.profiles[profile], values);
+
+                if let Some(summary) = cycles[profile].update(&config.profiles[profile], values, transition) {
+                    publish_summary(&mqtt, &config.profiles[profile], summary, &updates).await;
+                }
+
+                let paused = is_paused(&config.profiles[profile]);
+
+                // A blinking display is the machine itself pausing, not
+                // sampling being paused through the enable topic. Only sent
+                // when it changes, so it's retained, and a change while
+                // sampling is paused is sent once it resumes.
+                let any_blinking = blinks[profile].iter().any(blink::Tracker::blinking);
+                if any_blinking != blinking[profile] && !paused {
+                    blinking[profile] = any_blinking;
+                    let topic = format!("{}/paused", config.profiles[profile].topic_prefix);
+                    mqtt.publish(topic, any_blinking.to_string(), true, &updates).await;
+                }
+
+                if transition == Some(EventKind::CycleFinished) {
+                    reminders[profile].start();
+                }
+
+                if let Some(vibration) = &config.profiles[profile].vibration {
+                    let detector = &mut vibrations[profile];
+                    let (change, flipped) = detector.update(vibration, sampled.vibration);
+
+                    if let Some(change) = change {
+                        updates.send(Update::Vibration(profile, change, detector.vibrating()));
+                    }
+
+                    // Retained, like the cycle summary, as it only changes
+                    // when the drum starts or stops.
+                    if let Some(vibrating) = flipped.filter(|_| !paused) {
+                        let topic = format!("{}/vibrating", config.profiles[profile].topic_prefix);
+                        mqtt.publish(topic, vibrating.to_string(), true, &updates).await;
+                    }
+                }
+
+                if let Some(door) = &config.profiles[profile].door {
+                    let tracker = &mut doors[profile];
+                    if transition == Some(EventKind::CycleFinished) {
+                        tracker.finished();
+                    }
+
+                    let (difference, flipped) = tracker.update(door, &sampled.door);
+                    if let Some(difference) = difference {
+                        updates.send(Update::Door(profile, difference, tracker.open()));
+                    }
+
+                    let prefix = &config.profiles[profile].topic_prefix;
+                    if flipped == Some(true) {
+                        reminders[profile].stop();
+                    }
+
+                    if let Some(open) = flipped.filter(|_| !paused) {
+                        let topic = format!("{}/door_open", prefix);
+                        mqtt.publish(topic, open.to_string(), true, &updates).await;
+                    }
+                    if let Some(unretrieved) = tracker.unretrieved(door).filter(|_| !paused) {
+                        let topic = format!("{}/unretrieved", prefix);
+                        mqtt.publish(topic, unretrieved.to_string(), true, &updates).await;
+                    }
+                }
+
+                // A washer may finish without any value changing.
+                let publish_start = Instant::now();
+                if (!indices.is_empty() || transition.is_some()) && !paused {
+                    sinks.write(&Batch {
+                        profile: &config.profiles[profile],
+                        previous: &previous,
+                        values,
+                        quality: &quality[profile],
+                        indices: &indices,
+                        transition,
+                        reminder: None,
+                    });
+                }
+                updates.send(Update::Timings(profile, Timings {
+                    publish: publish_start.elapsed(),
+                    ..sampled.timings
+                }));
+
+                if let Some(publishing) = config.frame_publishing.as_ref().filter(|_| !paused) {
+                    let interval = Duration::from_secs(publishing.interval_secs);
+                    if published_frame[profile].is_none_or(|time| time.elapsed() >= interval) {
+                        published_frame[profile] = Some(Instant::now());
+
+                        publish_frame(
+                            &mqtt,
+                            config.profiles[profile].clone(),
+                            publishing.annotate.then(|| (samples[profile].clone(), values.clone())),
+                            image.clone(),
+                            &updates,
+                        );
+                    }
+                }
+
+                if let Some(publishing) = config.debug_publishing.as_ref().filter(|_| !paused) {
+                    let interval = Duration::from_secs(publishing.interval_secs);
+                    if published_debug[profile].is_none_or(|time| time.elapsed() >= interval) {
+                        published_debug[profile] = Some(Instant::now());
+                        publish_samples(&mqtt, &config.profiles[profile], &samples[profile], &updates);
+                    }
+                }
+
+                updates.send(Update::Frame(profile, image));
+            },
+            _ = time::sleep_until(next) => {
+                let now = Instant::now();
+
+                let stalled = config
+                    .profiles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, profile)| !profile.markers.is_empty() && !is_paused(profile))
+                    .filter_map(|(idx, profile)| {
+                        let interval = sample_interval(profile, config.refresh_rate(refresh_rate));
+                        let stalled = now.duration_since(last_sampled[idx]);
+                        (config.stall_intervals > 0 && stalled > interval * config.stall_intervals)
+                            .then_some((idx, stalled))
+                    })
+                    .collect::<Vec<_>>();
+
+                for (idx, stalled) in stalled {
+                    warn!(
+                        "No frame sampled for {} in {} s, reopening its webcam",
+                        config.profiles[idx].name,
+                        stalled.as_secs()
+                    );
+
+                    sources[idx] = None;
+                    last_sampled[idx] = now;
+                    // Counted from the first stall while the source stays down.
+                    restarted[idx] = Some(restarted[idx].map_or(stalled, |previous| previous + stalled));
+                }
+
+                // Only ends on a disconnect, or if it panicked.
+                if config.stall_intervals > 0 && mqtt.event_loop.is_finished() {
+                    warn!("MQTT event loop stopped, reconnecting");
+                    mqtt = Mqtt::connect(&config, updates.clone(), enabled_tx.clone(), ack_tx.clone());
+                    sinks = make_sinks(&config, &mqtt);
+                }
+
+                for (idx, profile) in config.profiles.iter().enumerate() {
+                    let Some(settings) = &profile.reminders else {
+                        continue;
+                    };
+                    let Some((count, waited_mins)) = reminders[idx].due(settings) else {
+                        continue;
+                    };
+                    if is_paused(profile) {
+                        continue;
+                    }
+
+                    info!("Reminding of {}, {} min after it finished", profile.name, waited_mins);
+
+                    let payload = json!({
+                        "count": count,
+                        "waited_mins": waited_mins,
+                    });
+                    mqtt.publish(
+                        format!("{}/reminder", profile.topic_prefix),
+                        payload.to_string(),
+                        false,
+                        &updates,
+                    )
+                    .await;
+
+                    sinks.write(&Batch {
+                        profile,
+                        previous: &values[idx],
+                        values: &values[idx],
+                        quality: &quality[idx],
+                        indices: &[],
+                        transition: None,
+                        reminder: Some(waited_mins),
+                    });
+                }
+
+                for (profile_idx, profile) in config.profiles.iter().enumerate() {
+                    let next_sample = &mut next_sample[profile_idx];
+
+                    let due = (0..profile.markers.len())
+                        .filter(|&idx| next_sample[idx] <= now)
+                        .collect::<Vec<_>>();
+
+                    if due.is_empty() {
+                        continue;
+                    }
+
+                    for &idx in &due {
+                        let interval = profile.markers[idx]
+                            .refresh_secs
+                            .map_or(config.refresh_rate(refresh_rate), Duration::from_secs);
+                        next_sample[idx] = now
+                            + profile
+                                .schedule
+                                .as_ref()
+                                .map_or(interval, |schedule| schedule.wait(interval));
+                    }
+
+                    // Nothing is expected to be sampled, so the watchdog
+                    // starts over.
+                    if is_paused(profile) || !profile.schedule.as_ref().is_none_or(Schedule::is_active) {
+                        last_sampled[profile_idx] = now;
+                        continue;
+                    }
+
+                    fetch(&config, &mut sources, &references, &decodes, profile_idx, due);
+                }
+            },
+        }
+    }
+
+    mqtt.shutdown().await;
+}
+
+/// Takes in a profile's newly sampled markers, holding the values of
+/// displays that blink while paused, and passes each on to `report`.
+/// Returns the indices of the markers taken in, and of those that changed.
+pub fn take_markers(
+    profile: &Profile,
+    markers: Vec<(usize, Vec<f32>, Value, Quality)>,
+    samples: &mut [Vec<f32>],
+    values: &mut [Value],
+    quality: &mut [Quality],
+    blinks: &mut [blink::Tracker],
+    report: &dyn Fn(usize, Vec<f32>, Value, Quality),
+) -> (Vec<usize>, Vec<usize>) {
+    let mut changed = vec![];
+    let len = values.len();
+
+    // Markers may have been removed while the frame was fetched.
+    let indices = markers
+        .into_iter()
+        .filter(|&(idx, ..)| idx < len)
+        .map(|(idx, new_samples, value, new_quality)| {
+            let (value, held) = if profile.markers[idx].blinks_when_paused {
+                let (held_value, blinking) = blinks[idx].update(value.clone());
+                (held_value, value.is_null() && blinking)
+            } else {
+                (value, false)
+            };
+
+            // A sample close to the threshold can flip the value without
+            // looking different, and a blank sample of a blinking display
+            // looks different without changing it.
+            if (!held && !looks_same(&samples[idx], &new_samples)) || values[idx] != value {
+                changed.push(idx);
+            }
+
+            samples[idx].clone_from(&new_samples);
+            values[idx] = value.clone();
+            quality[idx] = new_quality;
+            report(idx, new_samples, value, new_quality);
+            idx
+        })
+        .collect();
+
+    (indices, changed)
+}
+
+/// Whether two sets of samples of a marker are close enough that it can't
+/// have changed.
+fn looks_same(old: &[f32], new: &[f32]) -> bool {
+    // Roughly the noise between frames of a static display.
+    const TOLERANCE: f32 = 1. / 64.;
+
+    old.len() == new.len() && old.iter().zip(new).all(|(a, b)| (a - b).abs() < TOLERANCE)
+}
+
+/// Encodes and publishes a frame in the background, with the samples and
+/// values drawn on if given.
+fn publish_frame(
+    mqtt: &Mqtt,
+    profile: Profile,
+    overlay: Option<(Vec<Vec<f32>>, Vec<Value>)>,
+    image: ColorImage,
+    updates: &Arc<Updates>,
+) {
+    let client = mqtt.client.clone();
+    let updates = updates.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let overlay = overlay.as_ref().map(|(samples, values)| api::Overlay {
+            profile: &profile,
+            samples,
+            values,
+        });
+
+        let result = api::frame_jpeg(&image, overlay).and_then(|jpeg| {
+            let topic = format!("{}/frame", profile.topic_prefix);
+            Ok(client.try_publish(topic, QoS::AtLeastOnce, true, jpeg)?)
+        });
+
+        if let Err(e) = result {
+            updates.send(Update::Error(format!("Error publishing frame: {}", e)));
+        }
+    });
+}
+
+/// Publishes the summary of a finished cycle, retained, and appends it to the
+/// history in the background.
+async fn publish_summary(
+    mqtt: &Mqtt,
+    profile: &Profile,
+    summary: cycle::Summary,
+    updates: &Arc<Updates>,
+) {
+    let payload = serde_json::to_string(&summary).unwrap();
+    mqtt.publish(
+        format!("{}/cycle", profile.topic_prefix),
+        payload,
+        true,
+        updates,
+    )
+    .await;
+
+    let updates = updates.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = cycle::append(&summary) {
+            updates.send(Update::Error(format!("Error saving cycle summary: {}", e)));
+        }
+    });
+}
+
+/// Indices of the markers whose values are published, which leaves out the
+/// ambient reference and the camera's clock.
+pub fn published_indices(profile: &Profile) -> Vec<usize> {
+    let ambient_reference = profile
+        .ambient_reference
+        .as_ref()
+        .and_then(|reference| reference.find(&profile.markers));
+
+    (0..profile.markers.len())
+        .filter(|&idx| Some(idx) != ambient_reference && !reads_clock(profile, idx))
+        .collect()
+}
+
+/// Publishes the retained meta topics of every profile's markers that
+/// changed since they were last published, and clears those of markers that
+/// are gone.
+async fn publish_meta(
+    mqtt: &Mqtt,
+    config: &Config,
+    published: &mut HashMap<String, String>,
+    updates: &Updates,
+) {
+    let meta = config
+        .profiles
+        .iter()
+        .flat_map(|profile| {
+            laundry_machine_core::meta_messages(
+                &profile.topic_prefix,
+                &profile.markers,
+                &published_indices(profile),
+                &profile.time_remaining,
+            )
+        })
+        .chain(
+            config
+                .mqtt
+                .bridge_topic
+                .iter()
+                .flat_map(|base| bridge::messages(base, config)),
+        )
+        .map(|message| (message.topic, message.payload))
+        .collect::<HashMap<_, _>>();
+
+    for topic in published.keys().filter(|topic| !meta.contains_key(*topic)) {
+        mqtt.publish(topic.clone(), String::new(), true, updates)
+            .await;
+    }
+
+    for (topic, payload) in &meta {
+        if published.get(topic) != Some(payload) {
+            mqtt.publish(topic.clone(), payload.clone(), true, updates)
+                .await;
+        }
+    }
+
+    *published = meta;
+}
+
+/// Whether the marker with the given index reads the camera's clock.
+pub fn reads_clock(profile: &Profile, idx: usize) -> bool {
+    profile.frame_clock.as_ref().is_some_and(|clock| {
+        profile
+            .markers
+            .get(idx)
+            .is_some_and(|marker| clock.reads(&marker.name))
+    })
+}
+
+fn presence_topics(config: &Config) -> Vec<&str> {
+    config
+        .profiles
+        .iter()
+        .filter_map(|profile| Some(profile.presence.as_ref()?.topic.as_str()))
+        .collect()
+}
+
+/// The shortest time between samples of any of a profile's markers.
+fn sample_interval(profile: &Profile, refresh_rate: Duration) -> Duration {
+    profile
+        .markers
+        .iter()
+        .map(|marker| {
+            marker
+                .refresh_secs
+                .map_or(refresh_rate, Duration::from_secs)
+        })
+        .min()
+        .unwrap_or(refresh_rate)
+}
+
+/// Publishes the luminance at each point of each marker as a JSON array.
+fn publish_samples(mqtt: &Mqtt, profile: &Profile, samples: &[Vec<f32>], updates: &Updates) {
+    for (marker, samples) in profile.markers.iter().zip(samples) {
+        if samples.is_empty() {
+            continue;
+        }
+
+        let topic = format!("{}/debug/{}", profile.topic_prefix, marker.name);
+        let payload = serde_json::to_string(samples).unwrap();
+
+        if let Err(e) = mqtt
+            .client
+            .try_publish(&topic, QoS::AtMostOnce, false, payload)
+        {
+            updates.send(Update::Error(format!("Error publishing {}: {}", topic, e)));
+        }
+    }
+}
+
+/// Saves a sampled frame in the background.
+fn record(dir: PathBuf, profile: Profile, sampled: &Sampled, updates: &Arc<Updates>) {
+    let image = sampled.image.clone();
+    let time = sampled.time;
+    let markers = sampled
+        .markers
+        .iter()
+        .map(|(idx, samples, value, _)| (*idx, samples.clone(), value.clone()))
+        .collect::<Vec<_>>();
+    let updates = updates.clone();
+
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = recording::record(&dir, &profile, &image, time, &markers) {
+            updates.send(Update::Error(format!("Error recording frame: {}", e)));
+        }
+    });
+}
+
+/// Where `online` and `offline` are published, retained, as the app starts
+/// and stops, for each profile, and `paused` while it's paused.
+fn availability_topics(config: &Config) -> Vec<String> {
+    config
+        .profiles
+        .iter()
+        .map(|profile| availability_topic(&profile.topic_prefix))
+        .collect()
+}
+
+fn availability_topic(prefix: &str) -> String {
+    format!("{}/availability", prefix)
+}
+
+/// A retained topic to pause a profile with, such as during maintenance.
+fn enable_topic(prefix: &str) -> String {
+    format!("{}/enable", prefix)
+}
+
+/// Any message here acknowledges the profile's finished laundry.
+fn ack_topic(prefix: &str) -> String {
+    format!("{}/ack", prefix)
+}
+
+fn parse_enabled(payload: &[u8]) -> Option<bool> {
+    match String::from_utf8_lossy(payload)
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        "true" | "on" | "1" => Some(true),
+        "false" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(samples: &[f32], retries: usize) -> Decoded {
+        Decoded {
+            samples: samples.to_vec(),
+            threshold: 0.5,
+            value: Value::Bool(true),
+            quality: Quality {
+                retries,
+                threshold: 0.5,
+            },
+        }
+    }
+
+    #[test]
+    fn reads_same_on_the_same_side_of_the_threshold() {
+        let decoded = decoded(&[0.2, 0.8], 0);
+
+        assert!(decoded.reads_same(&[0.2, 0.8], 0.5));
+        assert!(decoded.reads_same(&[0.3, 0.6], 0.5));
+    }
+
+    #[test]
+    fn reads_differently_across_the_threshold() {
+        let decoded = decoded(&[0.2, 0.499], 0);
+
+        assert!(!decoded.reads_same(&[0.2, 0.501], 0.5));
+        assert!(!decoded.reads_same(&[0.2, 0.499], 0.4));
+        assert!(!decoded.reads_same(&[0.2], 0.5));
+    }
+
+    #[test]
+    fn reads_same_after_retries_only_if_unchanged() {
+        let decoded = decoded(&[0.2, 0.8], 3);
+
+        assert!(decoded.reads_same(&[0.2, 0.8], 0.5));
+        assert!(!decoded.reads_same(&[0.21, 0.8], 0.5));
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    cmp::Reverse,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A previous version of the config file.
+pub struct Backup {
+    pub path: PathBuf,
+    pub time: SystemTime,
+}
+
+impl Backup {
+    /// How long ago this backup was made, e.g. "5 minutes ago".
+    #[cfg(feature = "gui")]
+    pub fn age(&self) -> String {
+        let secs = self.time.elapsed().unwrap_or_default().as_secs();
+
+        match secs {
+            0..=59 => format!("{} seconds ago", secs),
+            60..=3599 => format!("{} minutes ago", secs / 60),
+            3600..=86399 => format!("{} hours ago", secs / 3600),
+            _ => format!("{} days ago", secs / 86400),
+        }
+    }
+}
+
+fn backup_dir(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("backups")
+}
+
+/// Copies the current config file into the backup directory, and removes
+/// all but the `keep` most recent backups.
+pub fn create(config_path: &Path, keep: usize) -> io::Result<()> {
+    let dir = backup_dir(config_path);
+    fs::create_dir_all(&dir)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    fs::copy(config_path, dir.join(format!("config-{}.toml", timestamp)))?;
+
+    for old in list(config_path).into_iter().skip(keep) {
+        fs::remove_file(old.path)?;
+    }
+
+    Ok(())
+}
+
+/// All backups of the config file, newest first.
+pub fn list(config_path: &Path) -> Vec<Backup> {
+    let Ok(entries) = fs::read_dir(backup_dir(config_path)) else {
+        return vec![];
+    };
+
+    let mut backups = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let millis = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix("config-")?
+                .strip_suffix(".toml")?
+                .parse()
+                .ok()?;
+
+            Some(Backup {
+                path,
+                time: UNIX_EPOCH + Duration::from_millis(millis),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    backups.sort_by_key(|backup| Reverse(backup.time));
+    backups
+}
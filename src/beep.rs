@@ -0,0 +1,92 @@
+//! Recognizing the beeps a machine plays when it finishes, from the
+//! microphone levels.
+
+use std::collections::VecDeque;
+
+use crate::audio::BLOCK;
+
+/// Turns levels, one per [`BLOCK`], into the lengths in ms of alternating
+/// beeps and gaps, from the start of the first beep to the end of the last.
+#[cfg(feature = "gui")]
+pub fn pattern(levels: &[f32], threshold: f32) -> Vec<u32> {
+    let loud = levels.iter().map(|&level| level > threshold);
+    let mut runs = runs(loud);
+
+    if runs.first().is_some_and(|&(loud, _)| !loud) {
+        runs.remove(0);
+    }
+    if runs.last().is_some_and(|&(loud, _)| !loud) {
+        runs.pop();
+    }
+
+    runs.into_iter().map(|(_, length)| length).collect()
+}
+
+/// Lengths in ms of the runs of loud and quiet blocks.
+fn runs(loud: impl Iterator<Item = bool>) -> Vec<(bool, u32)> {
+    let block = BLOCK.as_millis() as u32;
+    let mut runs: Vec<(bool, u32)> = vec![];
+
+    for loud in loud {
+        match runs.last_mut() {
+            Some((run, length)) if *run == loud => *length += block,
+            _ => runs.push((loud, block)),
+        }
+    }
+
+    runs
+}
+
+/// Whether a beep or gap is close enough in length to the one trained,
+/// allowing for a couple of blocks of jitter.
+fn close(length: u32, trained: u32) -> bool {
+    let slack = (trained * 3 / 10).max(2 * BLOCK.as_millis() as u32);
+    length.abs_diff(trained) <= slack
+}
+
+/// Follows a single profile's levels, looking for its pattern.
+#[derive(Default)]
+pub struct Matcher {
+    history: VecDeque<bool>,
+}
+
+impl Matcher {
+    /// Takes in the latest level, returning whether the pattern just
+    /// finished playing.
+    pub fn update(&mut self, pattern: &[u32], threshold: f32, level: f32) -> bool {
+        let loud = level > threshold;
+        self.history.push_back(loud);
+
+        // Room for the whole pattern, however sloppily it's played.
+        let total = pattern.iter().sum::<u32>() * 2 / BLOCK.as_millis() as u32;
+        while self.history.len() > total as usize + 2 {
+            self.history.pop_front();
+        }
+
+        // Only checked right after a beep ends.
+        let len = self.history.len();
+        if pattern.is_empty() || loud || len < 2 || !self.history[len - 2] {
+            return false;
+        }
+
+        let runs = runs(self.history.iter().take(len - 1).copied());
+        let Some(played) = runs
+            .len()
+            .checked_sub(pattern.len())
+            .map(|start| &runs[start..])
+        else {
+            return false;
+        };
+
+        let matched = played
+            .iter()
+            .zip(pattern)
+            .all(|(&(_, length), &trained)| close(length, trained));
+
+        if matched {
+            self.history.clear();
+        }
+
+        matched
+    }
+}
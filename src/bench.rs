@@ -0,0 +1,146 @@
+//! Timing each stage of the pipeline against a recorded frame, to measure
+//! changes to sampling and decoding on the hardware it runs on.
+
+use std::{
+    error::Error,
+    fs,
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde_json::Value;
+
+use crate::{
+    backend::{self, Pipeline},
+    cli::Args,
+    recording,
+    sink::{Batch, NullSink, Sinks},
+    source,
+};
+
+/// The stages of a sample, in order.
+const STAGES: [&str; 4] = ["frame decode", "sample", "values", "publish"];
+
+/// Runs the active profile's pipeline on the frame `iterations` times, with
+/// a sink that builds messages but doesn't send them, and prints the timings
+/// of each stage. `path` is an image or a recorded frame's directory.
+pub fn run(args: &Args, path: &Path, iterations: usize) -> Result<(), Box<dyn Error>> {
+    let config = args.effective_config();
+    let profile = config.profile();
+    let profile_idx = config.active_profile;
+
+    let path = if path.is_dir() {
+        path.join(recording::FRAME_FILE)
+    } else {
+        path.to_owned()
+    };
+    let bytes = fs::read(&path)?;
+    let iterations = iterations.max(1);
+
+    let indices = (0..profile.markers.len()).collect::<Vec<_>>();
+    let crop = backend::crop(&config, profile, false);
+    // Loaded on the first iteration, like on the first frame.
+    let reference = backend::SharedReference::default();
+    let mut sinks = Sinks::only(vec![Box::new(NullSink {
+        payload_format: config.mqtt.payload_format,
+    })]);
+
+    let mut samples = vec![vec![]; profile.markers.len()];
+    let mut values = vec![Value::Null; profile.markers.len()];
+    let mut quality = vec![Default::default(); profile.markers.len()];
+    let mut blinks = vec![];
+    blinks.resize_with(profile.markers.len(), Default::default);
+
+    let mut timings = vec![Vec::with_capacity(iterations); STAGES.len()];
+
+    for _ in 0..iterations {
+        // Every iteration decodes every marker, as the frame never changes
+        // and would otherwise only be decoded once.
+        let pipeline = Pipeline::new(
+            profile,
+            indices.clone(),
+            reference.clone(),
+            Default::default(),
+        );
+
+        let start = Instant::now();
+        let frame = source::Frame::decode(&bytes, crop.as_deref(), SystemTime::now())?;
+        let decoded = Instant::now();
+
+        let sampled = pipeline.run(profile_idx, frame.image, frame.time, &|_| {});
+        let sampled_at = Instant::now();
+
+        let previous = values.clone();
+        let (indices, _) = backend::take_markers(
+            profile,
+            sampled.markers,
+            &mut samples,
+            &mut values,
+            &mut quality,
+            &mut blinks,
+            &|_, _, _, _| {},
+        );
+        let indices = indices
+            .into_iter()
+            .filter(|&idx| !backend::reads_clock(profile, idx))
+            .collect::<Vec<_>>();
+        let taken = Instant::now();
+
+        sinks.write(&Batch {
+            profile,
+            previous: &previous,
+            values: &values,
+            quality: &quality,
+            indices: &indices,
+            transition: None,
+            reminder: None,
+        });
+        let published = Instant::now();
+
+        for (stage, time) in timings.iter_mut().zip([
+            decoded - start,
+            sampled_at - decoded,
+            taken - sampled_at,
+            published - taken,
+        ]) {
+            stage.push(time);
+        }
+    }
+
+    println!(
+        "{} iterations of {} markers on {}",
+        iterations,
+        profile.markers.len(),
+        path.display()
+    );
+    println!(
+        "{:<14}{:>10}{:>10}{:>10}{:>10}",
+        "stage", "min", "median", "p95", "max"
+    );
+
+    let mut totals = vec![Duration::ZERO; iterations];
+    for (name, times) in STAGES.iter().zip(&mut timings) {
+        for (total, time) in totals.iter_mut().zip(times.iter()) {
+            *total += *time;
+        }
+        print_row(name, times);
+    }
+    print_row("total", &mut totals);
+
+    Ok(())
+}
+
+fn print_row(name: &str, times: &mut [Duration]) {
+    times.sort();
+    let percentile = |p: usize| times[(times.len() - 1) * p / 100];
+    let ms = |time: Duration| format!("{:.3}ms", time.as_secs_f64() * 1000.);
+
+    println!(
+        "{:<14}{:>10}{:>10}{:>10}{:>10}",
+        name,
+        ms(times[0]),
+        ms(percentile(50)),
+        ms(percentile(95)),
+        ms(times[times.len() - 1])
+    );
+}
@@ -0,0 +1,151 @@
+//! Holding on to the value of a display that blinks while the machine is
+//! paused, rather than publishing it alternating with nothing.
+
+use std::collections::VecDeque;
+
+use serde_json::Value;
+
+/// Samples looked back on. A display that stays blank for longer than this
+/// went off rather than blinking.
+const WINDOW: usize = 6;
+
+/// Follows a single marker's values.
+#[derive(Default)]
+pub struct Tracker {
+    /// The latest values, blank ones included.
+    history: VecDeque<Value>,
+    blinking: bool,
+}
+
+impl Tracker {
+    /// Takes in a marker's latest value, returning the value to publish in
+    /// its place, which is the last one shown while it blinks, and whether
+    /// it's blinking. It takes a few samples to tell.
+    pub fn update(&mut self, value: Value) -> (Value, bool) {
+        self.history.push_back(value.clone());
+        if self.history.len() > WINDOW {
+            self.history.pop_front();
+        }
+
+        let mut shown = self.history.iter().filter(|value| !value.is_null());
+        let Some(last_shown) = self.history.iter().rev().find(|value| !value.is_null()) else {
+            self.blinking = false;
+            return (value, false);
+        };
+
+        // Alternating between blank and a single value a few times over.
+        let flips = self
+            .history
+            .iter()
+            .zip(self.history.iter().skip(1))
+            .filter(|(a, b)| a.is_null() != b.is_null())
+            .count();
+        let blinking = flips >= 3 && shown.all(|value| value == last_shown);
+        self.blinking = blinking;
+
+        if value.is_null() && blinking {
+            (last_shown.clone(), blinking)
+        } else {
+            (value, blinking)
+        }
+    }
+
+    pub fn blinking(&self) -> bool {
+        self.blinking
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// What's published after each of the values, and whether it's blinking.
+    fn run(tracker: &mut Tracker, values: &[Value]) -> Vec<(Value, bool)> {
+        values
+            .iter()
+            .map(|value| tracker.update(value.clone()))
+            .collect()
+    }
+
+    const BLANK: Value = Value::Null;
+
+    #[test]
+    fn steady_values_pass_through() {
+        let mut tracker = Tracker::default();
+
+        assert_eq!(
+            run(&mut tracker, &[json!(5), json!(5), json!(6)]),
+            [(json!(5), false), (json!(5), false), (json!(6), false)],
+        );
+        assert!(!tracker.blinking());
+    }
+
+    #[test]
+    fn holds_the_value_while_blinking() {
+        let mut tracker = Tracker::default();
+
+        assert_eq!(
+            run(
+                &mut tracker,
+                &[json!(12), BLANK, json!(12), BLANK, json!(12)]
+            ),
+            [
+                (json!(12), false),
+                (BLANK, false),
+                (json!(12), false),
+                (json!(12), true),
+                (json!(12), true),
+            ],
+        );
+        assert!(tracker.blinking());
+    }
+
+    #[test]
+    fn goes_blank_once_the_display_stays_off() {
+        let mut tracker = Tracker::default();
+        let values = [
+            json!(12),
+            BLANK,
+            json!(12),
+            BLANK,
+            BLANK,
+            BLANK,
+            BLANK,
+            BLANK,
+        ];
+
+        assert_eq!(
+            run(&mut tracker, &values)[5..],
+            [(json!(12), true), (BLANK, false), (BLANK, false)],
+        );
+        assert!(!tracker.blinking());
+    }
+
+    #[test]
+    fn stops_blinking_once_the_display_is_steady() {
+        let mut tracker = Tracker::default();
+        run(&mut tracker, &[json!(12), BLANK, json!(12), BLANK]);
+        assert!(tracker.blinking());
+
+        let steady = run(&mut tracker, &vec![json!(12); 6]);
+        assert_eq!(steady.last(), Some(&(json!(12), false)));
+        assert!(!tracker.blinking());
+    }
+
+    #[test]
+    fn changing_values_arent_blinking() {
+        let mut tracker = Tracker::default();
+
+        assert_eq!(
+            run(&mut tracker, &[json!(12), BLANK, json!(13), BLANK]),
+            [
+                (json!(12), false),
+                (BLANK, false),
+                (json!(13), false),
+                (BLANK, false),
+            ],
+        );
+    }
+}
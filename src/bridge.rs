@@ -0,0 +1,306 @@
+//! Following Zigbee2MQTT's bridge conventions, for tooling that discovers
+//! such bridges: `{base}/bridge/state`, `{base}/bridge/info` and
+//! `{base}/bridge/devices` are published retained, each profile is a device
+//! whose state is published to `{base}/{name}`, and commands are accepted on
+//! `{base}/{name}/set`.
+
+use laundry_machine_core::Message;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    backend,
+    config::{Config, Profile},
+};
+
+/// A command on a device's set topic.
+#[derive(Debug, PartialEq)]
+pub enum SetCommand {
+    Enabled(bool),
+    Ack,
+}
+
+pub fn state_topic(base: &str) -> String {
+    format!("{}/bridge/state", base)
+}
+
+pub fn state_payload(online: bool) -> String {
+    json!({ "state": if online { "online" } else { "offline" } }).to_string()
+}
+
+/// The profile's name as a device, which is its topic prefix under the base
+/// topic if it's there.
+pub fn friendly_name<'a>(base: &str, profile: &'a Profile) -> &'a str {
+    profile
+        .topic_prefix
+        .strip_prefix(base)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .filter(|rest| !rest.is_empty())
+        .unwrap_or(&profile.name)
+}
+
+pub fn device_topic(base: &str, profile: &Profile) -> String {
+    format!("{}/{}", base, friendly_name(base, profile))
+}
+
+pub fn set_topic(base: &str, profile: &Profile) -> String {
+    format!("{}/set", device_topic(base, profile))
+}
+
+/// Reads a device's set payload, such as `{"enabled": false}` or
+/// `{"ack": true}`.
+pub fn parse_set(payload: &[u8]) -> Result<Vec<SetCommand>, String> {
+    let payload: Map<String, Value> = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+    let mut commands = vec![];
+
+    if let Some(enabled) = payload.get("enabled") {
+        let enabled = match enabled {
+            Value::Bool(enabled) => *enabled,
+            Value::String(state) if state.eq_ignore_ascii_case("on") => true,
+            Value::String(state) if state.eq_ignore_ascii_case("off") => false,
+            _ => return Err("enabled must be true, false, ON or OFF".into()),
+        };
+        commands.push(SetCommand::Enabled(enabled));
+    }
+
+    if payload.contains_key("ack") {
+        commands.push(SetCommand::Ack);
+    }
+
+    if commands.is_empty() {
+        return Err("expected enabled or ack".into());
+    }
+
+    Ok(commands)
+}
+
+/// The retained `bridge/info` and `bridge/devices` messages.
+pub fn messages(base: &str, config: &Config) -> Vec<Message> {
+    let info = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "coordinator": { "type": "laundry-machine-mqtt" },
+        "permit_join": false,
+        "restart_required": false,
+        "config": { "mqtt": { "base_topic": base } },
+    });
+
+    let devices = config
+        .profiles
+        .iter()
+        .map(|profile| device(base, profile))
+        .collect::<Vec<_>>();
+
+    vec![
+        Message {
+            topic: format!("{}/bridge/info", base),
+            payload: info.to_string(),
+        },
+        Message {
+            topic: format!("{}/bridge/devices", base),
+            payload: Value::Array(devices).to_string(),
+        },
+    ]
+}
+
+fn device(base: &str, profile: &Profile) -> Value {
+    let indices = backend::published_indices(profile);
+    let mut exposes =
+        laundry_machine_core::meta(&profile.markers, &indices, &profile.time_remaining)
+            .into_iter()
+            .map(|(name, meta)| expose(&name, &meta))
+            .collect::<Vec<_>>();
+
+    exposes.push(json!({
+        "type": "binary",
+        "name": "enabled",
+        "property": "enabled",
+        "access": 2,
+        "value_on": true,
+        "value_off": false,
+    }));
+    exposes.push(json!({
+        "type": "enum",
+        "name": "ack",
+        "property": "ack",
+        "access": 2,
+        "values": [true],
+    }));
+
+    json!({
+        "friendly_name": friendly_name(base, profile),
+        "ieee_address": ieee_address(&profile.topic_prefix),
+        "type": "EndDevice",
+        "supported": true,
+        "interview_completed": true,
+        "definition": {
+            "model": profile.machine.name(),
+            "vendor": "laundry-machine-mqtt",
+            "description": profile.name,
+            "exposes": exposes,
+        },
+    })
+}
+
+/// A value described by [`laundry_machine_core::meta`] as a read-only
+/// expose.
+fn expose(name: &str, meta: &Map<String, Value>) -> Value {
+    let mut expose = json!({
+        "name": name,
+        "property": name,
+        "access": 1,
+    });
+
+    match meta.get("value").and_then(Value::as_str) {
+        Some("boolean") => {
+            expose["type"] = "binary".into();
+            expose["value_on"] = true.into();
+            expose["value_off"] = false.into();
+        }
+        Some("integer") => {
+            expose["type"] = "numeric".into();
+            for (key, name) in [("min", "value_min"), ("max", "value_max"), ("unit", "unit")] {
+                if let Some(value) = meta.get(key) {
+                    expose[name] = value.clone();
+                }
+            }
+        }
+        _ => expose["type"] = "text".into(),
+    }
+
+    expose
+}
+
+/// A made up, but stable, address for a device, which tooling keys devices
+/// by.
+fn ieee_address(topic_prefix: &str) -> String {
+    // FNV-1a, which unlike the std hasher is the same across builds.
+    let hash = topic_prefix
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+
+    format!("0x{:016x}", hash)
+}
+
+/// A device's state, with every published value by name like
+/// [`laundry_machine_core::messages`] publishes them.
+pub fn device_state(profile: &Profile, values: &[Value]) -> Value {
+    let indices = backend::published_indices(profile);
+    let mut state = laundry_machine_core::named_values(&profile.markers, values)
+        .into_iter()
+        .filter(|(idx, ..)| indices.contains(idx))
+        .map(|(idx, name, value)| {
+            let value = match (value, &profile.markers[idx].unknown) {
+                (Value::Null, Some(unknown)) => unknown.clone(),
+                _ => value.clone(),
+            };
+            (name.to_owned(), value)
+        })
+        .collect::<Map<_, _>>();
+
+    let composite = &profile.time_remaining;
+    if state.contains_key(&composite.hour) && state.contains_key(&composite.minute) {
+        state.remove(&composite.hour);
+        state.remove(&composite.minute);
+        let time_remaining =
+            laundry_machine_core::time_remaining(&profile.markers, values, composite);
+        state.insert(
+            composite.topic.clone(),
+            time_remaining.map_or(Value::Null, |seconds| composite.value(seconds)),
+        );
+    }
+
+    Value::Object(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn profile(name: &str, topic_prefix: &str) -> Profile {
+        Profile {
+            name: name.to_owned(),
+            topic_prefix: topic_prefix.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn parse(payload: Value) -> Result<Vec<SetCommand>, String> {
+        parse_set(payload.to_string().as_bytes())
+    }
+
+    #[test]
+    fn parses_enabled() {
+        assert_eq!(
+            parse(json!({ "enabled": false })),
+            Ok(vec![SetCommand::Enabled(false)])
+        );
+        assert_eq!(
+            parse(json!({ "enabled": "ON" })),
+            Ok(vec![SetCommand::Enabled(true)])
+        );
+        assert_eq!(
+            parse(json!({ "enabled": "off" })),
+            Ok(vec![SetCommand::Enabled(false)])
+        );
+        assert!(parse(json!({ "enabled": 1 })).is_err());
+        assert!(parse(json!({ "enabled": "maybe" })).is_err());
+    }
+
+    #[test]
+    fn parses_ack_with_any_value() {
+        assert_eq!(parse(json!({ "ack": true })), Ok(vec![SetCommand::Ack]));
+        assert_eq!(parse(json!({ "ack": "" })), Ok(vec![SetCommand::Ack]));
+    }
+
+    #[test]
+    fn parses_both() {
+        assert_eq!(
+            parse(json!({ "ack": true, "enabled": true })),
+            Ok(vec![SetCommand::Enabled(true), SetCommand::Ack]),
+        );
+    }
+
+    #[test]
+    fn rejects_other_payloads() {
+        assert_eq!(parse(json!({})), Err("expected enabled or ack".to_owned()));
+        assert_eq!(
+            parse(json!({ "state": "ON" })),
+            Err("expected enabled or ack".to_owned())
+        );
+        assert!(parse(json!(["enabled"])).is_err());
+        assert!(parse_set(b"ON").is_err());
+    }
+
+    #[test]
+    fn names_devices_after_their_topic_under_the_base() {
+        let base = "zigbee2mqtt";
+
+        assert_eq!(
+            friendly_name(base, &profile("Washer", "zigbee2mqtt/washer")),
+            "washer"
+        );
+        assert_eq!(
+            friendly_name(base, &profile("Washer", "zigbee2mqtt/")),
+            "Washer"
+        );
+        assert_eq!(
+            friendly_name(base, &profile("Washer", "zigbee2mqttx/washer")),
+            "Washer"
+        );
+        assert_eq!(friendly_name(base, &profile("Washer", "laundry")), "Washer");
+        assert_eq!(
+            set_topic(base, &profile("Washer", "zigbee2mqtt/washer")),
+            "zigbee2mqtt/washer/set",
+        );
+    }
+
+    #[test]
+    fn addresses_are_stable_and_distinct() {
+        assert_eq!(ieee_address(""), "0xcbf29ce484222325");
+        assert_eq!(ieee_address("washer"), ieee_address("washer"));
+        assert_ne!(ieee_address("washer"), ieee_address("dryer"));
+    }
+}
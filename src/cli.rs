@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use tracing::error;
+
+use crate::{addon, config::Config};
+
+#[derive(Clone, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Path to the config file, instead of the one in the user's config
+    /// directory.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Override the MQTT broker host.
+    #[arg(long)]
+    pub mqtt_host: Option<String>,
+
+    /// Override the webcam snapshot URL of the active profile.
+    #[arg(long)]
+    pub webcam_url: Option<String>,
+
+    /// Seconds between samples.
+    #[arg(long, default_value_t = 15)]
+    pub refresh_secs: u64,
+
+    /// Run the sampling and publishing loop without a GUI, using only the
+    /// config file.
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Like `--headless`, but showing the values, connection state and logs
+    /// in the terminal instead of logging to it.
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Serve the HTTP API and calibration page on this address, such as
+    /// `0.0.0.0:8080`. Anyone who can reach it can move the markers.
+    #[arg(long)]
+    pub http: Option<String>,
+
+    /// Run as a Home Assistant add-on, reading the config from the add-on
+    /// options and using the supervisor's MQTT broker unless one is set.
+    #[arg(long)]
+    pub addon: bool,
+
+    /// Expose values and state over D-Bus on the given bus.
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_enum)]
+    pub dbus: Option<DbusBus>,
+
+    /// Record every sampled frame with its decoded values in this directory.
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+
+    /// Log filter such as `debug`, overriding the config. `RUST_LOG` takes
+    /// precedence over both.
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Log JSON lines instead of plain text.
+    #[arg(long)]
+    pub log_json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum Command {
+    /// Install a systemd user service that runs in headless mode.
+    Install,
+    /// Fetch a single frame and print the decoded values as JSON.
+    SampleOnce,
+    /// Fetch a single frame, publish the decoded values and exit.
+    Publish,
+    /// Check the config for problems, exiting with an error if there are any.
+    Validate,
+    /// Decode a local image file with the configured markers and print the
+    /// values as JSON.
+    Decode { image: PathBuf },
+    /// Decode recorded frames with the current config and compare the
+    /// results to their labels, exiting with an error on any difference.
+    Replay { directory: PathBuf },
+    /// Check the health of an instance serving the HTTP API on the `--http`
+    /// address, exiting with an error if it's unhealthy.
+    Healthcheck {
+        /// Seconds since the last frame and publish before the instance is
+        /// considered unhealthy. Defaults to four sample intervals.
+        #[arg(long)]
+        max_age: Option<u64>,
+    },
+    /// Time each stage of decoding and publishing an image, or a recorded
+    /// frame's directory, with the active profile.
+    Bench {
+        frame: PathBuf,
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DbusBus {
+    Session,
+    System,
+}
+
+impl Args {
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.config.clone().or_else(Config::default_path)
+    }
+
+    /// Loads the config file, if there is one.
+    pub fn load_config(&self) -> Option<Config> {
+        if self.addon {
+            return addon::load_config()
+                .map_err(|e| error!("Error loading add-on options: {}", e))
+                .ok();
+        }
+
+        let path = self.config_path()?;
+        if !path.exists() {
+            return None;
+        }
+
+        match Config::load(&path) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                error!("Error loading config from {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// The config file with the command line overrides applied, or the
+    /// default config if there is no config file.
+    pub fn effective_config(&self) -> Config {
+        let mut config = self.load_config().unwrap_or_default();
+        config.migrate();
+        self.apply(&mut config);
+        config
+    }
+
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(host) = &self.mqtt_host {
+            config.mqtt.host.clone_from(host);
+        }
+
+        if let Some(url) = &self.webcam_url {
+            let webcam = &mut config.profile_mut().webcam;
+            webcam.url.clone_from(url);
+            webcam.preset = None;
+            webcam.home_assistant = None;
+        }
+
+        if let Some(dir) = &self.record {
+            config.record_directory = Some(dir.clone());
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Reading the time a camera burns into its frames, which is when a frame was
+//! really captured, unlike when it arrived through a caching proxy.
+
+use std::time::SystemTime;
+
+use chrono::{Local, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use laundry_machine_core::Marker;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameClock {
+    /// Names of the seven segment markers over the overlay's hours, minutes
+    /// and seconds.
+    pub hour: String,
+    pub minute: String,
+    #[serde(default)]
+    pub second: Option<String>,
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self {
+            hour: "clock_hour".to_owned(),
+            minute: "clock_minute".to_owned(),
+            second: Some("clock_second".to_owned()),
+        }
+    }
+}
+
+impl FrameClock {
+    /// Whether the marker with the given name reads the clock rather than
+    /// the machine, so it isn't published.
+    pub fn reads(&self, name: &str) -> bool {
+        name == self.hour || name == self.minute || self.second.as_deref() == Some(name)
+    }
+
+    /// When the frame was captured, assuming it was in the last day.
+    pub fn time(&self, markers: &[Marker], values: &[Value]) -> Option<SystemTime> {
+        let read = |name: &str| {
+            let idx = markers.iter().position(|marker| marker.name == name)?;
+            u32::try_from(values.get(idx)?.as_u64()?).ok()
+        };
+
+        let second = match &self.second {
+            Some(name) => read(name)?,
+            None => 0,
+        };
+        let time = NaiveTime::from_hms_opt(read(&self.hour)?, read(&self.minute)?, second)?;
+
+        let now = Local::now();
+        let mut captured = now
+            .date_naive()
+            .and_time(time)
+            .and_local_timezone(Local)
+            .earliest()?;
+
+        // Read just after midnight from a frame just before, allowing for
+        // the clocks being a bit apart.
+        if captured > now + TimeDelta::minutes(1) {
+            captured -= TimeDelta::days(1);
+        }
+
+        Some(captured.into())
+    }
+}
@@ -1,196 +1,883 @@
-use egui::Pos2;
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use directories::ProjectDirs;
+use laundry_machine_core::{Interpolation, Marker, PayloadFormat, SegmentPattern, TimeRemaining};
+use rumqttc::MqttOptions;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use tracing::error;
+
+#[cfg(feature = "gui")]
+use crate::backup;
+use crate::{
+    addon,
+    alarm::Alarm,
+    audio::Audio,
+    clock::FrameClock,
+    door::Door,
+    lighting::{AmbientReference, Lighting},
+    machine::Machine,
+    notification::{Notifications, Presence, Provider},
+    ptz::PtzConfig,
+    reminder::Reminders,
+    schedule::Schedule,
+    source::Preset,
+    vibration::Vibration,
+    webhook::Webhook,
+};
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub mqtt: MqttConfig,
+    /// Also write decoded values to InfluxDB, if set.
+    #[serde(default)]
+    pub influxdb: Option<InfluxConfig>,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub notifications: Notifications,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Save every sampled frame with its decoded values in this directory.
+    #[serde(default)]
+    pub record_directory: Option<PathBuf>,
+    /// If set, only publish values whose markers look different from the
+    /// previous frame, and all values at least this many seconds apart.
+    #[serde(default)]
+    pub heartbeat_secs: Option<u64>,
+    /// If set, publish to each topic at most once per this many seconds,
+    /// with the last value of each window published once it closes.
+    #[serde(default)]
+    pub throttle_secs: Option<u64>,
+    /// Also publish frames over MQTT, if set.
+    #[serde(default)]
+    pub frame_publishing: Option<FramePublishing>,
+    /// Also publish each marker's luminance samples over MQTT, if set.
+    #[serde(default)]
+    pub debug_publishing: Option<DebugPublishing>,
+    /// Trade responsiveness for less CPU, network and battery use, if set.
+    #[serde(default)]
+    pub low_power: Option<LowPower>,
+    /// Reopen a profile's webcam when none of its frames have been sampled
+    /// for this many sample intervals, or never if 0. MQTT is only
+    /// reconnected if its event loop stopped.
+    #[serde(default = "default_stall_intervals")]
+    pub stall_intervals: u32,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default)]
+    pub active_profile: usize,
+    #[serde(default)]
+    pub keybindings: Keybindings,
+    /// How many previous versions of the config file to keep.
+    #[serde(default = "default_backup_count")]
+    pub backup_count: usize,
+    /// Save changes periodically and on exit, rather than only when asked.
+    #[serde(default)]
+    pub autosave: bool,
+
+    // Fields from before profiles existed, moved into a profile by `migrate`.
+    #[serde(default, skip_serializing)]
+    webcam: Option<WebcamConfig>,
+    #[serde(default, skip_serializing)]
+    markers: Option<Vec<Marker>>,
+    #[serde(default, skip_serializing)]
+    luminance_threshold: Option<f32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mqtt: Default::default(),
+            influxdb: None,
+            webhooks: Default::default(),
+            notifications: Default::default(),
+            logging: Default::default(),
+            record_directory: None,
+            heartbeat_secs: None,
+            throttle_secs: None,
+            frame_publishing: None,
+            debug_publishing: None,
+            low_power: None,
+            stall_intervals: default_stall_intervals(),
+            profiles: Default::default(),
+            active_profile: Default::default(),
+            keybindings: Default::default(),
+            backup_count: default_backup_count(),
+            autosave: false,
+            webcam: None,
+            markers: None,
+            luminance_threshold: None,
+        }
+    }
+}
+
+fn default_backup_count() -> usize {
+    10
+}
+
+fn default_stall_intervals() -> u32 {
+    10
+}
+
+/// Everything specific to a single machine.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
     #[serde(default)]
     pub webcam: WebcamConfig,
     #[serde(default)]
     pub markers: Vec<Marker>,
     #[serde(default = "default_luminance_threshold")]
     pub luminance_threshold: f32,
+    /// Thresholds to use instead for other lighting. The first one that
+    /// applies is used.
+    #[serde(default)]
+    pub lighting: Vec<Lighting>,
+    /// The characters seven segment displays can show.
+    #[serde(default = "default_segment_patterns")]
+    pub segment_patterns: Vec<SegmentPattern>,
+    /// Adjust all thresholds to the ambient light seen by one of the
+    /// markers.
+    #[serde(default)]
+    pub ambient_reference: Option<AmbientReference>,
+    /// Pixels around each point to search for the brightest one, to make up
+    /// for camera shake.
+    #[serde(default)]
+    pub peak_search: usize,
+    /// How points between pixel centers are read.
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// A frame from when the markers were placed, to compare against.
+    #[serde(default)]
+    pub reference_frame: Option<PathBuf>,
+    /// Move the markers along with the camera by up to this many pixels,
+    /// compared to the reference frame.
+    #[serde(default)]
+    pub max_shift: usize,
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    /// Which values the time remaining is read from, and how it's
+    /// published.
+    #[serde(default)]
+    pub time_remaining: TimeRemaining,
+    /// What kind of machine this is, which decides when cycles really
+    /// start and finish.
+    #[serde(default)]
+    pub machine: Machine,
+    /// Chime locally when a cycle finishes.
+    #[serde(default)]
+    pub alarm: Option<Alarm>,
+    /// Only notify the people who are home.
+    #[serde(default)]
+    pub presence: Option<Presence>,
+    /// When to sample, rather than all the time.
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// Tell whether the drum is running from the frame itself.
+    #[serde(default)]
+    pub vibration: Option<Vibration>,
+    /// Tell whether the door is open from the frame.
+    #[serde(default)]
+    pub door: Option<Door>,
+    /// Remind of laundry left in after a cycle finished.
+    #[serde(default)]
+    pub reminders: Option<Reminders>,
+    /// Read when each frame was captured from the camera's timestamp.
+    #[serde(default)]
+    pub frame_clock: Option<FrameClock>,
+    /// Listen to the machine through a microphone.
+    #[serde(default)]
+    pub audio: Option<Audio>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: "default".to_owned(),
+            webcam: Default::default(),
+            markers: Default::default(),
+            luminance_threshold: default_luminance_threshold(),
+            lighting: vec![],
+            segment_patterns: default_segment_patterns(),
+            ambient_reference: None,
+            peak_search: 0,
+            interpolation: Interpolation::Nearest,
+            reference_frame: None,
+            max_shift: 0,
+            topic_prefix: default_topic_prefix(),
+            time_remaining: Default::default(),
+            machine: Machine::Generic,
+            alarm: None,
+            presence: None,
+            schedule: None,
+            vibration: None,
+            door: None,
+            reminders: None,
+            frame_clock: None,
+            audio: None,
+        }
+    }
+}
+
+fn default_topic_prefix() -> String {
+    "laundry-machine".to_owned()
+}
+
+impl Config {
+    /// `~/.config/laundry-machine-mqtt/config.toml`, or the platform's
+    /// equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "laundry-machine-mqtt")
+            .map(|dirs| dirs.config_dir().join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&contents)?;
+        config.migrate();
+        Ok(config)
+    }
+
+    /// Moves settings from before profiles existed into a profile, and makes
+    /// sure there's always at least one profile selected.
+    pub fn migrate(&mut self) {
+        if self.webcam.is_some() || self.markers.is_some() || self.luminance_threshold.is_some() {
+            let profile = Profile {
+                webcam: self.webcam.take().unwrap_or_default(),
+                markers: self.markers.take().unwrap_or_default(),
+                luminance_threshold: self
+                    .luminance_threshold
+                    .take()
+                    .unwrap_or_else(default_luminance_threshold),
+                ..Default::default()
+            };
+
+            self.profiles.insert(0, profile);
+        }
+
+        if self.profiles.is_empty() {
+            self.profiles.push(Profile::default());
+        }
+
+        if self.active_profile >= self.profiles.len() {
+            self.active_profile = 0;
+        }
+    }
+
+    /// Time between samples, given the one asked for on the command line.
+    pub fn refresh_rate(&self, requested: Duration) -> Duration {
+        match &self.low_power {
+            Some(low_power) => requested.max(Duration::from_secs(low_power.refresh_secs)),
+            None => requested,
+        }
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profiles[self.active_profile]
+    }
+
+    pub fn profile_mut(&mut self) -> &mut Profile {
+        &mut self.profiles[self.active_profile]
+    }
+
+    /// Writes the config to disk, backing up the previous version if it
+    /// differs.
+    #[cfg(feature = "gui")]
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = toml::to_string_pretty(self)?;
+
+        match fs::read_to_string(path) {
+            Ok(previous) if previous == contents => return Ok(()),
+            Ok(_) if self.backup_count > 0 => backup::create(path, self.backup_count)?,
+            _ => {}
+        }
+
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+impl Config {
+    /// A copy of this config with the MQTT and webcam passwords removed, for
+    /// sharing or moving to another machine.
+    pub fn without_secrets(&self) -> Self {
+        let mut config = self.clone();
+        config.mqtt.password = None;
+        if let Some(influxdb) = &mut config.influxdb {
+            influxdb.token = None;
+        }
+        for provider in &mut config.notifications.providers {
+            provider.clear_secrets();
+        }
+        for person in &mut config.notifications.people {
+            person
+                .providers
+                .iter_mut()
+                .for_each(Provider::clear_secrets);
+        }
+        for profile in &mut config.profiles {
+            profile.webcam.password = None;
+            if let Some(camera) = &mut profile.webcam.home_assistant {
+                camera.token = None;
+            }
+        }
+        config
+    }
+
+    /// Replaces this config with an imported one, keeping the current
+    /// passwords if the imported config doesn't contain any.
+    pub fn import(&mut self, mut imported: Config) {
+        imported.migrate();
+
+        if imported.mqtt.password.is_none() {
+            imported.mqtt.password = self.mqtt.password.take();
+        }
+
+        if let (Some(imported), Some(current)) = (&mut imported.influxdb, &mut self.influxdb) {
+            if imported.token.is_none() {
+                imported.token = current.token.take();
+            }
+        }
+
+        for (imported, current) in imported
+            .notifications
+            .providers
+            .iter_mut()
+            .zip(&mut self.notifications.providers)
+        {
+            imported.keep_secrets(current);
+        }
+
+        for (imported, current) in imported
+            .notifications
+            .people
+            .iter_mut()
+            .zip(&mut self.notifications.people)
+        {
+            for (imported, current) in imported.providers.iter_mut().zip(&mut current.providers) {
+                imported.keep_secrets(current);
+            }
+        }
+
+        for profile in &mut imported.profiles {
+            if profile.webcam.password.is_some() {
+                continue;
+            }
+
+            if let Some(current) = self.profiles.iter_mut().find(|p| p.name == profile.name) {
+                profile.webcam.password = current.webcam.password.take();
+            }
+        }
+
+        for profile in &mut imported.profiles {
+            let Some(imported) = &mut profile.webcam.home_assistant else {
+                continue;
+            };
+
+            let current = self
+                .profiles
+                .iter_mut()
+                .find(|p| p.name == profile.name)
+                .and_then(|p| p.webcam.home_assistant.as_mut());
+            if let (None, Some(current)) = (&imported.token, current) {
+                imported.token = current.token.take();
+            }
+        }
+
+        *self = imported;
+    }
+}
+
+fn default_segment_patterns() -> Vec<SegmentPattern> {
+    SegmentPattern::defaults()
 }
 
 fn default_luminance_threshold() -> f32 {
     0.4
 }
 
-#[derive(Default, Serialize, Deserialize)]
+/// Publishing the latest frame of each profile as a retained JPEG to
+/// `{prefix}/frame`, such as for a Home Assistant MQTT camera.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct FramePublishing {
+    /// Minimum seconds between frames.
+    #[serde(default = "default_frame_interval_secs")]
+    pub interval_secs: u64,
+    /// Draw the sampled points and decoded values on the frame.
+    #[serde(default = "default_annotate")]
+    pub annotate: bool,
+}
+
+impl Default for FramePublishing {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_frame_interval_secs(),
+            annotate: default_annotate(),
+        }
+    }
+}
+
+fn default_frame_interval_secs() -> u64 {
+    60
+}
+
+fn default_annotate() -> bool {
+    true
+}
+
+/// Publishing raw samples to `{prefix}/debug/{marker}`, for troubleshooting
+/// an instance without a screen.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebugPublishing {
+    /// Minimum seconds between samples of a profile.
+    #[serde(default = "default_debug_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for DebugPublishing {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_debug_interval_secs(),
+        }
+    }
+}
+
+fn default_debug_interval_secs() -> u64 {
+    10
+}
+
+/// Settings for battery powered devices on a slow network.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LowPower {
+    /// Minimum seconds between samples.
+    #[serde(default = "default_low_power_refresh_secs")]
+    pub refresh_secs: u64,
+    /// Frames of streams and local cameras are scaled down to this width.
+    #[serde(default = "default_low_power_max_width")]
+    pub max_width: u32,
+    /// Seconds between MQTT pings.
+    #[serde(default = "default_low_power_keep_alive_secs")]
+    pub keep_alive_secs: u64,
+}
+
+impl Default for LowPower {
+    fn default() -> Self {
+        Self {
+            refresh_secs: default_low_power_refresh_secs(),
+            max_width: default_low_power_max_width(),
+            keep_alive_secs: default_low_power_keep_alive_secs(),
+        }
+    }
+}
+
+fn default_low_power_refresh_secs() -> u64 {
+    60
+}
+
+fn default_low_power_max_width() -> u32 {
+    640
+}
+
+fn default_low_power_keep_alive_secs() -> u64 {
+    120
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// A filter such as `info` or `laundry_machine_mqtt=debug`.
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// Log JSON lines instead of plain text.
+    #[serde(default)]
+    pub json: bool,
+    /// Also log to daily rotated files in this directory.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// How many daily log files to keep.
+    #[serde(default = "default_keep_log_files")]
+    pub keep_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            json: false,
+            directory: None,
+            keep_files: default_keep_log_files(),
+        }
+    }
+}
+
+fn default_log_level() -> String {
+    "info".to_owned()
+}
+
+fn default_keep_log_files() -> usize {
+    7
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keybindings {
+    /// Deselects the marker being edited, or closes the active dialog.
+    pub cancel: Shortcut,
+    pub quit: Shortcut,
+    /// Ask for confirmation before quitting through the quit shortcut.
+    pub confirm_quit: bool,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            cancel: Shortcut::new(Default::default(), "Escape"),
+            quit: Shortcut::new(
+                ShortcutModifiers {
+                    command: true,
+                    ..Default::default()
+                },
+                "Q",
+            ),
+            confirm_quit: true,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Keybindings {
+    pub fn shortcuts_mut(&mut self) -> [(&'static str, &mut Shortcut); 2] {
+        [("Cancel", &mut self.cancel), ("Quit", &mut self.quit)]
+    }
+}
+
+/// A keyboard shortcut, stored the way egui stores its own so that headless
+/// builds without egui read the same config.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shortcut {
+    #[serde(default)]
+    pub modifiers: ShortcutModifiers,
+    /// The name of egui's `Key` variant, e.g. `Escape` or `Q`.
+    pub logical_key: String,
+}
+
+impl Shortcut {
+    pub fn new(modifiers: ShortcutModifiers, logical_key: &str) -> Self {
+        Self {
+            modifiers,
+            logical_key: logical_key.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortcutModifiers {
+    pub alt: bool,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub mac_cmd: bool,
+    /// Ctrl, or Cmd on macOS.
+    pub command: bool,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct MqttConfig {
     pub host: String,
     pub port: u16,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Name of the OS keyring entry holding the password.
+    #[serde(default)]
+    pub password_entry: Option<String>,
+    /// Accept config changes over MQTT.
+    #[serde(default)]
+    pub remote_config: bool,
+    /// How values are written in payloads.
+    #[serde(default)]
+    pub payload_format: PayloadFormat,
+    /// Also follow Zigbee2MQTT's bridge conventions under this base topic,
+    /// such as `laundry-machine-mqtt`, for tooling that discovers such
+    /// bridges.
+    #[serde(default)]
+    pub bridge_topic: Option<String>,
+}
+
+impl MqttConfig {
+    pub fn options(&self, client_id: &str) -> MqttOptions {
+        let mut options = MqttOptions::new(client_id, &self.host, self.port);
+        options.set_keep_alive(Duration::from_secs(5));
+        // Room for published frames, which are well over the default 10 KiB.
+        options.set_max_packet_size(16 * 1024 * 1024, 16 * 1024 * 1024);
+
+        if let Some((username, password)) = self.credentials() {
+            options.set_credentials(username, password);
+        }
+
+        options
+    }
+
+    /// Username and password to connect with, where `LMM_MQTT_USERNAME` and
+    /// `LMM_MQTT_PASSWORD` take precedence over the keyring and the
+    /// configured values.
+    pub fn credentials(&self) -> Option<(String, String)> {
+        credentials(
+            &self.username,
+            &self.password,
+            &self.password_entry,
+            "LMM_MQTT_USERNAME",
+            "LMM_MQTT_PASSWORD",
+        )
+    }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct WebcamConfig {
     pub url: String,
+    /// A camera of a service such as Frigate, used instead of the URL.
+    #[serde(default)]
+    pub preset: Option<Preset>,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Name of the OS keyring entry holding the password.
+    #[serde(default)]
+    pub password_entry: Option<String>,
+    /// Move the camera to a preset before sampling.
+    #[serde(default)]
+    pub ptz: Option<PtzConfig>,
+    /// Fetch snapshots of a Home Assistant camera entity instead of using
+    /// the URL and credentials above.
+    #[serde(default)]
+    pub home_assistant: Option<HomeAssistantCamera>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Marker {
-    pub name: String,
-    pub ty: MarkerType,
+impl WebcamConfig {
+    /// Username and password for the webcam, where `LMM_WEBCAM_USERNAME` and
+    /// `LMM_WEBCAM_PASSWORD` take precedence over the keyring and the
+    /// configured values.
+    pub fn credentials(&self) -> Option<(String, String)> {
+        credentials(
+            &self.username,
+            &self.password,
+            &self.password_entry,
+            "LMM_WEBCAM_USERNAME",
+            "LMM_WEBCAM_PASSWORD",
+        )
+    }
+
+    /// The snapshot URL, or the preset's, with the credentials, if any,
+    /// embedded as userinfo.
+    pub fn image_url(&self) -> String {
+        let url = self
+            .preset
+            .as_ref()
+            .map_or_else(|| self.url.clone(), Preset::url);
+        let Some((username, password)) = self.credentials() else {
+            return url;
+        };
+
+        match url.split_once("://") {
+            Some((scheme, rest)) => format!(
+                "{scheme}://{}:{}@{rest}",
+                percent_encode(&username),
+                percent_encode(&password)
+            ),
+            None => url,
+        }
+    }
 }
 
-impl Marker {
-    pub fn new(ty: MarkerType) -> Self {
-        Self {
-            name: Default::default(),
-            ty,
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize)]
-pub enum MarkerType {
-    Point {
-        pos: Pos2,
-        size: f32,
-    },
-    SevenSegment {
-        start: Pos2,
-        end: Pos2,
-        bottom: Pos2,
-        digits: usize,
-        spacing: f32,
-        size: f32,
-    },
-}
-
-pub struct Point {
-    pub pos: Pos2,
-    pub size: f32,
-}
-
-impl Point {
-    pub fn sample<T: Copy>(&self, pixels: &[T], width: usize, height: usize) -> T {
-        let x = (self.pos.x * width as f32).round() as usize;
-        let y = (self.pos.y * height as f32).round() as usize;
-
-        pixels[y * width + x]
-    }
-}
-
-impl MarkerType {
-    pub fn get_points(&self) -> Vec<Point> {
-        match *self {
-            MarkerType::Point { pos, size } => vec![Point { pos, size }],
-            MarkerType::SevenSegment {
-                start,
-                end,
-                bottom,
-                digits,
-                spacing,
-                size,
-            } => {
-                let length = (end - start).length();
-                let direction = (end - start).normalized();
-                let tangent = bottom - start;
-
-                let segment_length = (length - spacing * (digits as f32 - 1.)) / digits as f32;
-
-                //  aa
-                // f  b
-                //  gg
-                // e  c
-                //  dd
-
-                (0..digits)
-                    .flat_map(|n| {
-                        let start = start + direction * (n as f32 * (segment_length + spacing));
-                        let center = start + direction * segment_length / 2.;
-                        let end = start + direction * segment_length;
-
-                        let a = center - tangent;
-                        let b = end - tangent / 2.;
-                        let c = end + tangent / 2.;
-                        let d = center + tangent;
-                        let e = start + tangent / 2.;
-                        let f = start - tangent / 2.;
-                        let g = center;
-
-                        vec![a, b, c, d, e, f, g]
-                    })
-                    .map(|pos| Point { pos, size })
-                    .collect()
-            }
+/// A camera entity in Home Assistant, whose snapshots are fetched through
+/// its REST API, so that the camera's own credentials only live there.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HomeAssistantCamera {
+    /// Such as `http://homeassistant.local:8123`, or empty to go through
+    /// the supervisor when running as an add-on.
+    pub url: String,
+    /// Such as `camera.laundry_room`.
+    pub entity_id: String,
+    /// A long-lived access token.
+    pub token: Option<String>,
+    /// Name of the OS keyring entry holding the token.
+    #[serde(default)]
+    pub token_entry: Option<String>,
+}
+
+impl HomeAssistantCamera {
+    /// The access token, where `LMM_HOMEASSISTANT_TOKEN` takes precedence
+    /// over the keyring and the configured value. The supervisor's token is
+    /// used as an add-on, but only sent to the supervisor.
+    pub fn token(&self) -> Option<String> {
+        env::var("LMM_HOMEASSISTANT_TOKEN")
+            .ok()
+            .or_else(|| self.token_entry.as_deref().and_then(keyring_password))
+            .or_else(|| self.token.clone())
+            .or_else(|| {
+                self.through_supervisor()
+                    .then(|| env::var("SUPERVISOR_TOKEN").ok())
+                    .flatten()
+            })
+    }
+
+    fn through_supervisor(&self) -> bool {
+        self.api_url() == addon::SUPERVISOR_CORE_URL
+    }
+
+    /// The API's base URL, without a trailing slash.
+    pub fn api_url(&self) -> String {
+        match self.url.trim_end_matches('/') {
+            "" => addon::SUPERVISOR_CORE_URL.to_owned(),
+            url => url.to_owned(),
         }
     }
 
-    pub fn value(&self, samples: &[f32], mut threshold: f32) -> serde_json::Value {
-        match self {
-            MarkerType::Point { .. } => {
-                let Some(value) = samples.first() else {
-                    return Value::Null;
-                };
+    pub fn snapshot_url(&self) -> String {
+        format!("{}/api/camera_proxy/{}", self.api_url(), self.entity_id)
+    }
+}
 
-                Value::Bool(*value > threshold)
-            }
-            MarkerType::SevenSegment { .. } => {
-                let mut threshold_change = 0.01;
-
-                loop {
-                    let number = samples
-                        .chunks(7)
-                        .map(|segment| {
-                            seven_segment_to_number(
-                                &segment
-                                    .iter()
-                                    .map(|&value| value > threshold)
-                                    .collect::<Vec<_>>(),
-                            )
-                        })
-                        .collect::<Option<Vec<_>>>()
-                        .map(|digits| {
-                            digits
-                                .iter()
-                                .fold(0i32, |acc, value| acc * 10 + value)
-                                .into()
-                        });
-
-                    if let Some(number) = number {
-                        return Value::Number(number);
-                    }
-
-                    threshold += threshold_change;
-                    threshold_change *= -1.5;
-
-                    if !(0.0..=1.0).contains(&threshold) {
-                        return Value::Null;
-                    }
-                }
-            }
+/// An InfluxDB v2 bucket to write values to.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InfluxConfig {
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    pub token: Option<String>,
+    /// Name of the OS keyring entry holding the token.
+    #[serde(default)]
+    pub token_entry: Option<String>,
+}
+
+impl InfluxConfig {
+    /// The API token, where `LMM_INFLUXDB_TOKEN` takes precedence over the
+    /// keyring and the configured value.
+    pub fn token(&self) -> Option<String> {
+        env::var("LMM_INFLUXDB_TOKEN")
+            .ok()
+            .or_else(|| self.token_entry.as_deref().and_then(keyring_password))
+            .or_else(|| self.token.clone())
+    }
+}
+
+fn credentials(
+    username: &Option<String>,
+    password: &Option<String>,
+    password_entry: &Option<String>,
+    username_var: &str,
+    password_var: &str,
+) -> Option<(String, String)> {
+    let username = env::var(username_var).ok().or_else(|| username.clone())?;
+    let password = env::var(password_var)
+        .ok()
+        .or_else(|| password_entry.as_deref().and_then(keyring_password))
+        .or_else(|| password.clone())
+        .unwrap_or_default();
+
+    Some((username, password))
+}
+
+const KEYRING_SERVICE: &str = "laundry-machine-mqtt";
+
+/// How long until a failed keyring lookup is tried again, since a keyring
+/// may still be locked at boot.
+const KEYRING_RETRY: Duration = Duration::from_secs(30);
+
+/// Keyring lookups can be slow, and credentials are needed every frame.
+/// Failed lookups are kept as the time they failed.
+fn keyring_cache() -> &'static Mutex<HashMap<String, Result<String, Instant>>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Result<String, Instant>>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn keyring_password(entry: &str) -> Option<String> {
+    match keyring_cache().lock().unwrap().get(entry) {
+        Some(Ok(password)) => return Some(password.clone()),
+        Some(Err(failed)) if failed.elapsed() < KEYRING_RETRY => return None,
+        _ => {}
+    }
+
+    // Not holding the cache, so that other entries don't wait on this one.
+    let result = keyring::Entry::new(KEYRING_SERVICE, entry).and_then(|e| e.get_password());
+    let mut cache = keyring_cache().lock().unwrap();
+
+    match result {
+        Ok(password) => {
+            cache.insert(entry.to_owned(), Ok(password.clone()));
+            Some(password)
+        }
+        Err(e) => {
+            error!("Error reading {} from keyring: {}", entry, e);
+            cache.insert(entry.to_owned(), Err(Instant::now()));
+            None
         }
     }
 }
 
-//  aa
-// f  b
-//  gg
-// e  c
-//  dd
-
-fn seven_segment_to_number(segments: &[bool]) -> Option<i32> {
-    match segments {
-        // aa   bb    cc    dd    ee     ff     gg
-        [true, true, true, true, true, true, false] => Some(0),
-        [false, true, true, false, false, false, false] => Some(1),
-        [true, true, false, true, true, false, true] => Some(2),
-        [true, true, true, true, false, false, true] => Some(3),
-        [false, true, true, false, false, true, true] => Some(4),
-        [true, false, true, true, false, true, true] => Some(5),
-        [true, false, true, true, true, true, true] => Some(6),
-        [true, true, true, false, false, false, false] => Some(7),
-        [true, true, true, true, true, true, true] => Some(8),
-        [true, true, true, true, false, true, true] => Some(9),
-        [false, false, false, false, false, false, false] => Some(0),
-        _ => None,
+#[cfg(feature = "gui")]
+pub fn store_in_keyring(entry: &str, password: &str) -> keyring::Result<()> {
+    keyring::Entry::new(KEYRING_SERVICE, entry)?.set_password(password)?;
+    keyring_cache()
+        .lock()
+        .unwrap()
+        .insert(entry.to_owned(), Ok(password.to_owned()));
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+pub fn delete_from_keyring(entry: &str) -> keyring::Result<()> {
+    keyring_cache().lock().unwrap().remove(entry);
+    keyring::Entry::new(KEYRING_SERVICE, entry)?.delete_password()
+}
+
+pub fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(url: &str) -> HomeAssistantCamera {
+        HomeAssistantCamera {
+            url: url.to_owned(),
+            entity_id: "camera.laundry_room".to_owned(),
+            token: None,
+            token_entry: None,
+        }
+    }
+
+    #[test]
+    fn sends_the_supervisor_token_only_to_the_supervisor() {
+        env::set_var("SUPERVISOR_TOKEN", "supervisor");
+
+        assert_eq!(camera("").token().as_deref(), Some("supervisor"));
+        assert_eq!(
+            camera("http://supervisor/core/").token().as_deref(),
+            Some("supervisor")
+        );
+        assert_eq!(camera("http://homeassistant.local:8123").token(), None);
+        assert_eq!(camera("http://example.com/supervisor/core").token(), None);
+
+        let mut own = camera("http://homeassistant.local:8123");
+        own.token = Some("own".to_owned());
+        assert_eq!(own.token().as_deref(), Some("own"));
     }
 }
@@ -0,0 +1,118 @@
+//! Summaries of finished cycles, published over MQTT and appended to a local
+//! history file.
+
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Local};
+use directories::ProjectDirs;
+use laundry_machine_core::MarkerType;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config::Profile, event::EventKind};
+
+#[derive(Serialize, Deserialize)]
+pub struct Summary {
+    pub profile: String,
+    /// RFC 3339 local time.
+    pub start: String,
+    pub end: String,
+    pub duration_secs: i64,
+    pub max_time_remaining: Option<u64>,
+    /// Anything with letters that a seven segment display showed.
+    pub error_codes: Vec<String>,
+}
+
+struct Cycle {
+    start: DateTime<Local>,
+    max_time_remaining: Option<u64>,
+    error_codes: BTreeSet<String>,
+}
+
+/// Follows a single profile's values through a cycle.
+#[derive(Default)]
+pub struct Tracker {
+    cycle: Option<Cycle>,
+}
+
+impl Tracker {
+    /// Takes in a profile's values after a sample, and whether that started
+    /// or finished a cycle, returning the summary of the cycle it finished,
+    /// if any. Cycles that were already running before the first sample
+    /// aren't summarized.
+    pub fn update(
+        &mut self,
+        profile: &Profile,
+        values: &[Value],
+        transition: Option<EventKind>,
+    ) -> Option<Summary> {
+        if transition == Some(EventKind::CycleStarted) {
+            self.cycle = Some(Cycle {
+                start: Local::now(),
+                max_time_remaining: None,
+                error_codes: BTreeSet::new(),
+            });
+        }
+
+        let cycle = self.cycle.as_mut()?;
+
+        let time_remaining =
+            laundry_machine_core::time_remaining(&profile.markers, values, &profile.time_remaining);
+        cycle.max_time_remaining = cycle.max_time_remaining.max(time_remaining);
+
+        for (idx, _, value) in laundry_machine_core::named_values(&profile.markers, values) {
+            if let (MarkerType::SevenSegment { .. }, Value::String(text)) =
+                (&profile.markers[idx].ty, value)
+            {
+                if text.chars().any(|c| c.is_ascii_alphabetic()) {
+                    cycle.error_codes.insert(text.clone());
+                }
+            }
+        }
+
+        if transition != Some(EventKind::CycleFinished) {
+            return None;
+        }
+
+        self.finish(profile)
+    }
+
+    /// Finishes the cycle for something other than the values, like the
+    /// machine beeping, returning its summary if one was running.
+    pub fn finish(&mut self, profile: &Profile) -> Option<Summary> {
+        let cycle = self.cycle.take()?;
+        let end = Local::now();
+
+        Some(Summary {
+            profile: profile.name.clone(),
+            start: cycle.start.to_rfc3339(),
+            end: end.to_rfc3339(),
+            duration_secs: (end - cycle.start).num_seconds(),
+            max_time_remaining: cycle.max_time_remaining,
+            error_codes: cycle.error_codes.into_iter().collect(),
+        })
+    }
+}
+
+/// Where summaries are appended to, one JSON object per line.
+pub fn history_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "laundry-machine-mqtt")
+        .map(|dirs| dirs.data_dir().join("cycles.jsonl"))
+}
+
+pub fn append(summary: &Summary) -> Result<(), Box<dyn Error>> {
+    let path = history_path().ok_or("no data directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(summary)?)?;
+    Ok(())
+}
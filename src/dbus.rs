@@ -0,0 +1,87 @@
+//! A D-Bus service exposing the current values and machine state, so that
+//! desktop widgets and other local programs don't need MQTT.
+
+use zbus::{blocking::connection, interface};
+
+use crate::{
+    api::{self, SharedStatus},
+    cli::DbusBus,
+};
+
+pub const NAME: &str = "io.github.darkwater.LaundryMachineMqtt";
+const PATH: &str = "/io/github/darkwater/LaundryMachineMqtt";
+
+/// Keeps the service registered for as long as it's alive.
+pub struct Service {
+    _connection: zbus::blocking::Connection,
+}
+
+struct Machine {
+    status: SharedStatus,
+    sample: Box<dyn Fn() + Send + Sync>,
+}
+
+#[interface(name = "io.github.darkwater.LaundryMachineMqtt1")]
+impl Machine {
+    /// Decoded values of the active profile's markers, as a JSON object.
+    #[zbus(property)]
+    fn values(&self) -> String {
+        api::values(&self.status.lock().unwrap()).to_string()
+    }
+
+    /// `running` while there's time remaining, `idle` when it's zero, and
+    /// `unknown` if it can't be read.
+    #[zbus(property)]
+    fn state(&self) -> String {
+        match self.time_remaining_secs() {
+            Some(0) => "idle",
+            Some(_) => "running",
+            None => "unknown",
+        }
+        .to_owned()
+    }
+
+    /// Seconds remaining in the current cycle, or 0 if unknown.
+    #[zbus(property)]
+    fn time_remaining(&self) -> u64 {
+        self.time_remaining_secs().unwrap_or_default()
+    }
+
+    /// Fetches a frame and samples all markers right away.
+    fn sample_now(&self) {
+        (self.sample)();
+    }
+}
+
+impl Machine {
+    fn time_remaining_secs(&self) -> Option<u64> {
+        let status = self.status.lock().unwrap();
+        laundry_machine_core::time_remaining(
+            &status.config.profile().markers,
+            &status.values,
+            &status.config.profile().time_remaining,
+        )
+    }
+}
+
+pub fn serve(
+    bus: DbusBus,
+    status: SharedStatus,
+    sample: impl Fn() + Send + Sync + 'static,
+) -> zbus::Result<Service> {
+    let machine = Machine {
+        status,
+        sample: Box::new(sample),
+    };
+
+    let builder = match bus {
+        DbusBus::Session => connection::Builder::session()?,
+        DbusBus::System => connection::Builder::system()?,
+    };
+
+    let connection = builder.name(NAME)?.serve_at(PATH, machine)?.build()?;
+
+    Ok(Service {
+        _connection: connection,
+    })
+}
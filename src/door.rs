@@ -0,0 +1,97 @@
+//! Telling whether the door is open by comparing a region over it to how it
+//! looked closed, and noticing laundry left in after a cycle finished.
+
+use std::time::{Duration, Instant};
+
+use epaint::pos2;
+use serde::{Deserialize, Serialize};
+
+use crate::region::{self, Region};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Door {
+    /// A region over the door that looks different when it's open.
+    #[serde(flatten)]
+    pub region: Region,
+    /// The region's cells with the door closed, as captured in the GUI.
+    #[serde(default)]
+    pub closed: Vec<f32>,
+    /// Difference from closed above which the door is open.
+    pub threshold: f32,
+    /// Minutes after a cycle finished without the door opening before the
+    /// laundry counts as left in, or 0 to never.
+    pub unretrieved_mins: u64,
+}
+
+impl Default for Door {
+    fn default() -> Self {
+        Self {
+            region: Region {
+                min: pos2(0.3, 0.3),
+                max: pos2(0.7, 0.7),
+            },
+            closed: vec![],
+            threshold: 0.05,
+            unretrieved_mins: 30,
+        }
+    }
+}
+
+/// Follows a single profile's door.
+#[derive(Default)]
+pub struct Tracker {
+    open: Option<bool>,
+    /// When the last cycle finished, until the door opens.
+    finished: Option<Instant>,
+    unretrieved: bool,
+}
+
+impl Tracker {
+    /// Takes in the region's cells of the latest frame, returning how much
+    /// they differ from closed, and the new state if it flipped.
+    pub fn update(&mut self, door: &Door, cells: &[f32]) -> (Option<f32>, Option<bool>) {
+        // Nothing to compare to before closed is captured.
+        let Some(difference) = region::difference(&door.closed, cells) else {
+            return (None, None);
+        };
+
+        let open = difference > door.threshold;
+        if self.open == Some(open) {
+            return (Some(difference), None);
+        }
+
+        self.open = Some(open);
+        if open {
+            self.finished = None;
+        }
+
+        (Some(difference), Some(open))
+    }
+
+    /// Starts waiting for the door to open.
+    pub fn finished(&mut self) {
+        self.finished = Some(Instant::now());
+    }
+
+    /// Stops waiting for the door to open, as if it did.
+    pub fn acknowledge(&mut self) {
+        self.finished = None;
+    }
+
+    /// Whether the laundry has been left in for too long, returning the new
+    /// state if it flipped.
+    pub fn unretrieved(&mut self, door: &Door) -> Option<bool> {
+        let wait = Duration::from_secs(door.unretrieved_mins * 60);
+        let unretrieved =
+            door.unretrieved_mins > 0 && self.finished.is_some_and(|time| time.elapsed() >= wait);
+
+        (unretrieved != self.unretrieved).then(|| {
+            self.unretrieved = unretrieved;
+            unretrieved
+        })
+    }
+
+    pub fn open(&self) -> Option<bool> {
+        self.open
+    }
+}
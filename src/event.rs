@@ -0,0 +1,249 @@
+//! Events derived from changes in the decoded values, for webhooks and
+//! notifications.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::config::Profile;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    ValueChanged,
+    CycleStarted,
+    CycleFinished,
+    /// Laundry is still in after a cycle finished, with the minutes since as
+    /// the value.
+    Reminder,
+}
+
+impl EventKind {
+    #[cfg(feature = "gui")]
+    pub const ALL: [Self; 4] = [
+        Self::ValueChanged,
+        Self::CycleStarted,
+        Self::CycleFinished,
+        Self::Reminder,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ValueChanged => "value-changed",
+            Self::CycleStarted => "cycle-started",
+            Self::CycleFinished => "cycle-finished",
+            Self::Reminder => "reminder",
+        }
+    }
+}
+
+pub struct Event {
+    pub kind: EventKind,
+    pub marker: Option<String>,
+    pub value: Value,
+}
+
+/// The events caused by the markers with the given indices going from
+/// `previous` to `values`, along with the cycle starting or finishing as
+/// decided by [`crate::machine::Tracker`].
+pub fn events(
+    profile: &Profile,
+    previous: &[Value],
+    values: &[Value],
+    indices: &[usize],
+    transition: Option<EventKind>,
+) -> Vec<Event> {
+    let mut events = indices
+        .iter()
+        .filter(|&&idx| previous.get(idx) != values.get(idx))
+        .filter_map(|&idx| {
+            Some(Event {
+                kind: EventKind::ValueChanged,
+                marker: Some(profile.markers.get(idx)?.name.clone()),
+                value: values.get(idx)?.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(kind) = transition {
+        events.push(Event {
+            kind,
+            marker: None,
+            value: json!(laundry_machine_core::time_remaining(
+                &profile.markers,
+                values,
+                &profile.time_remaining
+            )),
+        });
+    }
+
+    events
+}
+
+/// How many samples in a row the time remaining has to be missing for the
+/// display to count as blank, rather than a frame that didn't decode.
+const BLANK_SAMPLES: usize = 3;
+
+/// What the display last showed for the time remaining.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Shown {
+    Time(u64),
+    Blank,
+}
+
+/// Follows a profile's time remaining to tell when the display shows a cycle
+/// starting or finishing.
+///
+/// A cycle starts when time remaining appears, and finishes when it runs out
+/// from the last minute, either reaching zero or the display going blank.
+/// Only a change from something that was seen counts, so neither the first
+/// sample nor one that didn't decode does.
+#[derive(Default)]
+pub struct Countdown {
+    shown: Option<Shown>,
+    /// How many samples in a row the time remaining was missing from.
+    missing: usize,
+}
+
+impl Countdown {
+    /// Takes in the time remaining after a sample, if it was decoded.
+    pub fn update(&mut self, time_remaining: Option<u64>) -> Option<EventKind> {
+        let shown = match time_remaining {
+            Some(secs) => {
+                self.missing = 0;
+                Shown::Time(secs)
+            }
+            None => {
+                self.missing += 1;
+                if self.missing < BLANK_SAMPLES {
+                    return None;
+                }
+                Shown::Blank
+            }
+        };
+
+        match (self.shown.replace(shown), shown) {
+            (Some(Shown::Blank | Shown::Time(0)), Shown::Time(1..)) => {
+                Some(EventKind::CycleStarted)
+            }
+            (Some(Shown::Time(1..=60)), Shown::Blank | Shown::Time(0)) => {
+                Some(EventKind::CycleFinished)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Event {
+    /// The variables available to templates for this event.
+    pub fn variables(&self, profile: &Profile, time_remaining: Option<u64>) -> Map<String, Value> {
+        Map::from_iter([
+            ("event".to_owned(), json!(self.kind.name())),
+            ("profile".to_owned(), json!(profile.name)),
+            ("marker".to_owned(), json!(self.marker)),
+            ("value".to_owned(), self.value.clone()),
+            ("time_remaining".to_owned(), json!(time_remaining)),
+        ])
+    }
+}
+
+/// Replaces each `{{name}}` in `template` with the text of that variable.
+pub fn fill_text(template: &str, variables: &Map<String, Value>) -> String {
+    let mut text = template.to_owned();
+
+    for (name, value) in variables {
+        let value = match value {
+            Value::String(value) => value.clone(),
+            Value::Null => String::new(),
+            value => value.to_string(),
+        };
+        text = text.replace(&format!("{{{{{}}}}}", name), &value);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The events after each of the times remaining, starting from a
+    /// display showing zero.
+    fn events(times: &[Option<u64>]) -> Vec<Option<EventKind>> {
+        let mut countdown = Countdown::default();
+        countdown.update(Some(0));
+        times.iter().map(|&time| countdown.update(time)).collect()
+    }
+
+    #[test]
+    fn starts_when_time_appears() {
+        assert_eq!(
+            events(&[Some(0), Some(3600), Some(3540)]),
+            [None, Some(EventKind::CycleStarted), None],
+        );
+    }
+
+    #[test]
+    fn finishes_when_the_last_minute_runs_out() {
+        assert_eq!(
+            events(&[Some(120), Some(60), Some(0)]),
+            [
+                Some(EventKind::CycleStarted),
+                None,
+                Some(EventKind::CycleFinished)
+            ],
+        );
+    }
+
+    #[test]
+    fn finishes_when_the_display_stays_blank() {
+        assert_eq!(
+            events(&[Some(60), None, None, None, None]),
+            [
+                Some(EventKind::CycleStarted),
+                None,
+                None,
+                Some(EventKind::CycleFinished),
+                None
+            ],
+        );
+    }
+
+    #[test]
+    fn ignores_frames_that_dont_decode() {
+        assert_eq!(
+            events(&[Some(3600), None, Some(3540), None, None, Some(3480)]),
+            [Some(EventKind::CycleStarted), None, None, None, None, None],
+        );
+        assert_eq!(
+            events(&[Some(60), None, Some(0)])[2],
+            Some(EventKind::CycleFinished)
+        );
+    }
+
+    #[test]
+    fn ignores_time_running_out_early() {
+        assert_eq!(
+            events(&[Some(600), Some(0)]),
+            [Some(EventKind::CycleStarted), None],
+        );
+    }
+
+    #[test]
+    fn starts_after_the_display_was_blank() {
+        assert_eq!(
+            events(&[None, None, None, Some(3600)]),
+            [None, None, None, Some(EventKind::CycleStarted)],
+        );
+    }
+
+    #[test]
+    fn doesnt_start_on_the_first_sample() {
+        let mut countdown = Countdown::default();
+        assert_eq!(countdown.update(Some(3600)), None);
+        assert_eq!(countdown.update(Some(3540)), None);
+
+        let mut countdown = Countdown::default();
+        assert_eq!(countdown.update(None), None);
+        assert_eq!(countdown.update(Some(3600)), None);
+    }
+}
@@ -0,0 +1,22 @@
+//! Reading sensors wired to a Raspberry Pi's GPIO pins, such as a door reed
+//! switch or a vibration sensor.
+
+use std::error::Error;
+
+/// Whether the input on a BCM pin is active.
+#[cfg(target_os = "linux")]
+pub fn read(pin: u8, active_low: bool, pull_up: bool) -> Result<bool, Box<dyn Error>> {
+    let pin = rppal::gpio::Gpio::new()?.get(pin)?;
+    let input = if pull_up {
+        pin.into_input_pullup()
+    } else {
+        pin.into_input()
+    };
+
+    Ok(input.is_high() != active_low)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_pin: u8, _active_low: bool, _pull_up: bool) -> Result<bool, Box<dyn Error>> {
+    Err("GPIO is only supported on Linux".into())
+}
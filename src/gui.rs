@@ -0,0 +1,3491 @@
+//! The calibration window, for placing markers on the camera's frame and
+//! watching what they decode to.
+
+use std::{
+    cell::Cell,
+    cmp::Ordering,
+    collections::HashSet,
+    env, fs,
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use eframe::{egui, CreationContext, HardwareAcceleration};
+use egui::{
+    pos2, vec2, CentralPanel, CollapsingHeader, Color32, ColorImage, ComboBox, Context, DragValue,
+    Event, Grid, Key, KeyboardShortcut, Modifiers, Pos2, Rect, ScrollArea, Sense, Slider, Stroke,
+    TextEdit, TextureHandle, TextureOptions, TopBottomPanel, Vec2, ViewportCommand, Widget, Window,
+};
+use serde_json::Value;
+use tracing::{error, info, warn};
+
+use laundry_machine_core::{
+    marker_topic, DigitGroup, Interpolation, Marker, MarkerType, Output, PayloadFormat, Quality,
+    SegmentPattern, TimeUnit,
+};
+
+use crate::{
+    audio, backend, backup, beep, cli, config, event, headless, lighting, logging, machine,
+    notification, recording, region, registration, remote, snap, source, sweep, templates, tune,
+    validate, watch,
+};
+
+/// Screen pixels per frame pixel from which the pixel grid is drawn, with
+/// the pixels each point reads.
+const PIXEL_GRID_ZOOM: f32 = 8.;
+
+/// Opens the window, falling back to software rendering if there's no
+/// usable GPU, and to headless mode if no window can be opened at all.
+pub fn run(args: cli::Args) {
+    if !has_display() {
+        warn!("No display to open a window on, running headless");
+        headless::run(args);
+        return;
+    }
+
+    for hardware_acceleration in [HardwareAcceleration::Preferred, HardwareAcceleration::Off] {
+        let native_options = eframe::NativeOptions {
+            hardware_acceleration,
+            ..Default::default()
+        };
+
+        // Errors after the app started are its own, and not retried.
+        let started = Rc::new(Cell::new(false));
+        let app_args = args.clone();
+        let app_started = started.clone();
+        let result = eframe::run_native(
+            "Laundry Machine MQTT",
+            native_options,
+            Box::new(move |cc| {
+                app_started.set(true);
+                Box::new(MyEguiApp::new(cc, app_args))
+            }),
+        );
+
+        match result {
+            Ok(()) => return,
+            Err(e) if started.get() => {
+                error!("Error running GUI: {}", e);
+                std::process::exit(1);
+            }
+            Err(e) if hardware_acceleration == HardwareAcceleration::Off => {
+                error!("Error opening window: {}", e);
+            }
+            Err(e) => warn!("Error opening window, trying software rendering: {}", e),
+        }
+    }
+
+    warn!("Running headless instead");
+    headless::run(args);
+}
+
+/// Whether there's a display server to connect to. Other platforms always
+/// have one.
+fn has_display() -> bool {
+    !cfg!(target_os = "linux")
+        || env::var_os("DISPLAY").is_some()
+        || env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+struct MyEguiApp {
+    config: config::Config,
+    /// The config as it was last saved or loaded, to compare against and
+    /// revert to.
+    saved_config: config::Config,
+    save_requested: bool,
+    config_path: Option<PathBuf>,
+    config_watcher: Option<watch::ConfigWatcher>,
+    /// The config as it was last sent to the backend.
+    sent_config: config::Config,
+    /// Problems with the config as it was last validated.
+    problems: Vec<validate::Problem>,
+    validated_config: config::Config,
+    backend: backend::Backend,
+    args: cli::Args,
+    editing_marker: Option<usize>,
+    /// Whether the edited point marker's handle is being dragged.
+    dragging_point: bool,
+    /// What to snap point markers to when they're dropped.
+    snap: Option<snap::Snap>,
+    marker_filter: String,
+    confirming_quit: bool,
+    recording_shortcut: Option<usize>,
+    sweep: Option<sweep::Sweep>,
+    tune: Option<tune::Tune>,
+    export_secrets: bool,
+    backups: Vec<backup::Backup>,
+    selected_backup: usize,
+    frame: Option<TextureHandle>,
+    /// The latest frame of the active profile, to save as a reference.
+    frame_image: Option<ColorImage>,
+    /// Whether [`Self::frame`] is behind [`Self::frame_image`], which it is
+    /// while the window is minimized in low power mode.
+    frame_stale: bool,
+    /// How far the markers were moved to follow the camera.
+    shift: Option<[i64; 2]>,
+    /// The ambient luminance around the markers, and the lighting whose
+    /// threshold was used, if any.
+    lighting: Option<(f32, Option<String>)>,
+    /// The luminance of the active profile's ambient reference.
+    ambient: Option<f32>,
+    /// How much the vibration region changed between the latest frames, and
+    /// whether that counts as vibrating yet.
+    vibration: Option<(f32, Option<bool>)>,
+    /// How much the door region differs from closed, and whether that
+    /// counts as open.
+    door: Option<(f32, Option<bool>)>,
+    /// When the latest frame was captured, from the camera's clock.
+    frame_captured: Option<SystemTime>,
+    /// How long each stage of the latest sample took, and the time between
+    /// the latest two frames.
+    timings: Option<backend::Timings>,
+    frame_interval: Option<Duration>,
+    /// Whether the timings are shown over the frame.
+    show_timings: bool,
+    /// The latest microphone level, and whether it counts as loud.
+    sound: Option<(f32, bool)>,
+    /// Levels recorded while training the finished beeps.
+    beep_recording: Option<Vec<f32>>,
+    /// The active profile's reference frame and the path it was loaded from,
+    /// if that worked.
+    reference: Option<(PathBuf, Option<(registration::Reference, TextureHandle)>)>,
+    /// How much of the reference frame to blend over the live one, while
+    /// comparing them.
+    compare: Option<f32>,
+    /// How different each marker's region is from the reference frame.
+    differences: Vec<Option<f32>>,
+    /// When the last frame of the active profile arrived.
+    frame_time: Option<Instant>,
+    sampled: Vec<Vec<f32>>,
+    values: Vec<Value>,
+    /// How each marker's value was decoded.
+    quality: Vec<Quality>,
+    /// Topic prefixes of the profiles that are paused.
+    paused: HashSet<String>,
+    /// Least severe level shown in the log panel.
+    log_level: tracing::Level,
+}
+
+impl MyEguiApp {
+    fn new(cc: &CreationContext<'_>, args: cli::Args) -> Self {
+        let config_path = args.config_path();
+
+        let mut config: config::Config = args
+            .load_config()
+            .or_else(|| {
+                cc.storage
+                    .and_then(|storage| eframe::get_value(storage, "config"))
+            })
+            .unwrap_or_default();
+
+        config.migrate();
+        args.apply(&mut config);
+
+        let problems = validate::validate(&config);
+        for problem in &problems {
+            warn!("Config problem: {}", problem);
+        }
+
+        egui_extras::install_image_loaders(&cc.egui_ctx);
+
+        let config_watcher = config_path.as_deref().and_then(|path| {
+            watch::ConfigWatcher::new(path, cc.egui_ctx.clone())
+                .map_err(|e| error!("Error watching {}: {}", path.display(), e))
+                .ok()
+        });
+
+        let ctx = cc.egui_ctx.clone();
+        let backend = backend::Backend::start(
+            config.clone(),
+            Duration::from_secs(args.refresh_secs),
+            true,
+            move || ctx.request_repaint(),
+        );
+        backend.serve_api(&args);
+        #[cfg(target_os = "linux")]
+        backend.serve_dbus(&args);
+
+        Self {
+            saved_config: config.clone(),
+            save_requested: false,
+            config_path,
+            config_watcher,
+            sent_config: config.clone(),
+            problems,
+            validated_config: config.clone(),
+            config,
+            backend,
+            editing_marker: None,
+            dragging_point: false,
+            snap: None,
+            marker_filter: String::new(),
+            confirming_quit: false,
+            recording_shortcut: None,
+            sweep: None,
+            tune: None,
+            export_secrets: false,
+            backups: vec![],
+            selected_backup: 0,
+            frame: None,
+            frame_image: None,
+            frame_stale: false,
+            shift: None,
+            lighting: None,
+            ambient: None,
+            vibration: None,
+            door: None,
+            frame_captured: None,
+            timings: None,
+            frame_interval: None,
+            show_timings: false,
+            sound: None,
+            beep_recording: None,
+            reference: None,
+            compare: None,
+            differences: vec![],
+            frame_time: None,
+            sampled: vec![],
+            values: vec![],
+            quality: vec![],
+            paused: HashSet::new(),
+            log_level: tracing::Level::INFO,
+            args,
+        }
+    }
+}
+
+impl eframe::App for MyEguiApp {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        if self.config_watcher.as_ref().is_some_and(|w| w.changed()) {
+            self.reload_config();
+        }
+
+        self.load_reference(ctx);
+        self.receive_updates(ctx);
+        self.upload_frame(ctx);
+
+        self.profile_tabs(ctx);
+        self.log_panel(ctx);
+        self.frame_panel(ctx);
+        self.options_window(ctx);
+        self.dialogs(ctx);
+
+        self.handle_keys(ctx);
+        self.send_config();
+
+        if self.save_requested {
+            self.save_requested = false;
+
+            match frame.storage_mut() {
+                Some(storage) => self.save_config(storage),
+                None => error!("No storage available to save config"),
+            }
+        }
+
+        // Updates from the backend request a repaint themselves, so this is
+        // only a fallback in case the next sample doesn't arrive.
+        ctx.request_repaint_after(self.until_next_sample() + Duration::from_secs(1));
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        if self.config.autosave {
+            self.save_config(storage);
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.backend.shutdown();
+    }
+}
+
+impl MyEguiApp {
+    fn save_config(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, "config", &self.config);
+
+        if let Some(path) = &self.config_path {
+            if let Err(e) = self.config.save(path) {
+                error!("Error saving config to {}: {}", path.display(), e);
+                return;
+            }
+        }
+
+        self.saved_config = self.config.clone();
+    }
+
+    fn save_ui(&mut self, ui: &mut egui::Ui) {
+        let modified = self.config != self.saved_config;
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(modified, egui::Button::new("Save"))
+                .clicked()
+            {
+                self.save_requested = true;
+            }
+
+            if ui
+                .add_enabled(modified, egui::Button::new("Revert"))
+                .clicked()
+            {
+                self.config = self.saved_config.clone();
+                self.select_profile(self.config.active_profile);
+            }
+
+            ui.checkbox(&mut self.config.autosave, "Autosave");
+
+            if modified {
+                ui.colored_label(ui.visuals().warn_fg_color, "Modified");
+            }
+        });
+    }
+
+    fn handle_keys(&mut self, ctx: &Context) {
+        if let Some(idx) = self.recording_shortcut {
+            let pressed = ctx.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => Some(KeyboardShortcut::new(*modifiers, *key)),
+                    _ => None,
+                })
+            });
+
+            if let Some(pressed) = pressed {
+                if let Some((_, shortcut)) =
+                    self.config.keybindings.shortcuts_mut().into_iter().nth(idx)
+                {
+                    *shortcut = config_shortcut(pressed);
+                }
+                self.recording_shortcut = None;
+            }
+
+            return;
+        }
+
+        let keybindings = &self.config.keybindings;
+
+        let consume = |shortcut: &config::Shortcut| {
+            keyboard_shortcut(shortcut)
+                .is_some_and(|shortcut| ctx.input_mut(|i| i.consume_shortcut(&shortcut)))
+        };
+
+        if consume(&keybindings.cancel) {
+            if self.confirming_quit {
+                self.confirming_quit = false;
+            } else if self.sweep.is_some() {
+                self.sweep = None;
+            } else if self.tune.is_some() {
+                self.tune = None;
+            } else {
+                self.editing_marker = None;
+            }
+        }
+
+        if consume(&keybindings.quit) {
+            if keybindings.confirm_quit && !self.confirming_quit {
+                self.confirming_quit = true;
+            } else {
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+            }
+        }
+    }
+
+    /// Applies changes made to the config file outside of the app.
+    fn reload_config(&mut self) {
+        let Some(path) = &self.config_path else {
+            return;
+        };
+
+        let mut config = match config::Config::load(path) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Error reloading config from {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        self.args.apply(&mut config);
+
+        // Our own saves trigger the watcher too.
+        if toml::to_string(&config).ok() == toml::to_string(&self.config).ok() {
+            return;
+        }
+
+        info!("Reloaded config from {}", path.display());
+
+        self.saved_config = config.clone();
+
+        let markers_changed = config.active_profile != self.config.active_profile
+            || config.profile().markers.len() != self.config.profile().markers.len();
+
+        self.config = config;
+
+        if markers_changed {
+            self.editing_marker = None;
+            self.sweep = None;
+            self.tune = None;
+            self.sampled.clear();
+            self.values.clear();
+            self.quality.clear();
+        }
+    }
+
+    /// A tab for each machine. All of them are sampled and published, the
+    /// selected one is shown and edited.
+    fn profile_tabs(&mut self, ctx: &Context) {
+        if self.config.profiles.len() < 2 {
+            return;
+        }
+
+        let mut selected = self.config.active_profile;
+
+        TopBottomPanel::top("profiles").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (idx, profile) in self.config.profiles.iter().enumerate() {
+                    ui.selectable_value(&mut selected, idx, &profile.name);
+                }
+            });
+        });
+
+        if selected != self.config.active_profile {
+            self.select_profile(selected);
+        }
+    }
+
+    fn log_panel(&mut self, ctx: &Context) {
+        TopBottomPanel::bottom("log")
+            .resizable(true)
+            .show(ctx, |ui| {
+                CollapsingHeader::new("Log").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ComboBox::from_id_source("log_level")
+                            .selected_text(self.log_level.as_str())
+                            .show_ui(ui, |ui| {
+                                for level in [
+                                    tracing::Level::ERROR,
+                                    tracing::Level::WARN,
+                                    tracing::Level::INFO,
+                                    tracing::Level::DEBUG,
+                                    tracing::Level::TRACE,
+                                ] {
+                                    ui.selectable_value(&mut self.log_level, level, level.as_str());
+                                }
+                            });
+
+                        ui.checkbox(&mut self.show_timings, "Timings")
+                            .on_hover_text("Show how long each stage of sampling takes");
+
+                        if ui.button("Copy").clicked() {
+                            let text = logging::with_recent(|lines| {
+                                lines
+                                    .iter()
+                                    .filter(|line| line.level <= self.log_level)
+                                    .map(|line| format!("{}\n", line))
+                                    .collect::<String>()
+                            });
+                            ui.output_mut(|output| output.copied_text = text);
+                        }
+                    });
+
+                    ScrollArea::vertical()
+                        .stick_to_bottom(true)
+                        .auto_shrink(false)
+                        .show(ui, |ui| {
+                            logging::with_recent(|lines| {
+                                for line in lines.iter().filter(|line| line.level <= self.log_level)
+                                {
+                                    let color = match line.level {
+                                        tracing::Level::ERROR => Color32::LIGHT_RED,
+                                        tracing::Level::WARN => Color32::YELLOW,
+                                        _ => ui.visuals().text_color(),
+                                    };
+
+                                    ui.colored_label(color, line.to_string());
+                                }
+                            });
+                        });
+                });
+            });
+    }
+
+    /// The frame, with the markers and regions drawn over it.
+    fn frame_panel(&mut self, ctx: &Context) {
+        CentralPanel::default()
+            .frame(egui::Frame::default().inner_margin(0.))
+            .show(ctx, |ui| {
+                let Some(frame) = &self.frame else {
+                    ui.centered_and_justified(|ui| ui.spinner());
+                    return;
+                };
+                let size = frame.size();
+
+                // Letterboxed, with everything drawn over it and dragged on
+                // it mapped through the same rect.
+                let rect = fit_rect(ui.available_rect_before_wrap(), frame.size_vec2());
+                ui.painter().image(
+                    frame.id(),
+                    rect,
+                    Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+                    Color32::WHITE,
+                );
+
+                self.comparison_overlay(ui.painter(), rect, size);
+
+                let style = OverlayStyle::new(rect.width() / size[0] as f32);
+                if style.zoom >= PIXEL_GRID_ZOOM {
+                    pixel_grid(ui.painter(), rect, size);
+                }
+
+                self.region_overlay(ui.painter(), rect, style.guide);
+
+                let drag_response = ui.allocate_rect(rect, Sense::drag());
+                self.drag_markers(ui, &drag_response, rect, style.handle);
+                self.marker_overlay(ui, &drag_response, rect, size, style);
+
+                if let Some(timings) = self.timings.filter(|_| self.show_timings) {
+                    timings_overlay(ui.painter(), rect, timings, self.frame_interval);
+                }
+            });
+    }
+
+    /// The reference frame blended over the frame, with how different each
+    /// marker's region is.
+    fn comparison_overlay(&self, painter: &egui::Painter, rect: Rect, [width, height]: [usize; 2]) {
+        let (Some(blend), Some((_, Some((_, reference))))) = (self.compare, &self.reference) else {
+            return;
+        };
+
+        painter.image(
+            reference.id(),
+            rect,
+            Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+            Color32::from_white_alpha((blend * 255.) as u8),
+        );
+
+        let markers = &self.config.profile().markers;
+        for (marker, difference) in markers.iter().zip(&self.differences) {
+            let (Some(difference), Some((x, y, w, h))) = (
+                difference,
+                recording::crop_rect(marker, width as u32, height as u32),
+            ) else {
+                continue;
+            };
+
+            let to_screen = |x: u32, y: u32| {
+                map_pos(
+                    pos2(x as f32 / width as f32, y as f32 / height as f32),
+                    rect,
+                )
+            };
+
+            // Lighting changes make everything a bit different, the camera
+            // moving makes some regions very different.
+            let stroke = if *difference > 0.1 {
+                Stroke::new(2., Color32::RED)
+            } else {
+                Stroke::new(1., Color32::GREEN)
+            };
+
+            painter.rect_stroke(
+                Rect::from_min_max(to_screen(x, y), to_screen(x + w, y + h)),
+                0.,
+                stroke,
+            );
+        }
+    }
+
+    /// The door and vibration regions, highlighted while they detect
+    /// something.
+    fn region_overlay(&self, painter: &egui::Painter, rect: Rect, guide: Stroke) {
+        let profile = self.config.profile();
+
+        if let Some(door) = &profile.door {
+            let stroke = match self.door {
+                Some((_, Some(true))) => Stroke::new(guide.width, Color32::RED),
+                _ => Stroke::new(guide.width, Color32::LIGHT_BLUE),
+            };
+            painter.rect_stroke(
+                Rect::from_min_max(
+                    map_pos(door.region.min, rect),
+                    map_pos(door.region.max, rect),
+                ),
+                0.,
+                stroke,
+            );
+        }
+
+        if let Some(vibration) = &profile.vibration {
+            let stroke = match self.vibration {
+                Some((_, Some(true))) => Stroke::new(guide.width, Color32::YELLOW),
+                _ => guide,
+            };
+            painter.rect_stroke(
+                Rect::from_min_max(
+                    map_pos(vibration.region.min, rect),
+                    map_pos(vibration.region.max, rect),
+                ),
+                0.,
+                stroke,
+            );
+        }
+    }
+
+    /// Moves the handles of the marker being edited with the pointer, and
+    /// snaps a dragged point once it's let go.
+    fn drag_markers(&mut self, ui: &egui::Ui, response: &egui::Response, rect: Rect, handle: f32) {
+        let drag = response.drag_delta();
+        let mouse_pos = response.interact_pointer_pos().unwrap_or_default();
+
+        if !response.dragged() {
+            if self.dragging_point {
+                let marker = self
+                    .editing_marker
+                    .and_then(|idx| self.config.profile_mut().markers.get_mut(idx));
+
+                if let (
+                    Some(snap),
+                    Some(image),
+                    Some(Marker {
+                        ty: MarkerType::Point { pos, .. },
+                        ..
+                    }),
+                ) = (self.snap, &self.frame_image, marker)
+                {
+                    if let Some(snapped) = snap.snap(image, *pos) {
+                        *pos = snapped;
+                    }
+                }
+            }
+
+            self.dragging_point = false;
+        }
+
+        let Some(marker) = self
+            .editing_marker
+            .and_then(|idx| self.config.profile_mut().markers.get_mut(idx))
+        else {
+            return;
+        };
+
+        match &mut marker.ty {
+            MarkerType::Point { pos, .. } => {
+                // Only a drag that starts on the handle moves it.
+                let hovered = handle_hovered(response, map_pos(*pos, rect), handle);
+
+                if response.drag_started() {
+                    self.dragging_point = hovered;
+                }
+
+                if self.dragging_point {
+                    pos.x += drag.x / rect.width();
+                    pos.y += drag.y / rect.height();
+                    ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+                } else if hovered {
+                    ui.ctx().set_cursor_icon(egui::CursorIcon::Grab);
+                }
+            }
+            MarkerType::SevenSegment {
+                start, end, bottom, ..
+            } => {
+                let closest = [start, end, bottom]
+                    .into_iter()
+                    .min_by(|a, b| {
+                        let a_dist = (map_pos(**a, rect) - mouse_pos).length();
+                        let b_dist = (map_pos(**b, rect) - mouse_pos).length();
+                        a_dist.partial_cmp(&b_dist).unwrap_or(Ordering::Equal)
+                    })
+                    .unwrap();
+
+                closest.x += drag.x / rect.width();
+                closest.y += drag.y / rect.height();
+            }
+            // Not in the frame.
+            MarkerType::Gpio { .. } => {}
+        }
+    }
+
+    /// Each marker's handles and sampled areas, with whether the last sample
+    /// of each point was lit.
+    fn marker_overlay(
+        &self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        rect: Rect,
+        [width, height]: [usize; 2],
+        style: OverlayStyle,
+    ) {
+        let profile = self.config.profile();
+        let painter = ui.painter();
+
+        for (idx, marker) in profile.markers.iter().enumerate() {
+            match &marker.ty {
+                MarkerType::Point { pos, .. } => {
+                    if self.editing_marker == Some(idx) {
+                        let center = map_pos(*pos, rect);
+                        let radius = if self.dragging_point
+                            || handle_hovered(response, center, style.handle)
+                        {
+                            style.handle * 1.5
+                        } else {
+                            style.handle
+                        };
+                        painter.circle(
+                            center,
+                            radius,
+                            Color32::YELLOW,
+                            Stroke::new(style.outline.width, Color32::BLACK),
+                        );
+                    }
+                }
+                MarkerType::SevenSegment {
+                    start, end, bottom, ..
+                } => {
+                    painter.line_segment([map_pos(*start, rect), map_pos(*end, rect)], style.guide);
+                    painter
+                        .line_segment([map_pos(*start, rect), map_pos(*bottom, rect)], style.guide);
+
+                    painter.circle_filled(map_pos(*start, rect), style.handle, Color32::RED);
+                    painter.circle_filled(map_pos(*end, rect), style.handle, Color32::GREEN);
+                    painter.circle_filled(map_pos(*bottom, rect), style.handle, Color32::BLUE);
+                }
+                // Not in the frame.
+                MarkerType::Gpio { .. } => {}
+            }
+
+            let points = marker.ty.get_points();
+            for (pidx, point) in points.into_iter().enumerate() {
+                if style.zoom >= PIXEL_GRID_ZOOM {
+                    let (left, top, right, bottom) =
+                        point.pixels_read(width, height, profile.interpolation);
+                    let search = profile.peak_search;
+
+                    // The pixels searched around the point, and the ones it
+                    // reads.
+                    let pixels = |x0: usize, y0: usize, x1: usize, y1: usize| {
+                        Rect::from_min_max(
+                            map_pos(
+                                pos2(x0 as f32 / width as f32, y0 as f32 / height as f32),
+                                rect,
+                            ),
+                            map_pos(
+                                pos2(x1 as f32 / width as f32, y1 as f32 / height as f32),
+                                rect,
+                            ),
+                        )
+                    };
+                    if search > 0 {
+                        painter.rect_stroke(
+                            pixels(
+                                left.saturating_sub(search),
+                                top.saturating_sub(search),
+                                (right + search).min(width),
+                                (bottom + search).min(height),
+                            ),
+                            0.,
+                            Stroke::new(style.outline.width, Color32::LIGHT_BLUE),
+                        );
+                    }
+                    painter.rect_filled(
+                        pixels(left, top, right, bottom),
+                        0.,
+                        Color32::from_rgba_unmultiplied(255, 255, 0, 96),
+                    );
+                }
+
+                let sample_rect =
+                    Rect::from_center_size(map_pos(point.pos, rect), rect.size() * point.size);
+                painter.rect_stroke(sample_rect, 0., style.outline);
+
+                if let Some(sample) = self.sampled.get(idx).and_then(|v| v.get(pidx)) {
+                    // Beside the sampled area, so it stays visible.
+                    let indicator = style.indicator;
+                    let center = sample_rect.right_top() + vec2(indicator, -indicator);
+                    painter.circle(
+                        center,
+                        indicator,
+                        if *sample > marker.threshold_or(profile.luminance_threshold) {
+                            Color32::WHITE
+                        } else {
+                            Color32::BLACK
+                        },
+                        Stroke::new(style.outline.width, Color32::GRAY),
+                    );
+                }
+            }
+        }
+    }
+
+    fn options_window(&mut self, ctx: &Context) {
+        Window::new("Options").show(ctx, |ui| {
+            ui.set_min_width(100.);
+
+            self.save_ui(ui);
+
+            ui.separator();
+
+            self.profile_ui(ui);
+
+            self.webcam_ui(ui);
+
+            self.mqtt_ui(ui);
+
+            self.influxdb_ui(ui);
+
+            self.webhooks_ui(ui);
+
+            self.notifications_ui(ui);
+
+            self.markers_ui(ui);
+
+            self.sampling_ui(ui);
+
+            self.config_ui(ui);
+
+            self.keybindings_ui(ui);
+        });
+    }
+
+    fn dialogs(&mut self, ctx: &Context) {
+        if self.config != self.validated_config {
+            self.problems = validate::validate(&self.config);
+            self.validated_config = self.config.clone();
+        }
+
+        if !self.problems.is_empty() {
+            Window::new(format!("Warnings ({})", self.problems.len()))
+                .id(egui::Id::new("warnings"))
+                .default_open(false)
+                .show(ctx, |ui| {
+                    for problem in &self.problems {
+                        ui.colored_label(ui.visuals().warn_fg_color, problem.to_string());
+                    }
+                });
+        }
+
+        if let Some(sweep) = &self.sweep {
+            let mut open = true;
+            Window::new("Threshold sweep")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    sweep.show(ui, &mut self.config.profile_mut().luminance_threshold);
+                });
+
+            if !open {
+                self.sweep = None;
+            }
+        }
+
+        if let Some(tune) = &mut self.tune {
+            let mut open = true;
+            Window::new("Tune to display")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    tune.show(ui, self.config.profile_mut(), &self.sampled);
+                });
+
+            if !open {
+                self.tune = None;
+            }
+        }
+
+        if self.confirming_quit {
+            Window::new("Quit?")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label("Are you sure you want to quit?");
+                    ui.horizontal(|ui| {
+                        if ui.button("Quit").clicked() {
+                            ctx.send_viewport_cmd(ViewportCommand::Close);
+                        }
+
+                        if ui.button("Cancel").clicked() {
+                            self.confirming_quit = false;
+                        }
+                    });
+                });
+        }
+    }
+
+    fn webcam_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Webcam", |ui| {
+            let profile = self.config.profile_mut();
+            let mut home_assistant = profile.webcam.home_assistant.is_some();
+            if ui
+                .checkbox(&mut home_assistant, "From a Home Assistant camera")
+                .changed()
+            {
+                profile.webcam.home_assistant = home_assistant.then(Default::default);
+            }
+
+            if let Some(camera) = &mut profile.webcam.home_assistant {
+                Grid::new("home_assistant_config")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Home Assistant URL")
+                            .on_hover_text("Empty to go through the supervisor as an add-on");
+                        ui.text_edit_singleline(&mut camera.url);
+                        ui.end_row();
+
+                        ui.label("Entity");
+                        TextEdit::singleline(&mut camera.entity_id)
+                            .hint_text("camera.laundry_room")
+                            .ui(ui);
+                        ui.end_row();
+
+                        optional_text_edit(ui, &mut camera.token, "Access token", true);
+                        keyring_ui(
+                            ui,
+                            &mut camera.token,
+                            &mut camera.token_entry,
+                            format!("home-assistant/{}", profile.name),
+                        );
+                    });
+            }
+
+            // The camera entity has its own.
+            if self.config.profile().webcam.home_assistant.is_none() {
+                Grid::new("webcam_config").num_columns(2).show(ui, |ui| {
+                    let profile = self.config.profile_mut();
+                    let webcam = &mut profile.webcam;
+
+                    ui.label("Source");
+                    let selected = webcam
+                        .preset
+                        .as_ref()
+                        .map_or("URL", |preset| preset.service.name());
+                    ComboBox::from_id_source("webcam_preset")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            if ui
+                                .selectable_label(webcam.preset.is_none(), "URL")
+                                .clicked()
+                            {
+                                webcam.preset = None;
+                            }
+                            for service in source::Service::ALL {
+                                let current = webcam.preset.as_ref().map(|p| p.service);
+                                if ui
+                                    .selectable_label(current == Some(service), service.name())
+                                    .clicked()
+                                {
+                                    webcam.preset.get_or_insert_with(Default::default).service =
+                                        service;
+                                }
+                            }
+                        });
+                    ui.end_row();
+
+                    match &mut webcam.preset {
+                        Some(preset) => {
+                            ui.label(format!("{} URL", preset.service.name()));
+                            ui.text_edit_singleline(&mut preset.url);
+                            ui.end_row();
+
+                            ui.label("Camera");
+                            ui.text_edit_singleline(&mut preset.camera);
+                            ui.end_row();
+
+                            ui.label("");
+                            ui.checkbox(&mut preset.stream, "Stream");
+                            ui.end_row();
+                        }
+                        None => {
+                            ui.label("URL");
+                            ui.text_edit_singleline(&mut webcam.url);
+                            ui.end_row();
+                        }
+                    }
+
+                    optional_text_edit(ui, &mut profile.webcam.username, "Username", false);
+                    optional_text_edit(ui, &mut profile.webcam.password, "Password", true);
+                    keyring_ui(
+                        ui,
+                        &mut profile.webcam.password,
+                        &mut profile.webcam.password_entry,
+                        format!("webcam/{}", profile.name),
+                    );
+                });
+            }
+
+            let webcam = &mut self.config.profile_mut().webcam;
+            let mut ptz = webcam.ptz.is_some();
+            if ui
+                .checkbox(&mut ptz, "Move to a PTZ preset before sampling")
+                .changed()
+            {
+                webcam.ptz = ptz.then(Default::default);
+            }
+
+            if let Some(ptz) = &mut webcam.ptz {
+                Grid::new("ptz_config").num_columns(2).show(ui, |ui| {
+                    ui.label("ONVIF PTZ URL");
+                    ui.text_edit_singleline(&mut ptz.url);
+                    ui.end_row();
+
+                    ui.label("Profile token");
+                    ui.text_edit_singleline(&mut ptz.profile_token);
+                    ui.end_row();
+
+                    ui.label("Preset");
+                    ui.text_edit_singleline(&mut ptz.preset);
+                    ui.end_row();
+
+                    optional_text_edit(ui, &mut ptz.return_preset, "Return to", false);
+
+                    ui.label("Settle time");
+                    DragValue::new(&mut ptz.settle_secs)
+                        .suffix(" s")
+                        .speed(0.1)
+                        .clamp_range(0.0..=60.0)
+                        .ui(ui);
+                    ui.end_row();
+                });
+            }
+
+            if ui.button("Refresh").clicked() {
+                self.backend
+                    .send(backend::Command::Refresh(self.config.active_profile));
+            }
+        });
+    }
+
+    fn mqtt_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("MQTT", |ui| {
+            Grid::new("mqtt_config").num_columns(2).show(ui, |ui| {
+                ui.label("Host");
+                ui.text_edit_singleline(&mut self.config.mqtt.host);
+                ui.end_row();
+
+                ui.label("Port");
+                DragValue::new(&mut self.config.mqtt.port)
+                    .speed(1)
+                    .clamp_range(1..=65535)
+                    .ui(ui);
+                ui.end_row();
+
+                let mqtt = &mut self.config.mqtt;
+
+                optional_text_edit(ui, &mut mqtt.username, "Username", false);
+                optional_text_edit(ui, &mut mqtt.password, "Password", true);
+                keyring_ui(
+                    ui,
+                    &mut mqtt.password,
+                    &mut mqtt.password_entry,
+                    "mqtt".to_owned(),
+                );
+
+                optional_text_edit(ui, &mut mqtt.bridge_topic, "Zigbee2MQTT bridge", false);
+            });
+
+            ui.checkbox(
+                &mut self.config.mqtt.remote_config,
+                "Accept config changes over MQTT",
+            );
+
+            ui.horizontal(|ui| {
+                ui.label("Payloads");
+                for format in PayloadFormat::ALL {
+                    ui.radio_value(&mut self.config.mqtt.payload_format, format, format.name());
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let mut frames = self.config.frame_publishing.is_some();
+                if ui
+                    .checkbox(&mut frames, "Publish frames")
+                    .on_hover_text("Retained JPEGs on {prefix}/frame, for a camera entity")
+                    .changed()
+                {
+                    self.config.frame_publishing = frames.then(Default::default);
+                }
+
+                if let Some(publishing) = &mut self.config.frame_publishing {
+                    ui.label("every");
+                    DragValue::new(&mut publishing.interval_secs)
+                        .suffix(" s")
+                        .clamp_range(1..=86400)
+                        .ui(ui);
+                    ui.checkbox(&mut publishing.annotate, "Annotated");
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let mut debug = self.config.debug_publishing.is_some();
+                if ui
+                    .checkbox(&mut debug, "Publish samples")
+                    .on_hover_text("Luminance of each point on {prefix}/debug/{marker}")
+                    .changed()
+                {
+                    self.config.debug_publishing = debug.then(Default::default);
+                }
+
+                if let Some(publishing) = &mut self.config.debug_publishing {
+                    ui.label("every");
+                    DragValue::new(&mut publishing.interval_secs)
+                        .suffix(" s")
+                        .clamp_range(1..=86400)
+                        .ui(ui);
+                }
+            });
+
+            if ui.button("Publish").clicked() {
+                self.publish();
+            }
+        });
+    }
+
+    fn influxdb_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("InfluxDB", |ui| {
+            let mut enabled = self.config.influxdb.is_some();
+            if ui
+                .checkbox(&mut enabled, "Write values to InfluxDB")
+                .changed()
+            {
+                self.config.influxdb = enabled.then(Default::default);
+            }
+
+            let Some(influxdb) = &mut self.config.influxdb else {
+                return;
+            };
+
+            Grid::new("influxdb_config").num_columns(2).show(ui, |ui| {
+                ui.label("URL");
+                ui.text_edit_singleline(&mut influxdb.url);
+                ui.end_row();
+
+                ui.label("Organization");
+                ui.text_edit_singleline(&mut influxdb.org);
+                ui.end_row();
+
+                ui.label("Bucket");
+                ui.text_edit_singleline(&mut influxdb.bucket);
+                ui.end_row();
+
+                optional_text_edit(ui, &mut influxdb.token, "Token", true);
+                keyring_ui(
+                    ui,
+                    &mut influxdb.token,
+                    &mut influxdb.token_entry,
+                    "influxdb".to_owned(),
+                );
+            });
+        });
+    }
+
+    fn webhooks_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Webhooks", |ui| {
+            let mut remove = None;
+
+            for (idx, webhook) in self.config.webhooks.iter_mut().enumerate() {
+                ui.push_id(idx, |ui| {
+                    Grid::new("webhook").num_columns(2).show(ui, |ui| {
+                        ui.label("URL");
+                        ui.text_edit_singleline(&mut webhook.url);
+                        ui.end_row();
+
+                        ui.label("Events");
+                        ui.horizontal(|ui| {
+                            for kind in event::EventKind::ALL {
+                                let mut enabled = webhook.events.contains(&kind);
+                                if ui.checkbox(&mut enabled, kind.name()).changed() {
+                                    webhook.events.retain(|&k| k != kind);
+                                    if enabled {
+                                        webhook.events.push(kind);
+                                    }
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Markers")
+                            .on_hover_text("Comma separated, empty for all markers");
+                        let mut markers = webhook.markers.join(", ");
+                        if ui.text_edit_singleline(&mut markers).changed() {
+                            // Empty names are kept so that a trailing
+                            // comma survives while typing.
+                            webhook.markers = markers
+                                .split(',')
+                                .map(|name| name.trim().to_owned())
+                                .collect();
+                        }
+                        ui.end_row();
+
+                        ui.label("Body");
+                        TextEdit::multiline(&mut webhook.body)
+                            .code_editor()
+                            .desired_rows(2)
+                            .show(ui);
+                        ui.end_row();
+
+                        ui.label("Retries");
+                        DragValue::new(&mut webhook.retries)
+                            .clamp_range(0..=10)
+                            .ui(ui);
+                        ui.end_row();
+                    });
+
+                    if ui.button("Remove").clicked() {
+                        remove = Some(idx);
+                    }
+                });
+
+                ui.separator();
+            }
+
+            if let Some(idx) = remove {
+                self.config.webhooks.remove(idx);
+            }
+
+            if ui.button("Add webhook").clicked() {
+                self.config.webhooks.push(Default::default());
+            }
+        });
+    }
+
+    fn notifications_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Notifications", |ui| {
+            let notifications = &mut self.config.notifications;
+            let markers = &self.config.profiles[self.config.active_profile].markers;
+
+            ui.label("Send to");
+
+            let mut remove = None;
+
+            for (idx, provider) in notifications.providers.iter_mut().enumerate() {
+                if ui.push_id(("provider", idx), |ui| provider_ui(ui, provider)).inner {
+                    remove = Some(idx);
+                }
+            }
+
+            if let Some(idx) = remove {
+                notifications.providers.remove(idx);
+            }
+
+            ui.horizontal(|ui| {
+                for name in notification::Provider::NAMES {
+                    if ui.button(format!("Add {}", name)).clicked() {
+                        notifications
+                            .providers
+                            .push(notification::Provider::new(name));
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.label("And to these people, while they're home");
+
+            let mut remove_person = None;
+
+            for (idx, person) in notifications.people.iter_mut().enumerate() {
+                ui.push_id(("person", idx), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut person.name)
+                            .on_hover_text("As in the presence topic");
+                        if ui.button("Remove").clicked() {
+                            remove_person = Some(idx);
+                        }
+                    });
+
+                    ui.indent("providers", |ui| {
+                        let mut remove = None;
+
+                        for (idx, provider) in person.providers.iter_mut().enumerate() {
+                            if ui.push_id(idx, |ui| provider_ui(ui, provider)).inner {
+                                remove = Some(idx);
+                            }
+                        }
+
+                        if let Some(idx) = remove {
+                            person.providers.remove(idx);
+                        }
+
+                        ui.horizontal(|ui| {
+                            for name in notification::Provider::NAMES {
+                                if ui.button(format!("Add {}", name)).clicked() {
+                                    person.providers.push(notification::Provider::new(name));
+                                }
+                            }
+                        });
+                    });
+                });
+            }
+
+            if let Some(idx) = remove_person {
+                notifications.people.remove(idx);
+            }
+
+            if ui.button("Add person").clicked() {
+                notifications.people.push(notification::Person {
+                    name: String::new(),
+                    providers: vec![],
+                });
+            }
+
+            ui.separator();
+            ui.label("When");
+
+            let mut remove = None;
+
+            for (idx, rule) in notifications.rules.iter_mut().enumerate() {
+                ui.push_id(("rule", idx), |ui| {
+                    ui.horizontal(|ui| {
+                        ComboBox::from_id_source("event")
+                            .selected_text(rule.event.name())
+                            .show_ui(ui, |ui| {
+                                for kind in event::EventKind::ALL {
+                                    ui.selectable_value(&mut rule.event, kind, kind.name());
+                                }
+                            });
+
+                        if rule.event == event::EventKind::ValueChanged {
+                            ComboBox::from_id_source("marker")
+                                .selected_text(rule.marker.as_deref().unwrap_or("(marker)"))
+                                .show_ui(ui, |ui| {
+                                    for marker in markers {
+                                        ui.selectable_value(
+                                            &mut rule.marker,
+                                            Some(marker.name.clone()),
+                                            &marker.name,
+                                        );
+                                    }
+                                });
+                        }
+
+                        if ui.button("Remove").clicked() {
+                            remove = Some(idx);
+                        }
+                    });
+
+                    ui.text_edit_singleline(&mut rule.message)
+                        .on_hover_text("{{event}}, {{marker}}, {{value}}, {{time_remaining}} and {{profile}} are filled in");
+                });
+            }
+
+            if let Some(idx) = remove {
+                notifications.rules.remove(idx);
+            }
+
+            if ui.button("Add rule").clicked() {
+                notifications.rules.push(Default::default());
+            }
+
+            ui.horizontal(|ui| {
+                let mut quiet = notifications.quiet_hours.is_some();
+                if ui.checkbox(&mut quiet, "Quiet from").changed() {
+                    notifications.quiet_hours = quiet.then(Default::default);
+                }
+
+                if let Some(quiet_hours) = &mut notifications.quiet_hours {
+                    TextEdit::singleline(&mut quiet_hours.from)
+                        .desired_width(50.)
+                        .ui(ui);
+                    ui.label("until");
+                    TextEdit::singleline(&mut quiet_hours.until)
+                        .desired_width(50.)
+                        .ui(ui);
+                }
+            });
+
+            if let Some(quiet_hours) = &mut notifications.quiet_hours {
+                ui.checkbox(&mut quiet_hours.defer, "Send them when quiet hours end");
+                ui.checkbox(&mut quiet_hours.non_essential, "Also skip webhooks and the chime");
+            }
+
+            let presence = &mut self.config.profiles[self.config.active_profile].presence;
+            let mut present_only = presence.is_some();
+            if ui
+                .checkbox(&mut present_only, "Skip people who are away")
+                .on_hover_text("For this profile, as told by presence topics")
+                .changed()
+            {
+                *presence = present_only.then(Default::default);
+            }
+
+            if let Some(presence) = presence {
+                Grid::new("presence").num_columns(2).show(ui, |ui| {
+                    ui.label("Presence topic");
+                    ui.text_edit_singleline(&mut presence.topic)
+                        .on_hover_text("With a + where the person's name is");
+                    ui.end_row();
+
+                    ui.label("Home payload");
+                    ui.text_edit_singleline(&mut presence.home_payload);
+                    ui.end_row();
+                });
+            }
+
+            ui.separator();
+
+            let profile = self.config.profile_mut();
+            let mut alarm = profile.alarm.is_some();
+            if ui
+                .checkbox(&mut alarm, "Chime here when a cycle finishes")
+                .changed()
+            {
+                profile.alarm = alarm.then(Default::default);
+            }
+
+            if let Some(alarm) = &mut profile.alarm {
+                ui.horizontal(|ui| {
+                    let label = match &alarm.sound {
+                        Some(path) => path.display().to_string(),
+                        None => "Terminal bell".to_owned(),
+                    };
+
+                    if ui.button(label).clicked() {
+                        alarm.sound = rfd::FileDialog::new()
+                            .add_filter("Sound", &["wav", "ogg", "oga", "mp3", "aiff"])
+                            .pick_file();
+                    }
+
+                    ui.checkbox(&mut alarm.flash, "Flash window");
+                });
+            }
+        });
+    }
+
+    fn markers_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Markers", |ui| {
+            let mut remove = None;
+            let mut set_open = None;
+
+            ui.horizontal(|ui| {
+                TextEdit::singleline(&mut self.marker_filter)
+                    .hint_text("Filter by name or type")
+                    .desired_width(150.)
+                    .show(ui);
+
+                if ui.button("Expand all").clicked() {
+                    set_open = Some(true);
+                }
+
+                if ui.button("Collapse all").clicked() {
+                    set_open = Some(false);
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Snap points to");
+                ComboBox::from_id_source("snap")
+                    .selected_text(self.snap.map_or("nothing", snap::Snap::name))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.snap, None, "nothing");
+                        for snap in snap::Snap::ALL {
+                            ui.selectable_value(&mut self.snap, Some(snap), snap.name());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Move dropped point markers to the middle of the nearest spot");
+            });
+
+            ui.separator();
+
+            let filter = self.marker_filter.to_lowercase();
+            let profile = self.config.profile_mut();
+            let threshold = profile.luminance_threshold;
+
+            let topics = marker_topics(profile);
+
+            for (idx, marker) in profile.markers.iter_mut().enumerate() {
+                if !marker.matches(&filter) {
+                    continue;
+                }
+
+                let title = match self.values.get(idx) {
+                    Some(value) => format!(
+                        "{} ({}): {}",
+                        marker.name,
+                        marker.ty.name(),
+                        serde_json::to_string(value).unwrap()
+                    ),
+                    None => format!("{} ({})", marker.name, marker.ty.name()),
+                };
+
+                CollapsingHeader::new(title)
+                    .id_source(("marker", idx))
+                    .default_open(true)
+                    .open(set_open)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.text_edit_singleline(&mut marker.name).changed() {
+                                validate::sanitize_marker_name(marker);
+                            }
+
+                            let (topics, conflict) = &topics[idx];
+                            if *conflict {
+                                ui.colored_label(ui.visuals().warn_fg_color, topics)
+                                    .on_hover_text("Another marker publishes to this topic");
+                            } else {
+                                ui.weak(topics);
+                            }
+
+                            if ui.button("Remove").clicked() {
+                                remove = Some(idx);
+                            }
+
+                            if ui
+                                .selectable_value(&mut self.editing_marker, Some(idx), "Edit")
+                                .clicked()
+                            {
+                                self.editing_marker = Some(idx);
+                            }
+                        });
+
+                        let problem = validate::check_marker_name(marker).err();
+                        let unclean = !marker.name.is_empty()
+                            && validate::check_topic_level(&marker.name).is_err();
+                        if problem.is_some() || marker.raw_name {
+                            ui.horizontal(|ui| {
+                                if let Some(problem) = problem {
+                                    ui.colored_label(
+                                        ui.visuals().warn_fg_color,
+                                        format!("Name {}", problem),
+                                    );
+                                }
+
+                                if unclean && ui.button("Fix").clicked() {
+                                    marker.raw_name = false;
+                                    validate::sanitize_marker_name(marker);
+                                }
+
+                                ui.checkbox(&mut marker.raw_name, "Keep as typed")
+                                    .on_hover_text(
+                                        "Allow whitespace and topic separators in the name",
+                                    );
+                            });
+                        }
+
+                        marker_settings_ui(ui, marker, threshold);
+
+                        marker_type_ui(
+                            ui,
+                            idx,
+                            marker,
+                            self.sampled.get(idx).map(Vec::as_slice),
+                            self.quality.get(idx),
+                            marker.threshold_or(threshold),
+                        );
+                    });
+            }
+
+            ui.separator();
+
+            if let Some(remove) = remove {
+                profile.markers.remove(remove);
+
+                if remove < self.sampled.len() {
+                    self.sampled.remove(remove);
+                }
+                if remove < self.values.len() {
+                    self.values.remove(remove);
+                }
+                if remove < self.quality.len() {
+                    self.quality.remove(remove);
+                }
+                self.editing_marker = None;
+            }
+
+            if ui.button("Add point marker").clicked() {
+                profile.markers.push(Marker::new(MarkerType::Point {
+                    pos: Pos2::new(0.5, 0.5),
+                    size: 0.01,
+                }));
+            }
+
+            if ui.button("Add GPIO input").clicked() {
+                profile.markers.push(Marker::new(MarkerType::Gpio {
+                    pin: 17,
+                    active_low: false,
+                    pull_up: false,
+                }));
+            }
+
+            if ui.button("Add seven segment marker").clicked() {
+                profile.markers.push(Marker::new(MarkerType::SevenSegment {
+                    start: Pos2::new(0.4, 0.4),
+                    end: Pos2::new(0.4, 0.6),
+                    bottom: Pos2::new(0.4, 0.5),
+                    digits: 3,
+                    spacing: 0.005,
+                    size: 0.01,
+                }));
+            }
+
+            ui.menu_button("Add from template", |ui| {
+                for template in templates::TEMPLATES {
+                    if ui.button(template.name).clicked() {
+                        profile.markers.extend((template.markers)());
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+    }
+
+    fn sampling_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Sampling", |ui| {
+            Slider::new(
+                &mut self.config.profile_mut().luminance_threshold,
+                0.001..=0.999,
+            )
+            .ui(ui);
+
+            ui.collapsing("Lighting", |ui| {
+                ui.label(
+                    "Other thresholds for other lighting. The first one that applies is used.",
+                );
+
+                if let Some((ambient, lighting)) = &self.lighting {
+                    ui.label(format!(
+                        "Ambient luminance is {:.2}, using {}",
+                        ambient,
+                        match lighting {
+                            Some(name) => format!("the {} threshold", name),
+                            None => "the default threshold".to_owned(),
+                        }
+                    ));
+                }
+
+                lighting::edit(ui, &mut self.config.profile_mut().lighting);
+            });
+
+            ui.collapsing("Segment patterns", |ui| {
+                segment_patterns_ui(ui, &mut self.config.profile_mut().segment_patterns);
+            });
+
+            self.ambient_reference_ui(ui);
+
+            ui.horizontal(|ui| {
+                ui.label("Shake compensation");
+                DragValue::new(&mut self.config.profile_mut().peak_search)
+                    .suffix(" px")
+                    .clamp_range(0..=8)
+                    .ui(ui)
+                    .on_hover_text("Use the brightest pixel this close to each point");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Interpolation").on_hover_text(
+                    "Bilinear reads between pixels, for points on segments only a few \
+                     pixels wide",
+                );
+                let profile = self.config.profile_mut();
+                for interpolation in Interpolation::ALL {
+                    ui.radio_value(
+                        &mut profile.interpolation,
+                        interpolation,
+                        interpolation.name(),
+                    );
+                }
+            });
+
+            self.reference_frame_ui(ui);
+
+            ui.horizontal(|ui| {
+                if ui.button("Sample").clicked() {
+                    self.sample();
+                }
+
+                if ui.button("Threshold sweep").clicked() {
+                    self.sweep = Some(sweep::Sweep::new(
+                        &self.config.profile().markers,
+                        &self.sampled,
+                        &self.config.profile().segment_patterns,
+                    ));
+                }
+
+                if ui
+                    .button("Tune to display")
+                    .on_hover_text("Find thresholds that decode what the display shows")
+                    .clicked()
+                {
+                    self.tune = Some(tune::Tune::new(
+                        &self.config.profile().markers,
+                        &self.values,
+                    ));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let mut recording = self.config.record_directory.is_some();
+                if ui
+                    .checkbox(&mut recording, "Record frames")
+                    .on_hover_text("Save every sampled frame with its decoded values")
+                    .changed()
+                {
+                    self.config.record_directory = if recording {
+                        rfd::FileDialog::new().pick_folder()
+                    } else {
+                        None
+                    };
+                }
+
+                if let Some(dir) = &self.config.record_directory {
+                    ui.label(dir.display().to_string());
+                }
+            });
+
+            let mut paused = self.paused.contains(&self.config.profile().topic_prefix);
+            if ui
+                .checkbox(&mut paused, "Paused for maintenance")
+                .on_hover_text("Stop sampling and publishing, and mark the machine as paused")
+                .changed()
+            {
+                self.backend.send(backend::Command::SetPaused {
+                    profile: self.config.active_profile,
+                    paused,
+                });
+            }
+
+            if ui
+                .button("Acknowledge")
+                .on_hover_text(format!(
+                    "Silence reminders and reset the cycle, like publishing to {}/ack",
+                    self.config.profile().topic_prefix
+                ))
+                .clicked()
+            {
+                self.backend
+                    .send(backend::Command::Ack(self.config.active_profile));
+            }
+
+            self.publishing_ui(ui);
+        });
+    }
+
+    /// The point marker that measures the ambient light for the thresholds,
+    /// and how they adjust to it.
+    fn ambient_reference_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Ambient reference");
+
+            let profile = self.config.profile_mut();
+            let mut selected = profile
+                .ambient_reference
+                .as_ref()
+                .map(|reference| reference.marker.clone());
+
+            ComboBox::from_id_source("ambient_reference")
+                .selected_text(selected.as_deref().unwrap_or("none"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut selected, None, "none");
+                    for marker in &profile.markers {
+                        if matches!(marker.ty, MarkerType::Point { .. }) {
+                            ui.selectable_value(
+                                &mut selected,
+                                Some(marker.name.clone()),
+                                &marker.name,
+                            );
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "A point marker that only sees the ambient light, to adjust all thresholds by",
+                );
+
+            match selected {
+                None => profile.ambient_reference = None,
+                Some(marker) => {
+                    profile
+                        .ambient_reference
+                        .get_or_insert_with(|| lighting::AmbientReference {
+                            marker: String::new(),
+                            baseline: self.ambient.unwrap_or(0.5),
+                            compensation: Default::default(),
+                        })
+                        .marker = marker
+                }
+            }
+        });
+
+        if let Some(reference) = &mut self.config.profile_mut().ambient_reference {
+            ui.horizontal(|ui| {
+                ui.radio_value(
+                    &mut reference.compensation,
+                    lighting::Compensation::Offset,
+                    "Offset",
+                );
+                ui.radio_value(
+                    &mut reference.compensation,
+                    lighting::Compensation::Scale,
+                    "Scale",
+                );
+                ui.label("thresholds from a baseline of");
+                DragValue::new(&mut reference.baseline)
+                    .speed(0.01)
+                    .clamp_range(0.0..=1.0)
+                    .ui(ui);
+
+                if let Some(ambient) = self.ambient {
+                    if ui.button(format!("Use current {:.2}", ambient)).clicked() {
+                        reference.baseline = ambient;
+                    }
+                }
+            });
+        }
+    }
+
+    /// The frame later frames are compared against, to follow the camera
+    /// when it moves.
+    fn reference_frame_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    self.frame_image.is_some(),
+                    egui::Button::new("Set reference frame"),
+                )
+                .on_hover_text("Save the current frame to compare later frames against")
+                .clicked()
+            {
+                self.save_reference_frame();
+            }
+
+            if let Some(path) = &self.config.profile().reference_frame {
+                ui.label(path.display().to_string());
+            }
+        });
+
+        if self.config.profile().reference_frame.is_some() {
+            ui.horizontal(|ui| {
+                ui.label("Follow the camera up to");
+                DragValue::new(&mut self.config.profile_mut().max_shift)
+                    .suffix(" px")
+                    .clamp_range(0..=64)
+                    .ui(ui);
+
+                let mut comparing = self.compare.is_some();
+                if ui.checkbox(&mut comparing, "Compare").changed() {
+                    self.compare = comparing.then_some(0.5);
+                    self.update_differences();
+                }
+
+                if let Some(blend) = &mut self.compare {
+                    Slider::new(blend, 0.0..=1.0).text("reference").ui(ui);
+                }
+
+                match self.shift {
+                    Some([0, 0]) | None => {}
+                    Some([dx, dy]) => {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            format!("moved by {dx}, {dy} px"),
+                        );
+                    }
+                }
+            });
+        }
+    }
+
+    /// How often to publish, and when to give up on the webcam and MQTT.
+    fn publishing_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut changes_only = self.config.heartbeat_secs.is_some();
+            if ui
+                .checkbox(&mut changes_only, "Only publish changes")
+                .on_hover_text("Skip markers that look the same as in the previous frame")
+                .changed()
+            {
+                self.config.heartbeat_secs = changes_only.then_some(300);
+            }
+
+            if let Some(secs) = &mut self.config.heartbeat_secs {
+                ui.label("Publish all every");
+                DragValue::new(secs)
+                    .suffix(" s")
+                    .clamp_range(1..=86400)
+                    .ui(ui);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Restart after");
+            DragValue::new(&mut self.config.stall_intervals)
+                .suffix(" missed samples")
+                .clamp_range(0..=1000)
+                .ui(ui)
+                .on_hover_text(
+                    "Reopen the webcam and reconnect to MQTT when no frame comes in, \
+                    or never if 0",
+                );
+        });
+
+        ui.horizontal(|ui| {
+            let mut throttle = self.config.throttle_secs.is_some();
+            if ui
+                .checkbox(&mut throttle, "Throttle publishes")
+                .on_hover_text(
+                    "Publish each topic at most once per window, \
+                    and the last change once it's over",
+                )
+                .changed()
+            {
+                self.config.throttle_secs = throttle.then_some(10);
+            }
+
+            if let Some(secs) = &mut self.config.throttle_secs {
+                ui.label("to every");
+                DragValue::new(secs)
+                    .suffix(" s")
+                    .clamp_range(1..=86400)
+                    .ui(ui);
+            }
+        });
+
+        let mut low_power = self.config.low_power.is_some();
+        if ui
+            .checkbox(&mut low_power, "Low power")
+            .on_hover_text("Sample less often and with smaller frames, for battery power")
+            .changed()
+        {
+            self.config.low_power = low_power.then(Default::default);
+        }
+
+        if let Some(low_power) = &mut self.config.low_power {
+            Grid::new("low_power").num_columns(2).show(ui, |ui| {
+                ui.label("Sample at most every");
+                DragValue::new(&mut low_power.refresh_secs)
+                    .suffix(" s")
+                    .clamp_range(1..=86400)
+                    .ui(ui);
+                ui.end_row();
+
+                ui.label("Scale streams down to");
+                DragValue::new(&mut low_power.max_width)
+                    .suffix(" px wide")
+                    .clamp_range(64..=4096)
+                    .ui(ui);
+                ui.end_row();
+
+                ui.label("Ping MQTT every");
+                DragValue::new(&mut low_power.keep_alive_secs)
+                    .suffix(" s")
+                    .clamp_range(5..=3600)
+                    .ui(ui);
+                ui.end_row();
+            });
+        }
+    }
+
+    fn config_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Config", |ui| {
+            ui.checkbox(&mut self.export_secrets, "Include passwords in export");
+
+            ui.horizontal(|ui| {
+                if ui.button("Export…").clicked() {
+                    self.export_config();
+                }
+
+                if ui.button("Import…").clicked() {
+                    self.import_config();
+                }
+            });
+
+            self.backups_ui(ui);
+        });
+    }
+
+    fn keybindings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Keybindings", |ui| {
+            Grid::new("keybindings").num_columns(2).show(ui, |ui| {
+                for (idx, (label, shortcut)) in self
+                    .config
+                    .keybindings
+                    .shortcuts_mut()
+                    .into_iter()
+                    .enumerate()
+                {
+                    ui.label(label);
+
+                    let text = if self.recording_shortcut == Some(idx) {
+                        "Press a key…".to_owned()
+                    } else {
+                        keyboard_shortcut(shortcut).map_or_else(
+                            || shortcut.logical_key.clone(),
+                            |shortcut| ui.ctx().format_shortcut(&shortcut),
+                        )
+                    };
+
+                    if ui.button(text).clicked() {
+                        self.recording_shortcut = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            ui.checkbox(
+                &mut self.config.keybindings.confirm_quit,
+                "Confirm before quitting",
+            );
+        });
+    }
+
+    fn profile_ui(&mut self, ui: &mut egui::Ui) {
+        let mut selected = self.config.active_profile;
+        let mut removed = false;
+
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("profile")
+                .selected_text(&self.config.profile().name)
+                .show_ui(ui, |ui| {
+                    for (idx, profile) in self.config.profiles.iter().enumerate() {
+                        ui.selectable_value(&mut selected, idx, &profile.name);
+                    }
+                });
+
+            if ui.button("Add").clicked() {
+                self.config.profiles.push(config::Profile {
+                    name: format!("profile {}", self.config.profiles.len() + 1),
+                    ..Default::default()
+                });
+                selected = self.config.profiles.len() - 1;
+            }
+
+            if ui
+                .add_enabled(self.config.profiles.len() > 1, egui::Button::new("Remove"))
+                .clicked()
+            {
+                self.config.profiles.remove(self.config.active_profile);
+                selected = 0;
+                removed = true;
+            }
+        });
+
+        if removed || selected != self.config.active_profile {
+            self.select_profile(selected);
+        }
+
+        ui.collapsing("Profile", |ui| {
+            Grid::new("profile_config").num_columns(2).show(ui, |ui| {
+                let profile = self.config.profile_mut();
+
+                ui.label("Name");
+                ui.text_edit_singleline(&mut profile.name);
+                ui.end_row();
+
+                ui.label("Topic prefix");
+                ui.text_edit_singleline(&mut profile.topic_prefix);
+                ui.end_row();
+
+                let composite = &mut profile.time_remaining;
+                ui.label("Time remaining").on_hover_text(
+                    "Combines the hour and minute values into one, published instead of them",
+                );
+                ui.horizontal(|ui| {
+                    TextEdit::singleline(&mut composite.hour)
+                        .desired_width(60.)
+                        .ui(ui);
+                    ui.label(":");
+                    TextEdit::singleline(&mut composite.minute)
+                        .desired_width(60.)
+                        .ui(ui);
+                    ui.label("on");
+                    TextEdit::singleline(&mut composite.topic)
+                        .desired_width(100.)
+                        .ui(ui);
+                });
+                ui.end_row();
+
+                ui.label("");
+                ui.horizontal(|ui| {
+                    for unit in TimeUnit::ALL {
+                        ui.radio_value(&mut composite.unit, unit, unit.name());
+                    }
+                });
+                ui.end_row();
+
+                let points = profile
+                    .markers
+                    .iter()
+                    .filter(|marker| matches!(marker.ty, MarkerType::Point { .. }))
+                    .map(|marker| marker.name.clone())
+                    .collect::<Vec<_>>();
+
+                ui.label("Machine");
+                ComboBox::from_id_source("machine")
+                    .selected_text(profile.machine.name())
+                    .show_ui(ui, |ui| {
+                        for name in machine::Machine::NAMES {
+                            if ui
+                                .selectable_label(profile.machine.name() == name, name)
+                                .clicked()
+                                && profile.machine.name() != name
+                            {
+                                profile.machine = machine::Machine::from_name(name);
+                            }
+                        }
+                    });
+                ui.end_row();
+
+                match &mut profile.machine {
+                    machine::Machine::Generic => {}
+                    machine::Machine::Washer {
+                        door_lock,
+                        door_lock_secs,
+                    } => {
+                        ui.label("Door lock light");
+                        ComboBox::from_id_source("door_lock")
+                            .selected_text(door_lock.as_deref().unwrap_or("None"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(door_lock, None, "None");
+                                for name in points {
+                                    ui.selectable_value(door_lock, Some(name.clone()), name);
+                                }
+                            })
+                            .response
+                            .on_hover_text("Finishes the cycle when it goes off");
+                        ui.end_row();
+
+                        ui.label("Door unlocks after");
+                        DragValue::new(door_lock_secs)
+                            .clamp_range(0..=3600)
+                            .suffix(" s")
+                            .ui(ui);
+                        ui.end_row();
+                    }
+                    machine::Machine::Dryer { anti_wrinkle_mins } => {
+                        ui.label("Anti-wrinkle for");
+                        DragValue::new(anti_wrinkle_mins)
+                            .clamp_range(0..=24 * 60)
+                            .suffix(" min")
+                            .ui(ui)
+                            .on_hover_text("Tumbling in this time doesn't start a new cycle");
+                        ui.end_row();
+                    }
+                }
+            });
+
+            let schedule = &mut self.config.profile_mut().schedule;
+            ui.horizontal(|ui| {
+                let mut scheduled = schedule.is_some();
+                if ui.checkbox(&mut scheduled, "Only sample from").changed() {
+                    *schedule = scheduled.then(Default::default);
+                }
+
+                if let Some(schedule) = schedule.as_mut() {
+                    for (time, label) in [
+                        (&mut schedule.from, Some("until")),
+                        (&mut schedule.until, None),
+                    ] {
+                        let mut text = time.clone().unwrap_or_default();
+                        if TextEdit::singleline(&mut text)
+                            .desired_width(50.)
+                            .hint_text("HH:MM")
+                            .ui(ui)
+                            .changed()
+                        {
+                            *time = Some(text).filter(|text| !text.is_empty());
+                        }
+
+                        if let Some(label) = label {
+                            ui.label(label);
+                        }
+                    }
+                }
+            });
+
+            if let Some(schedule) = schedule {
+                ui.checkbox(&mut schedule.on_the_clock, "On the clock")
+                    .on_hover_text("Such as every minute on the minute");
+            }
+
+            let frame_clock = &mut self.config.profile_mut().frame_clock;
+            let mut reading = frame_clock.is_some();
+            if ui
+                .checkbox(&mut reading, "Read frame time from the camera's clock")
+                .on_hover_text(
+                    "For cameras that burn a timestamp into the frame, which can be marked with the camera clock template",
+                )
+                .changed()
+            {
+                *frame_clock = reading.then(Default::default);
+            }
+
+            if let Some(clock) = frame_clock {
+                Grid::new("frame_clock").num_columns(2).show(ui, |ui| {
+                    ui.label("Hour marker");
+                    ui.text_edit_singleline(&mut clock.hour);
+                    ui.end_row();
+
+                    ui.label("Minute marker");
+                    ui.text_edit_singleline(&mut clock.minute);
+                    ui.end_row();
+
+                    ui.label("Second marker");
+                    let mut second = clock.second.clone().unwrap_or_default();
+                    if TextEdit::singleline(&mut second)
+                        .hint_text("None")
+                        .ui(ui)
+                        .changed()
+                    {
+                        clock.second = Some(second).filter(|second| !second.is_empty());
+                    }
+                    ui.end_row();
+
+                    if let Some(age) = self
+                        .frame_captured
+                        .and_then(|time| time.elapsed().ok())
+                    {
+                        ui.label("Captured");
+                        ui.label(format!("{} s ago", age.as_secs()));
+                        ui.end_row();
+                    }
+                });
+            }
+
+            let vibration = &mut self.config.profile_mut().vibration;
+            let mut detecting = vibration.is_some();
+            if ui
+                .checkbox(&mut detecting, "Detect vibration")
+                .on_hover_text("Publishes whether a region of the frame shakes along with the drum")
+                .changed()
+            {
+                *vibration = detecting.then(Default::default);
+            }
+
+            if let Some(vibration) = vibration {
+                Grid::new("vibration").num_columns(2).show(ui, |ui| {
+                    region::edit(ui, &mut vibration.region);
+
+                    ui.label("Threshold");
+                    ui.horizontal(|ui| {
+                        Slider::new(&mut vibration.threshold, 0.001..=0.2)
+                            .logarithmic(true)
+                            .ui(ui);
+                        if let Some((change, vibrating)) = self.vibration {
+                            ui.label(format!(
+                                "changed {:.3}, {}",
+                                change,
+                                match vibrating {
+                                    Some(true) => "vibrating",
+                                    Some(false) => "still",
+                                    None => "not sure yet",
+                                }
+                            ));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Frames to agree");
+                    DragValue::new(&mut vibration.frames)
+                        .clamp_range(1..=20)
+                        .ui(ui);
+                    ui.end_row();
+                });
+            }
+
+            let door = &mut self.config.profile_mut().door;
+            let mut detecting = door.is_some();
+            if ui
+                .checkbox(&mut detecting, "Detect the door")
+                .on_hover_text("Publishes whether the door is open, and whether laundry was left in")
+                .changed()
+            {
+                *door = detecting.then(Default::default);
+            }
+
+            if let Some(door) = door {
+                Grid::new("door").num_columns(2).show(ui, |ui| {
+                    region::edit(ui, &mut door.region);
+
+                    ui.label("Closed");
+                    ui.horizontal(|ui| {
+                        ui.label(if door.closed.is_empty() {
+                            "Not captured yet"
+                        } else {
+                            "Captured"
+                        });
+                        if let Some(image) = &self.frame_image {
+                            if ui
+                                .button("Closed now")
+                                .on_hover_text("Captures the region as it looks with the door closed")
+                                .clicked()
+                            {
+                                door.closed = region::cells(image, &door.region);
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Threshold");
+                    ui.horizontal(|ui| {
+                        Slider::new(&mut door.threshold, 0.001..=0.5)
+                            .logarithmic(true)
+                            .ui(ui);
+                        if let Some((difference, open)) = self.door {
+                            ui.label(format!(
+                                "differs {:.3}, {}",
+                                difference,
+                                match open {
+                                    Some(true) => "open",
+                                    Some(false) => "closed",
+                                    None => "not sure yet",
+                                }
+                            ));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Left in after");
+                    DragValue::new(&mut door.unretrieved_mins)
+                        .clamp_range(0..=24 * 60)
+                        .suffix(" min")
+                        .ui(ui)
+                        .on_hover_text("Publishes unretrieved once a cycle finished this long ago without the door opening, 0 to never");
+                    ui.end_row();
+                });
+            }
+
+            let ack_hint = format!(
+                "Publishes reminders until the door opens or something is published to {}/ack",
+                self.config.profile().topic_prefix
+            );
+            let reminders = &mut self.config.profile_mut().reminders;
+            ui.horizontal(|ui| {
+                let mut reminding = reminders.is_some();
+                if ui
+                    .checkbox(&mut reminding, "Remind after")
+                    .on_hover_text(ack_hint)
+                    .changed()
+                {
+                    *reminders = reminding.then(Default::default);
+                }
+
+                if let Some(reminders) = reminders {
+                    let intervals = &mut reminders.intervals_mins;
+                    for (idx, mins) in intervals.iter_mut().enumerate() {
+                        if idx > 0 {
+                            ui.label("then");
+                        }
+                        DragValue::new(mins)
+                            .clamp_range(1..=24 * 60)
+                            .suffix(" min")
+                            .ui(ui);
+                    }
+
+                    if ui.small_button("+").clicked() {
+                        intervals.push(intervals.last().copied().unwrap_or(10));
+                    }
+                    if intervals.len() > 1 && ui.small_button("-").clicked() {
+                        intervals.pop();
+                    }
+                }
+            });
+
+            let audio = &mut self.config.profile_mut().audio;
+            let mut listening = audio.is_some();
+            if ui
+                .checkbox(&mut listening, "Listen to a microphone")
+                .on_hover_text("Publishes whether it's loud, such as when the buzzer goes off")
+                .changed()
+            {
+                *audio = listening.then(Default::default);
+            }
+
+            if let Some(audio) = audio {
+                Grid::new("audio").num_columns(2).show(ui, |ui| {
+                    ui.label("Device");
+                    let mut device = audio.device.clone().unwrap_or_default();
+                    if TextEdit::singleline(&mut device)
+                        .hint_text("Default")
+                        .ui(ui)
+                        .changed()
+                    {
+                        audio.device = Some(device).filter(|device| !device.is_empty());
+                    }
+                    ui.end_row();
+
+                    ui.label("Frequencies");
+                    ui.horizontal(|ui| {
+                        DragValue::new(&mut audio.low_hz)
+                            .speed(10.)
+                            .clamp_range(20.0..=audio.high_hz)
+                            .suffix(" Hz")
+                            .ui(ui);
+                        ui.label("to");
+                        DragValue::new(&mut audio.high_hz)
+                            .speed(10.)
+                            .clamp_range(audio.low_hz..=20000.)
+                            .suffix(" Hz")
+                            .ui(ui);
+                    });
+                    ui.end_row();
+
+                    ui.label("Threshold");
+                    ui.horizontal(|ui| {
+                        Slider::new(&mut audio.threshold, 0.001..=1.)
+                            .logarithmic(true)
+                            .ui(ui);
+                        if let Some((level, loud)) = self.sound {
+                            ui.label(format!(
+                                "level {:.3}, {}",
+                                level,
+                                if loud { "loud" } else { "quiet" }
+                            ));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Stay loud for");
+                    DragValue::new(&mut audio.hold_secs)
+                        .speed(0.1)
+                        .clamp_range(0.0..=60.)
+                        .suffix(" s")
+                        .ui(ui);
+                    ui.end_row();
+
+                    ui.label("Finished beeps");
+                    ui.horizontal(|ui| match &self.beep_recording {
+                        Some(levels) => {
+                            ui.label(format!(
+                                "Recording, {:.1} s",
+                                (audio::BLOCK * levels.len() as u32).as_secs_f32()
+                            ));
+                            if ui.button("Stop").clicked() {
+                                audio.pattern = beep::pattern(levels, audio.threshold);
+                                self.beep_recording = None;
+                            }
+                        }
+                        None => {
+                            if audio.pattern.is_empty() {
+                                ui.label("None");
+                            } else {
+                                ui.label(format!(
+                                    "{} beeps over {:.1} s",
+                                    audio.pattern.len().div_ceil(2),
+                                    audio.pattern.iter().sum::<u32>() as f32 / 1000.
+                                ));
+                            }
+
+                            if ui
+                                .button("Record")
+                                .on_hover_text(
+                                    "Start, let the machine beep when it finishes, then stop",
+                                )
+                                .clicked()
+                            {
+                                self.beep_recording = Some(vec![]);
+                            }
+
+                            if !audio.pattern.is_empty() && ui.button("Forget").clicked() {
+                                audio.pattern.clear();
+                            }
+                        }
+                    });
+                    ui.end_row();
+                });
+            }
+        });
+    }
+
+    /// Loads the active profile's reference frame if it changed.
+    fn load_reference(&mut self, ctx: &Context) {
+        let path = self.config.profile().reference_frame.clone();
+        if self.reference.as_ref().map(|(path, _)| path) == path.as_ref() {
+            return;
+        }
+
+        self.reference = path.map(|path| {
+            let reference = registration::Reference::load(&path)
+                .map_err(|e| error!("Error loading reference frame {}: {}", path.display(), e))
+                .ok()
+                .map(|reference| {
+                    let texture = ctx.load_texture(
+                        "reference",
+                        reference.to_color_image(),
+                        TextureOptions::default(),
+                    );
+                    (reference, texture)
+                });
+
+            (path, reference)
+        });
+
+        self.update_differences();
+    }
+
+    fn update_differences(&mut self) {
+        self.differences = match (&self.reference, &self.frame_image, self.compare) {
+            (Some((_, Some((reference, _)))), Some(frame), Some(_)) => {
+                reference.differences(frame, &self.config.profile().markers)
+            }
+            _ => vec![],
+        };
+    }
+
+    /// Saves the current frame next to the config file, as the active
+    /// profile's reference frame.
+    fn save_reference_frame(&mut self) {
+        let (Some(frame), Some(config_path)) = (&self.frame_image, &self.config_path) else {
+            return;
+        };
+
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // A new name each time, so the backend notices the change.
+        let profile = self.config.profile_mut();
+        let path = config_path
+            .with_file_name("references")
+            .join(format!("{}-{}.png", profile.name, millis));
+
+        match registration::save(frame, &path) {
+            Ok(()) => profile.reference_frame = Some(path),
+            Err(e) => error!("Error saving reference frame to {}: {}", path.display(), e),
+        }
+    }
+
+    fn select_profile(&mut self, idx: usize) {
+        self.config.active_profile = idx;
+        self.editing_marker = None;
+        self.sweep = None;
+        self.tune = None;
+        self.sampled.clear();
+        self.values.clear();
+        self.quality.clear();
+        self.shift = None;
+        self.lighting = None;
+        self.ambient = None;
+        self.vibration = None;
+        self.door = None;
+        self.frame_captured = None;
+        self.timings = None;
+        self.frame_interval = None;
+        self.sound = None;
+        self.beep_recording = None;
+        self.frame_image = None;
+
+        // Don't show the previous profile's frame until the next scheduled
+        // sample.
+        self.sample();
+    }
+
+    fn backups_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(path) = self.config_path.clone() else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            let selected_text = self
+                .backups
+                .get(self.selected_backup)
+                .map(|backup| backup.age())
+                .unwrap_or_else(|| "Previous configs".to_owned());
+
+            let response = ComboBox::from_id_source("backups")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for (idx, backup) in self.backups.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected_backup, idx, backup.age());
+                    }
+                });
+
+            // Only hit the disk when the picker is opened.
+            if response.response.clicked() {
+                self.backups = backup::list(&path);
+                self.selected_backup = 0;
+            }
+
+            let selected = self
+                .backups
+                .get(self.selected_backup)
+                .map(|backup| backup.path.clone());
+
+            if ui
+                .add_enabled(selected.is_some(), egui::Button::new("Restore"))
+                .clicked()
+            {
+                if let Some(backup) = selected {
+                    match config::Config::load(&backup) {
+                        Ok(config) => {
+                            self.config = config;
+                            self.args.apply(&mut self.config);
+                            self.select_profile(self.config.active_profile);
+                            info!("Restored config from {}", backup.display());
+                        }
+                        Err(e) => {
+                            error!("Error restoring config from {}: {}", backup.display(), e)
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn export_config(&self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("laundry-machine-mqtt.json")
+            .save_file()
+        else {
+            return;
+        };
+
+        let config = if self.export_secrets {
+            self.config.clone()
+        } else {
+            self.config.without_secrets()
+        };
+
+        let res = serde_json::to_string_pretty(&config)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+
+        match res {
+            Ok(()) => info!("Exported config to {}", path.display()),
+            Err(e) => error!("Error exporting config to {}: {}", path.display(), e),
+        }
+    }
+
+    fn import_config(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .pick_file()
+        else {
+            return;
+        };
+
+        let res = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()));
+
+        match res {
+            Ok(config) => {
+                self.config.import(config);
+                self.editing_marker = None;
+                self.sampled.clear();
+                self.values.clear();
+                self.quality.clear();
+                info!("Imported config from {}", path.display());
+            }
+            Err(e) => error!("Error importing config from {}: {}", path.display(), e),
+        }
+    }
+
+    /// Sends the config to the backend if it changed since it was last sent.
+    fn send_config(&mut self) {
+        if self.config != self.sent_config {
+            self.sent_config = self.config.clone();
+            self.backend
+                .send(backend::Command::Config(Box::new(self.config.clone())));
+        }
+    }
+
+    fn sample(&mut self) {
+        // Profile and marker indices have to match the backend's.
+        self.send_config();
+
+        self.backend.send(backend::Command::Sample {
+            profile: self.config.active_profile,
+            markers: (0..self.config.profile().markers.len()).collect(),
+        });
+    }
+
+    /// Time until the backend samples the active profile again.
+    fn until_next_sample(&self) -> Duration {
+        let refresh_rate = self
+            .config
+            .refresh_rate(Duration::from_secs(self.args.refresh_secs));
+        let interval = self
+            .config
+            .profile()
+            .markers
+            .iter()
+            .map(|marker| {
+                marker
+                    .refresh_secs
+                    .map_or(refresh_rate, Duration::from_secs)
+            })
+            .min()
+            .unwrap_or(refresh_rate);
+
+        self.frame_time
+            .map_or(interval, |time| interval.saturating_sub(time.elapsed()))
+    }
+
+    fn publish(&self) {
+        self.backend.send(backend::Command::Publish);
+    }
+
+    /// Copies the latest frame to the GPU, unless nobody would see it.
+    fn upload_frame(&mut self, ctx: &Context) {
+        let minimized = ctx.input(|i| i.viewport().minimized == Some(true));
+        if !self.frame_stale || self.config.low_power.is_some() && minimized {
+            return;
+        }
+
+        let Some(image) = self.frame_image.clone() else {
+            return;
+        };
+
+        self.frame_stale = false;
+
+        match &mut self.frame {
+            Some(frame) => frame.set(image, TextureOptions::default()),
+            None => self.frame = Some(ctx.load_texture("frame", image, TextureOptions::default())),
+        }
+    }
+
+    fn receive_updates(&mut self, ctx: &Context) {
+        let updates = self.backend.poll().collect::<Vec<_>>();
+
+        for update in updates {
+            match update {
+                // Only the active profile is shown.
+                backend::Update::Frame(profile, _)
+                | backend::Update::Sampled(profile, ..)
+                | backend::Update::Shift(profile, _)
+                | backend::Update::Lighting(profile, ..)
+                | backend::Update::Ambient(profile, _)
+                | backend::Update::Vibration(profile, ..)
+                | backend::Update::Door(profile, ..)
+                | backend::Update::FrameTime(profile, _)
+                | backend::Update::FrameTime(profile, _)
+                | backend::Update::Timings(profile, _)
+                | backend::Update::Sound(profile, ..)
+                    if profile != self.config.active_profile => {}
+                backend::Update::Frame(_, image) => {
+                    self.frame_interval = self.frame_time.map(|time| time.elapsed());
+                    self.frame_time = Some(Instant::now());
+                    self.frame_image = Some(image);
+                    self.frame_stale = true;
+                    self.update_differences();
+                }
+                backend::Update::Sampled(_, idx, samples, value, quality) => {
+                    let markers = self.config.profile().markers.len();
+                    if idx >= markers {
+                        continue;
+                    }
+
+                    self.sampled.resize(markers, vec![]);
+                    self.values.resize(markers, Value::Null);
+                    self.quality.resize(markers, Default::default());
+                    self.sampled[idx] = samples;
+                    self.values[idx] = value;
+                    self.quality[idx] = quality;
+                }
+                backend::Update::MqttConnected => self.publish_config_state(),
+                backend::Update::Message { topic, payload } => {
+                    if remote::set_topic(&self.config).as_ref() == Some(&topic) {
+                        self.apply_remote_config(&payload);
+                    }
+                }
+                backend::Update::ConfigChange(payload) => self.apply_remote_config(&payload),
+                backend::Update::Shift(_, shift) => self.shift = Some(shift),
+                backend::Update::Lighting(_, ambient, lighting) => {
+                    self.lighting = Some((ambient, lighting))
+                }
+                backend::Update::Ambient(_, ambient) => self.ambient = Some(ambient),
+                backend::Update::Vibration(_, change, vibrating) => {
+                    self.vibration = Some((change, vibrating))
+                }
+                backend::Update::Door(_, difference, open) => self.door = Some((difference, open)),
+                backend::Update::FrameTime(_, time) => self.frame_captured = Some(time),
+                backend::Update::Timings(_, timings) => self.timings = Some(timings),
+                backend::Update::Sound(_, level, loud) => {
+                    self.sound = Some((level, loud));
+                    if let Some(levels) = &mut self.beep_recording {
+                        levels.push(level);
+                    }
+                }
+                backend::Update::Paused { prefix, paused } => {
+                    if paused {
+                        self.paused.insert(prefix);
+                    } else {
+                        self.paused.remove(&prefix);
+                    }
+                }
+                backend::Update::Alarm(profile) => {
+                    info!("Cycle finished on {}", profile);
+                    ctx.send_viewport_cmd(ViewportCommand::RequestUserAttention(
+                        egui::UserAttentionType::Critical,
+                    ));
+                }
+                backend::Update::Error(e) => error!("{}", e),
+            }
+        }
+    }
+
+    fn apply_remote_config(&mut self, payload: &[u8]) {
+        let active_profile = self.config.active_profile;
+
+        match remote::apply(&mut self.config, payload) {
+            Ok(()) => {
+                info!("Applied remote config change");
+                if self.config.active_profile != active_profile {
+                    self.select_profile(self.config.active_profile);
+                }
+                self.publish_config_state();
+            }
+            Err(e) => error!("Error applying remote config change: {}", e),
+        }
+    }
+
+    fn publish_config_state(&self) {
+        if !self.config.mqtt.remote_config {
+            return;
+        }
+
+        if let Some((topic, payload)) = remote::state_message(&self.config) {
+            self.backend.send(backend::Command::Message {
+                topic,
+                payload,
+                retain: true,
+            });
+        }
+    }
+}
+
+/// Each marker's topics, and whether another marker publishes to one of
+/// them.
+fn marker_topics(profile: &config::Profile) -> Vec<(String, bool)> {
+    // Split seven segment displays publish each digit group separately.
+    let topics = profile
+        .markers
+        .iter()
+        .map(|marker| {
+            marker
+                .value_names()
+                .into_iter()
+                .map(|name| {
+                    let topic = marker_topic(&profile.topic_prefix, name, &profile.time_remaining);
+                    (name, topic)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Only the time remaining's hour and minute are meant to share a topic.
+    let composite = &profile.time_remaining;
+    let conflicts = topics
+        .iter()
+        .enumerate()
+        .map(|(idx, own)| {
+            own.iter().any(|(name, topic)| {
+                topics
+                    .iter()
+                    .enumerate()
+                    .filter(|&(other_idx, _)| other_idx != idx)
+                    .flat_map(|(_, other)| other)
+                    .any(|(other_name, other_topic)| {
+                        other_topic == topic
+                            && !(name != other_name
+                                && composite.reads(name)
+                                && composite.reads(other_name))
+                    })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    topics
+        .iter()
+        .zip(conflicts)
+        .map(|(topics, conflict)| {
+            let topics = topics
+                .iter()
+                .map(|(_, topic)| topic.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            (topics, conflict)
+        })
+        .collect()
+}
+
+/// The settings every type of marker has.
+fn marker_settings_ui(ui: &mut egui::Ui, marker: &mut Marker, threshold: f32) {
+    ui.horizontal(|ui| {
+        let mut own_interval = marker.refresh_secs.is_some();
+        if ui.checkbox(&mut own_interval, "Own interval").changed() {
+            marker.refresh_secs = own_interval.then_some(15);
+        }
+
+        if let Some(secs) = &mut marker.refresh_secs {
+            DragValue::new(secs)
+                .speed(1)
+                .clamp_range(1..=3600)
+                .suffix(" s")
+                .ui(ui);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let mut own_threshold = marker.threshold.is_some();
+        if ui.checkbox(&mut own_threshold, "Own threshold").changed() {
+            marker.threshold = own_threshold.then_some(threshold);
+        }
+
+        if let Some(threshold) = &mut marker.threshold {
+            Slider::new(threshold, 0.0..=1.0).ui(ui);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("When unreadable, publish");
+
+        let unknown = &mut marker.unknown;
+        ui.radio_value(unknown, None, "as is");
+        ui.radio_value(unknown, Some(Value::Null), "null");
+
+        let text = matches!(unknown, Some(Value::String(_)));
+        if ui.radio(text, "text").clicked() && !text {
+            *unknown = Some(Value::String("unknown".to_owned()));
+        }
+
+        if let Some(Value::String(text)) = unknown {
+            TextEdit::singleline(text).desired_width(80.).ui(ui);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Unit");
+
+        let mut unit = marker.unit.clone().unwrap_or_default();
+        let response = TextEdit::singleline(&mut unit)
+            .hint_text("none")
+            .desired_width(80.)
+            .ui(ui);
+        if response.changed() {
+            marker.unit = (!unit.is_empty()).then_some(unit);
+        }
+    });
+}
+
+/// The settings specific to the marker's type, with how a seven segment
+/// display was last read.
+fn marker_type_ui(
+    ui: &mut egui::Ui,
+    idx: usize,
+    marker: &mut Marker,
+    samples: Option<&[f32]>,
+    quality: Option<&Quality>,
+    threshold: f32,
+) {
+    match &mut marker.ty {
+        MarkerType::Point { size, .. } => {
+            Slider::new(size, 0.001..=0.1).ui(ui);
+        }
+        MarkerType::Gpio {
+            pin,
+            active_low,
+            pull_up,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("BCM pin");
+                DragValue::new(pin).clamp_range(0..=27).ui(ui);
+                ui.checkbox(active_low, "Active low");
+                ui.checkbox(pull_up, "Pull up");
+            });
+        }
+        MarkerType::SevenSegment {
+            digits,
+            spacing,
+            size,
+            ..
+        } => {
+            DragValue::new(digits).speed(0.1).clamp_range(1..=10).ui(ui);
+            Slider::new(spacing, 0.001..=0.1).ui(ui);
+            Slider::new(size, 0.001..=0.1).ui(ui);
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut marker.reverse_digits, "Right to left")
+                    .on_hover_text("Read the digits from end to start");
+                ui.checkbox(&mut marker.mirror_segments, "Mirrored")
+                    .on_hover_text("Swap the left and right segments of each digit");
+                ui.checkbox(&mut marker.blinks_when_paused, "Blinks when paused")
+                    .on_hover_text("Keep the value through blank samples, and publish paused");
+            });
+
+            if let Some(samples) = samples {
+                ui.horizontal(|ui| {
+                    for digit in samples.chunks(7) {
+                        segment_schematic(ui, digit, threshold);
+                    }
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Publish as");
+
+                let output = &mut marker.output;
+                ui.radio_value(output, Output::Integer, "number");
+                ui.radio_value(output, Output::Padded, "padded string");
+
+                let split = matches!(output, Output::Split { .. });
+                if ui.radio(split, "digit groups").clicked() && !split {
+                    *output = Output::Split {
+                        groups: vec![
+                            DigitGroup {
+                                name: "hour".to_owned(),
+                                digits: *digits / 2,
+                            },
+                            DigitGroup {
+                                name: "minute".to_owned(),
+                                digits: *digits - *digits / 2,
+                            },
+                        ],
+                    };
+                }
+            });
+
+            if let Output::Split { groups } = &mut marker.output {
+                let mut remove = None;
+
+                for (group_idx, group) in groups.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut group.name);
+                        DragValue::new(&mut group.digits)
+                            .clamp_range(1..=10)
+                            .suffix(" digits")
+                            .ui(ui);
+
+                        if ui.button("Remove").clicked() {
+                            remove = Some(group_idx);
+                        }
+                    });
+                }
+
+                if let Some(group_idx) = remove {
+                    groups.remove(group_idx);
+                }
+
+                if ui.button("Add digit group").clicked() {
+                    groups.push(DigitGroup {
+                        name: String::new(),
+                        digits: 1,
+                    });
+                }
+            }
+
+            CollapsingHeader::new("Retries")
+                .id_source(("retries", idx))
+                .show(ui, |ui| {
+                    let retry = &mut marker.retry;
+
+                    ui.horizontal(|ui| {
+                        ui.label("Up to");
+                        DragValue::new(&mut retry.max_retries)
+                            .clamp_range(0..=100)
+                            .ui(ui);
+                        ui.label("retries, starting");
+                        DragValue::new(&mut retry.step)
+                            .speed(0.001)
+                            .clamp_range(0.001..=0.5)
+                            .ui(ui);
+                        ui.label("away and going");
+                        DragValue::new(&mut retry.factor)
+                            .speed(0.01)
+                            .clamp_range(1.0..=4.0)
+                            .suffix("×")
+                            .ui(ui);
+                        ui.label("further each time");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Between");
+                        DragValue::new(&mut retry.min_threshold)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0)
+                            .ui(ui);
+                        ui.label("and");
+                        DragValue::new(&mut retry.max_threshold)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0)
+                            .ui(ui);
+                    });
+                });
+
+            if let Some(quality) = quality {
+                let text = format!(
+                    "Decoded at {:.2} after {} retries",
+                    quality.threshold, quality.retries
+                );
+
+                // Drifting away from the threshold.
+                if quality.retries > 4 {
+                    ui.colored_label(ui.visuals().warn_fg_color, text)
+                        .on_hover_text("The threshold may need to be calibrated again");
+                } else {
+                    ui.weak(text);
+                }
+            }
+        }
+    }
+}
+
+/// The characters seven segment displays can show, by their lit segments.
+fn segment_patterns_ui(ui: &mut egui::Ui, patterns: &mut Vec<SegmentPattern>) {
+    ui.label("The characters seven segment displays can show, by their lit segments.");
+
+    let mut remove = None;
+
+    for (idx, pattern) in patterns.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let mask = pattern.mask().unwrap_or(0);
+            for (bit, segment) in SegmentPattern::SEGMENTS.chars().enumerate() {
+                if ui
+                    .selectable_label(mask & 1 << bit != 0, segment.to_string())
+                    .clicked()
+                {
+                    pattern.set_mask(mask ^ 1 << bit);
+                }
+            }
+
+            // The last character typed replaces the old one.
+            let mut character = pattern.character.to_string();
+            if TextEdit::singleline(&mut character)
+                .desired_width(16.)
+                .ui(ui)
+                .changed()
+            {
+                if let Some(character) = character.chars().last() {
+                    pattern.character = character;
+                }
+            }
+
+            if ui.button("Remove").clicked() {
+                remove = Some(idx);
+            }
+        });
+    }
+
+    if let Some(idx) = remove {
+        patterns.remove(idx);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add pattern").clicked() {
+            patterns.push(SegmentPattern {
+                segments: "g".to_owned(),
+                character: '-',
+            });
+        }
+
+        if ui.button("Reset to digits").clicked() {
+            *patterns = SegmentPattern::defaults();
+        }
+    });
+}
+
+/// Edits a notification provider, returning whether it should be removed.
+fn provider_ui(ui: &mut egui::Ui, provider: &mut notification::Provider) -> bool {
+    let mut remove = false;
+
+    Grid::new("provider").num_columns(2).show(ui, |ui| {
+        ui.label("Service");
+        ui.horizontal(|ui| {
+            ui.label(provider.name());
+            remove = ui.button("Remove").clicked();
+        });
+        ui.end_row();
+
+        match provider {
+            notification::Provider::Ntfy {
+                server,
+                topic,
+                token,
+            } => {
+                ui.label("Server");
+                ui.text_edit_singleline(server);
+                ui.end_row();
+
+                ui.label("Topic");
+                ui.text_edit_singleline(topic);
+                ui.end_row();
+
+                optional_text_edit(ui, token, "Token", true);
+            }
+            notification::Provider::Pushover {
+                app_token,
+                user_key,
+            } => {
+                ui.label("App token");
+                TextEdit::singleline(app_token).password(true).show(ui);
+                ui.end_row();
+
+                ui.label("User key");
+                ui.text_edit_singleline(user_key);
+                ui.end_row();
+            }
+            notification::Provider::Telegram { bot_token, chat_id } => {
+                ui.label("Bot token");
+                TextEdit::singleline(bot_token).password(true).show(ui);
+                ui.end_row();
+
+                ui.label("Chat ID");
+                ui.text_edit_singleline(chat_id);
+                ui.end_row();
+            }
+        }
+    });
+
+    remove
+}
+
+fn optional_text_edit(ui: &mut egui::Ui, value: &mut Option<String>, label: &str, password: bool) {
+    ui.label(label);
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut value.is_some(), "").changed() {
+            if value.is_none() {
+                *value = Some(String::new());
+            } else {
+                *value = None;
+            }
+        }
+        if let Some(value) = value {
+            if password {
+                TextEdit::singleline(value).password(true).show(ui);
+            } else {
+                ui.text_edit_singleline(value);
+            }
+        }
+    });
+    ui.end_row();
+}
+
+/// Lets a password be moved out of the config into the OS keyring, and back.
+fn keyring_ui(
+    ui: &mut egui::Ui,
+    password: &mut Option<String>,
+    entry: &mut Option<String>,
+    new_entry: String,
+) {
+    ui.label("Keyring");
+    ui.horizontal(|ui| match entry.clone() {
+        Some(name) => {
+            ui.label(&name);
+
+            if ui.button("Remove from keyring").clicked() {
+                if let Err(e) = config::delete_from_keyring(&name) {
+                    error!("Error removing {} from keyring: {}", name, e);
+                }
+                *entry = None;
+            }
+        }
+        None => {
+            if ui
+                .add_enabled(password.is_some(), egui::Button::new("Move to keyring"))
+                .clicked()
+            {
+                match config::store_in_keyring(&new_entry, password.as_deref().unwrap_or_default())
+                {
+                    Ok(()) => {
+                        *password = None;
+                        *entry = Some(new_entry);
+                    }
+                    Err(e) => error!("Error storing {} in keyring: {}", new_entry, e),
+                }
+            }
+        }
+    });
+    ui.end_row();
+}
+
+/// Sizes of what's drawn over the frame. They follow the zoom, within limits
+/// that keep them visible and easy to grab.
+#[derive(Clone, Copy)]
+struct OverlayStyle {
+    /// Screen pixels per frame pixel.
+    zoom: f32,
+    guide: Stroke,
+    outline: Stroke,
+    handle: f32,
+    indicator: f32,
+}
+
+impl OverlayStyle {
+    fn new(zoom: f32) -> Self {
+        Self {
+            zoom,
+            guide: Stroke::new((zoom * 0.5).clamp(0.2, 2.), Color32::WHITE),
+            outline: Stroke::new(zoom.clamp(0.5, 2.), Color32::WHITE),
+            handle: (zoom * 2.).clamp(2., 8.),
+            indicator: (zoom * 2.5).clamp(2., 8.),
+        }
+    }
+}
+
+/// How long each stage of sampling took, in the top left of the frame.
+fn timings_overlay(
+    painter: &egui::Painter,
+    rect: Rect,
+    timings: backend::Timings,
+    frame_interval: Option<Duration>,
+) {
+    let ms = |time: Duration| format!("{:>7.1} ms", time.as_secs_f64() * 1000.);
+    let fps = frame_interval
+        .filter(|interval| !interval.is_zero())
+        .map_or("-".to_owned(), |interval| {
+            format!("{:.2}", 1. / interval.as_secs_f64())
+        });
+
+    let text = format!(
+        "fps     {:>10}\nfetch   {}\ndecode  {}\nsample  {}\npublish {}",
+        fps,
+        ms(timings.fetch),
+        ms(timings.decode),
+        ms(timings.sample),
+        ms(timings.publish),
+    );
+
+    let galley = painter.layout_no_wrap(text, egui::FontId::monospace(12.), Color32::WHITE);
+    let pos = rect.left_top() + vec2(8., 8.);
+    painter.rect_filled(
+        Rect::from_min_size(pos, galley.size()).expand(4.),
+        2.,
+        Color32::from_black_alpha(160),
+    );
+    painter.galley(pos, galley, Color32::WHITE);
+}
+
+/// Draws a line between each of the frame's pixels, clipped to what's
+/// visible.
+fn pixel_grid(painter: &egui::Painter, rect: Rect, [width, height]: [usize; 2]) {
+    let stroke = Stroke::new(1., Color32::from_black_alpha(64));
+    let visible = rect.intersect(painter.clip_rect());
+
+    for x in 0..=width {
+        let left = rect.left() + rect.width() * x as f32 / width as f32;
+        if (visible.left()..=visible.right()).contains(&left) {
+            painter.vline(left, visible.y_range(), stroke);
+        }
+    }
+
+    for y in 0..=height {
+        let top = rect.top() + rect.height() * y as f32 / height as f32;
+        if (visible.top()..=visible.bottom()).contains(&top) {
+            painter.hline(visible.x_range(), top, stroke);
+        }
+    }
+}
+
+/// The configured shortcut as egui's, or `None` if egui has no such key.
+fn keyboard_shortcut(shortcut: &config::Shortcut) -> Option<KeyboardShortcut> {
+    let key = Key::ALL
+        .iter()
+        .find(|key| format!("{:?}", key) == shortcut.logical_key)?;
+    let config::ShortcutModifiers {
+        alt,
+        ctrl,
+        shift,
+        mac_cmd,
+        command,
+    } = shortcut.modifiers;
+
+    Some(KeyboardShortcut::new(
+        Modifiers {
+            alt,
+            ctrl,
+            shift,
+            mac_cmd,
+            command,
+        },
+        *key,
+    ))
+}
+
+fn config_shortcut(shortcut: KeyboardShortcut) -> config::Shortcut {
+    let Modifiers {
+        alt,
+        ctrl,
+        shift,
+        mac_cmd,
+        command,
+    } = shortcut.modifiers;
+
+    config::Shortcut::new(
+        config::ShortcutModifiers {
+            alt,
+            ctrl,
+            shift,
+            mac_cmd,
+            command,
+        },
+        &format!("{:?}", shortcut.logical_key),
+    )
+}
+
+/// The largest rect with the aspect ratio of `size` centered in `outer`.
+fn fit_rect(outer: Rect, size: Vec2) -> Rect {
+    let scale = (outer.width() / size.x).min(outer.height() / size.y);
+    Rect::from_center_size(outer.center(), size * scale)
+}
+
+/// Where a normalized position in the frame is on screen, when the frame is
+/// drawn in `rect`.
+fn map_pos(normalized: Pos2, rect: Rect) -> Pos2 {
+    pos2(
+        rect.left() + rect.width() * normalized.x,
+        rect.top() + rect.height() * normalized.y,
+    )
+}
+
+/// Whether the pointer is over a handle drawn at `center`, with some slack
+/// so small handles are easy to grab.
+fn handle_hovered(response: &egui::Response, center: Pos2, handle: f32) -> bool {
+    response
+        .hover_pos()
+        .is_some_and(|pointer| (pointer - center).length() <= handle * 2.)
+}
+
+/// Draws a single seven-segment digit, with each segment filled with its
+/// sampled luminance and outlined according to whether it's considered lit.
+fn segment_schematic(ui: &mut egui::Ui, samples: &[f32], threshold: f32) {
+    let (response, painter) = ui.allocate_painter(vec2(24., 40.), Sense::hover());
+    let rect = response.rect.shrink(2.);
+
+    let (l, r) = (rect.left(), rect.right());
+    let (t, m, b) = (rect.top(), rect.center().y, rect.bottom());
+
+    //  aa
+    // f  b
+    //  gg
+    // e  c
+    //  dd
+
+    let segments = [
+        [pos2(l, t), pos2(r, t)],
+        [pos2(r, t), pos2(r, m)],
+        [pos2(r, m), pos2(r, b)],
+        [pos2(l, b), pos2(r, b)],
+        [pos2(l, m), pos2(l, b)],
+        [pos2(l, t), pos2(l, m)],
+        [pos2(l, m), pos2(r, m)],
+    ];
+
+    for (segment, &sample) in segments.into_iter().zip(samples) {
+        let lit = sample > threshold;
+
+        painter.line_segment(
+            segment,
+            Stroke::new(
+                6.,
+                if lit {
+                    Color32::from_rgb(255, 160, 0)
+                } else {
+                    Color32::from_gray(40)
+                },
+            ),
+        );
+        painter.line_segment(
+            segment,
+            Stroke::new(3., Color32::from_gray((sample * 255.) as u8)),
+        );
+    }
+
+    response.on_hover_ui(|ui| {
+        Grid::new("segment_values").num_columns(2).show(ui, |ui| {
+            for (name, sample) in ["a", "b", "c", "d", "e", "f", "g"].iter().zip(samples) {
+                ui.label(*name);
+                ui.label(format!("{sample:.3}"));
+                ui.end_row();
+            }
+        });
+    });
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+#[cfg(feature = "tui")]
+use crate::tui;
+use crate::{
+    backend::{Backend, Command, Update},
+    cli::Args,
+    config::Config,
+    remote, systemd, validate,
+};
+
+/// Runs the capture, sample, decode and publish loop without a GUI.
+pub fn run(args: Args) {
+    let mut config = args.effective_config();
+
+    for problem in validate::validate(&config) {
+        warn!("Config problem: {}", problem);
+    }
+
+    let mut backend = Backend::start(
+        config.clone(),
+        Duration::from_secs(args.refresh_secs),
+        // Only the API shows frames.
+        args.http.is_some(),
+        || {},
+    );
+    backend.serve_api(&args);
+    #[cfg(target_os = "linux")]
+    backend.serve_dbus(&args);
+
+    let shutdown = backend.shutdown_handle();
+    if let Err(e) = ctrlc::set_handler(move || {
+        info!("Shutting down");
+        systemd::notify_stopping();
+        shutdown();
+    }) {
+        error!("Error installing signal handler: {}", e);
+    }
+
+    #[cfg(feature = "tui")]
+    let tui = args
+        .tui
+        .then(|| tui::Tui::start(backend.status(), backend.shutdown_handle()))
+        .and_then(|tui| {
+            tui.map_err(|e| error!("Error starting terminal UI: {}", e))
+                .ok()
+        });
+    #[cfg(not(feature = "tui"))]
+    if args.tui {
+        warn!("Built without the terminal UI, running headless");
+    }
+
+    systemd::notify_ready();
+
+    while let Some(update) = backend.recv() {
+        match update {
+            // Only ping the watchdog while frames keep coming in, so that
+            // systemd restarts us if the sampling loop gets stuck.
+            Update::Frame(..) => systemd::ping_watchdog(),
+            Update::Sampled(..) => {}
+            Update::MqttConnected => publish_config_state(&backend, &config),
+            Update::Message { topic, payload } => {
+                if remote::set_topic(&config).as_ref() == Some(&topic) {
+                    apply_remote_config(&backend, &mut config, &payload);
+                }
+            }
+            Update::ConfigChange(payload) => apply_remote_config(&backend, &mut config, &payload),
+            Update::Shift(..)
+            | Update::Lighting(..)
+            | Update::Ambient(..)
+            | Update::Vibration(..)
+            | Update::Sound(..)
+            | Update::Door(..)
+            | Update::FrameTime(..)
+            | Update::Paused { .. }
+            | Update::Alarm(_)
+            | Update::Timings(..) => {}
+            Update::Error(e) => error!("{}", e),
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    drop(tui);
+    backend.shutdown();
+}
+
+fn apply_remote_config(backend: &Backend, config: &mut Config, payload: &[u8]) {
+    match remote::apply(config, payload) {
+        Ok(()) => {
+            info!("Applied remote config change");
+            backend.send(Command::Config(Box::new(config.clone())));
+            publish_config_state(backend, config);
+        }
+        Err(e) => error!("Error applying remote config change: {}", e),
+    }
+}
+
+fn publish_config_state(backend: &Backend, config: &Config) {
+    if !config.mqtt.remote_config {
+        return;
+    }
+
+    if let Some((topic, payload)) = remote::state_message(config) {
+        backend.send(Command::Message {
+            topic,
+            payload,
+            retain: true,
+        });
+    }
+}
@@ -0,0 +1,63 @@
+//! Writing decoded values to InfluxDB v2, as points in the `laundry_machine`
+//! measurement tagged with the profile and marker names.
+
+use std::{error::Error, time::Duration};
+
+use serde_json::Value;
+
+use crate::config::{InfluxConfig, Profile};
+
+pub fn write(
+    influxdb: &InfluxConfig,
+    profile: &Profile,
+    values: &[Value],
+    indices: &[usize],
+) -> Result<(), Box<dyn Error>> {
+    let lines = laundry_machine_core::named_values(&profile.markers, values)
+        .into_iter()
+        .filter(|(idx, ..)| indices.contains(idx))
+        .filter_map(|(_, name, value)| {
+            let value = match value {
+                Value::Bool(value) => value.to_string(),
+                Value::Number(value) if value.is_f64() => value.to_string(),
+                Value::Number(value) => format!("{}i", value),
+                _ => return None,
+            };
+
+            Some(format!(
+                "laundry_machine,profile={},marker={} value={}",
+                escape_tag(&profile.name),
+                escape_tag(name),
+                value
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let mut request = ureq::post(&format!(
+        "{}/api/v2/write",
+        influxdb.url.trim_end_matches('/')
+    ))
+    .query("org", &influxdb.org)
+    .query("bucket", &influxdb.bucket)
+    .query("precision", "s")
+    .timeout(Duration::from_secs(10));
+
+    if let Some(token) = influxdb.token() {
+        request = request.set("Authorization", &format!("Token {}", token));
+    }
+
+    request.send_string(&lines.join("\n"))?;
+    Ok(())
+}
+
+fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
@@ -0,0 +1,249 @@
+//! Switching to other thresholds for other lighting, by time of day or by how
+//! bright it is around the markers.
+
+use chrono::{Local, NaiveTime};
+#[cfg(feature = "gui")]
+use egui::{DragValue, Slider, TextEdit, Ui, Widget};
+use epaint::ColorImage;
+use serde::{Deserialize, Serialize};
+
+use laundry_machine_core::{stats, Marker, MarkerType};
+
+use crate::recording::crop_rect;
+
+/// A threshold for some lighting, like at night or with the overhead light
+/// on.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lighting {
+    pub name: String,
+    pub luminance_threshold: f32,
+    /// Local time of day from which this applies, as `HH:MM`. Wraps around
+    /// midnight if it's after `until`.
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub until: Option<String>,
+    /// Range of the average luminance around the markers in which this
+    /// applies.
+    #[serde(default)]
+    pub min_ambient: Option<f32>,
+    #[serde(default)]
+    pub max_ambient: Option<f32>,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            name: "night".to_owned(),
+            luminance_threshold: 0.5,
+            from: Some("20:00".to_owned()),
+            until: Some("07:00".to_owned()),
+            min_ambient: None,
+            max_ambient: None,
+        }
+    }
+}
+
+impl Lighting {
+    pub fn applies(&self, time: NaiveTime, ambient: f32) -> bool {
+        let from = self.from.as_deref().and_then(parse_time);
+        let until = self.until.as_deref().and_then(parse_time);
+
+        in_window(time, from, until)
+            && !self.min_ambient.is_some_and(|min| ambient < min)
+            && !self.max_ambient.is_some_and(|max| ambient > max)
+    }
+}
+
+/// A point marker that only sees the ambient light, to adjust all thresholds
+/// by as the light changes.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct AmbientReference {
+    /// Name of the point marker.
+    pub marker: String,
+    /// The marker's luminance in the lighting the thresholds were set in.
+    pub baseline: f32,
+    #[serde(default)]
+    pub compensation: Compensation,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compensation {
+    /// Move thresholds up and down by as much as the ambient light changes.
+    #[default]
+    Offset,
+    /// Scale thresholds by how many times brighter the ambient light is.
+    Scale,
+}
+
+impl AmbientReference {
+    /// Index of the point marker in `markers`, if it's there.
+    pub fn find(&self, markers: &[Marker]) -> Option<usize> {
+        markers.iter().position(|marker| {
+            marker.name == self.marker && matches!(marker.ty, MarkerType::Point { .. })
+        })
+    }
+
+    /// A threshold set for the baseline, adjusted to the current ambient
+    /// luminance.
+    pub fn adjust(&self, threshold: f32, ambient: f32) -> f32 {
+        let adjusted = match self.compensation {
+            Compensation::Offset => threshold + ambient - self.baseline,
+            Compensation::Scale => threshold * ambient / self.baseline.max(0.001),
+        };
+
+        adjusted.clamp(0., 1.)
+    }
+}
+
+pub fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M").ok()
+}
+
+/// Whether `time` is from `from` until `until`, wrapping around midnight if
+/// `from` is after `until`. A missing end leaves that side open.
+pub fn in_window(time: NaiveTime, from: Option<NaiveTime>, until: Option<NaiveTime>) -> bool {
+    match (from, until) {
+        (Some(from), Some(until)) if from <= until => (from..until).contains(&time),
+        (Some(from), Some(until)) => time >= from || time < until,
+        (Some(from), None) => time >= from,
+        (None, Some(until)) => time < until,
+        (None, None) => true,
+    }
+}
+
+/// The first of `lightings` that applies to the frame right now, and the
+/// ambient luminance it was picked by.
+pub fn current<'a>(
+    lightings: &'a [Lighting],
+    image: &ColorImage,
+    markers: &[Marker],
+) -> (Option<&'a Lighting>, f32) {
+    let ambient = ambient(image, markers);
+    let time = Local::now().time();
+
+    (
+        lightings
+            .iter()
+            .find(|lighting| lighting.applies(time, ambient)),
+        ambient,
+    )
+}
+
+/// Average luminance of the regions around the markers, which is all that's
+/// fetched of cropped frames.
+pub fn ambient(image: &ColorImage, markers: &[Marker]) -> f32 {
+    let [width, height] = image.size;
+    let (mut sum, mut count) = (0., 0);
+    let mut line = vec![];
+
+    for marker in markers {
+        let Some((x, y, w, h)) = crop_rect(marker, width as u32, height as u32) else {
+            continue;
+        };
+        let (x, w) = (x as usize, w as usize);
+
+        for py in y as usize..(y + h) as usize {
+            stats::luminances(&image.pixels[py * width + x..py * width + x + w], &mut line);
+            sum += stats::sum(&line);
+            count += w;
+        }
+    }
+
+    if count == 0 {
+        0.
+    } else {
+        sum / count as f32
+    }
+}
+
+/// Editor for a profile's lightings.
+#[cfg(feature = "gui")]
+pub fn edit(ui: &mut Ui, lightings: &mut Vec<Lighting>) {
+    let mut remove = None;
+
+    for (idx, lighting) in lightings.iter_mut().enumerate() {
+        ui.push_id(idx, |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut lighting.name);
+                Slider::new(&mut lighting.luminance_threshold, 0.001..=0.999).ui(ui);
+
+                if ui.button("Remove").clicked() {
+                    remove = Some(idx);
+                }
+            });
+
+            ui.horizontal(|ui| {
+                optional(
+                    ui,
+                    "From",
+                    &mut lighting.from,
+                    "20:00".to_owned(),
+                    |ui, time| {
+                        TextEdit::singleline(time).desired_width(48.).ui(ui);
+                    },
+                );
+                optional(
+                    ui,
+                    "until",
+                    &mut lighting.until,
+                    "07:00".to_owned(),
+                    |ui, time| {
+                        TextEdit::singleline(time).desired_width(48.).ui(ui);
+                    },
+                );
+            });
+
+            ui.horizontal(|ui| {
+                optional(
+                    ui,
+                    "Ambient above",
+                    &mut lighting.min_ambient,
+                    0.5,
+                    |ui, min| {
+                        DragValue::new(min)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0)
+                            .ui(ui);
+                    },
+                );
+                optional(ui, "below", &mut lighting.max_ambient, 0.5, |ui, max| {
+                    DragValue::new(max)
+                        .speed(0.01)
+                        .clamp_range(0.0..=1.0)
+                        .ui(ui);
+                });
+            });
+
+            ui.separator();
+        });
+    }
+
+    if let Some(idx) = remove {
+        lightings.remove(idx);
+    }
+
+    if ui.button("Add lighting").clicked() {
+        lightings.push(Default::default());
+    }
+}
+
+/// A checkbox for whether a setting is used, and its editor if it is.
+#[cfg(feature = "gui")]
+fn optional<T>(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Option<T>,
+    default: T,
+    edit: impl FnOnce(&mut Ui, &mut T),
+) {
+    let mut enabled = value.is_some();
+    if ui.checkbox(&mut enabled, label).changed() {
+        *value = enabled.then_some(default);
+    }
+
+    if let Some(value) = value {
+        edit(ui, value);
+    }
+}
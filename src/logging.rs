@@ -0,0 +1,142 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write},
+    io,
+    sync::{Mutex, OnceLock},
+};
+
+use tracing::{field::Field, Event, Level, Subscriber};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    field::Visit, layer::Context, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+use crate::{cli::Args, config::LoggingConfig};
+
+/// Sets up logging to stderr and, if configured, to daily rotated files.
+///
+/// The filter comes from `RUST_LOG`, then `--log-level`, then the config.
+/// The returned guard flushes the log file when dropped.
+pub fn init(args: &Args, config: &LoggingConfig) -> Option<WorkerGuard> {
+    let level = args.log_level.as_deref().unwrap_or(&config.level);
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let json = args.log_json || config.json;
+
+    // The terminal UI shows the recent lines itself.
+    let stderr = (!args.tui || !cfg!(feature = "tui")).then(|| {
+        let stderr = tracing_subscriber::fmt::layer().with_writer(io::stderr);
+        if json {
+            stderr.json().boxed()
+        } else {
+            stderr.boxed()
+        }
+    });
+
+    let mut guard = None;
+
+    let file = config.directory.as_ref().and_then(|directory| {
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("laundry-machine-mqtt")
+            .filename_suffix("log")
+            .max_log_files(config.keep_files)
+            .build(directory);
+
+        let appender = match appender {
+            Ok(appender) => appender,
+            Err(e) => {
+                eprintln!("Error opening log file in {}: {}", directory.display(), e);
+                return None;
+            }
+        };
+
+        let (writer, file_guard) = tracing_appender::non_blocking(appender);
+        guard = Some(file_guard);
+
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false);
+
+        Some(if json {
+            layer.json().boxed()
+        } else {
+            layer.boxed()
+        })
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stderr)
+        .with(file)
+        .with(RecentLayer)
+        .init();
+
+    guard
+}
+
+/// How many lines the in-app log viewer keeps.
+const RECENT_LINES: usize = 1000;
+
+pub struct LogLine {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5} {}: {}", self.level, self.target, self.message)
+    }
+}
+
+fn recent_lines() -> &'static Mutex<VecDeque<LogLine>> {
+    static LINES: OnceLock<Mutex<VecDeque<LogLine>>> = OnceLock::new();
+    LINES.get_or_init(Default::default)
+}
+
+/// Calls `f` with the most recent log lines, oldest first.
+#[cfg(any(feature = "gui", feature = "tui"))]
+pub fn with_recent<T>(f: impl FnOnce(&VecDeque<LogLine>) -> T) -> T {
+    f(&recent_lines().lock().unwrap())
+}
+
+/// Keeps recent events in memory for the log viewer.
+struct RecentLayer;
+
+impl<S: Subscriber> Layer<S> for RecentLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+
+        let mut lines = recent_lines().lock().unwrap();
+        if lines.len() == RECENT_LINES {
+            lines.pop_front();
+        }
+
+        lines.push_back(LogLine {
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_owned(),
+            message: visitor.0,
+        });
+    }
+}
+
+/// Formats the message followed by any other fields as `name=value`.
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+
+        if field.name() == "message" {
+            write!(self.0, "{:?}", value).ok();
+        } else {
+            write!(self.0, "{}={:?}", field.name(), value).ok();
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! What kind of machine a profile watches, and when its cycles really start
+//! and finish for that kind.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    config::Profile,
+    event::{Countdown, EventKind},
+};
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Machine {
+    /// Finished as soon as the time runs out.
+    #[default]
+    Generic,
+    /// Keeps the door locked for a while after the time runs out.
+    Washer {
+        /// A point marker on the door lock light, which finishes the cycle
+        /// when it goes off.
+        #[serde(default)]
+        door_lock: Option<String>,
+        /// Finishes the cycle this long after the time ran out, if there's
+        /// no door lock marker or it never goes off.
+        #[serde(default = "default_door_lock_secs")]
+        door_lock_secs: u64,
+    },
+    /// Tumbles every now and then after finishing to keep creases out, which
+    /// shouldn't look like another cycle. Only a long enough time remaining
+    /// starts one during that.
+    Dryer {
+        #[serde(default = "default_anti_wrinkle_mins")]
+        anti_wrinkle_mins: u64,
+    },
+}
+
+/// Longer than any anti-wrinkle tumble shows.
+const MIN_DRYER_CYCLE_SECS: u64 = 10 * 60;
+
+fn default_door_lock_secs() -> u64 {
+    180
+}
+
+fn default_anti_wrinkle_mins() -> u64 {
+    120
+}
+
+impl Machine {
+    #[cfg(feature = "gui")]
+    pub const NAMES: [&'static str; 3] = ["Generic", "Washer", "Dryer"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Generic => "Generic",
+            Self::Washer { .. } => "Washer",
+            Self::Dryer { .. } => "Dryer",
+        }
+    }
+
+    /// The machine of the given [`Self::name`], with default settings.
+    #[cfg(feature = "gui")]
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "Washer" => Self::Washer {
+                door_lock: None,
+                door_lock_secs: default_door_lock_secs(),
+            },
+            "Dryer" => Self::Dryer {
+                anti_wrinkle_mins: default_anti_wrinkle_mins(),
+            },
+            _ => Self::Generic,
+        }
+    }
+}
+
+enum State {
+    Idle,
+    /// A washer's time ran out, but its door is still locked.
+    Unlocking {
+        since: Instant,
+    },
+    /// A dryer finished, and may still tumble until then.
+    AntiWrinkle {
+        until: Instant,
+    },
+}
+
+/// Follows a single profile's cycles.
+pub struct Tracker {
+    state: State,
+    countdown: Countdown,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self {
+            state: State::Idle,
+            countdown: Countdown::default(),
+        }
+    }
+}
+
+impl Tracker {
+    /// Takes in a profile's values after a sample, returning whether a cycle
+    /// started or finished for the profile's kind of machine.
+    pub fn update(&mut self, profile: &Profile, values: &[Value]) -> Option<EventKind> {
+        let time_remaining =
+            laundry_machine_core::time_remaining(&profile.markers, values, &profile.time_remaining);
+        let transition = self.countdown.update(time_remaining);
+        let now = Instant::now();
+
+        match (&profile.machine, &self.state) {
+            (Machine::Generic, _) => transition,
+
+            (Machine::Washer { .. }, State::Unlocking { .. })
+                if transition == Some(EventKind::CycleStarted) =>
+            {
+                self.state = State::Idle;
+                transition
+            }
+            (
+                Machine::Washer {
+                    door_lock,
+                    door_lock_secs,
+                },
+                State::Unlocking { since },
+            ) => {
+                let unlocked = door_lock
+                    .as_ref()
+                    .and_then(|name| profile.markers.iter().position(|m| &m.name == name))
+                    .is_some_and(|idx| values.get(idx) == Some(&Value::Bool(false)));
+
+                if unlocked || now - *since >= Duration::from_secs(*door_lock_secs) {
+                    self.state = State::Idle;
+                    Some(EventKind::CycleFinished)
+                } else {
+                    None
+                }
+            }
+            (Machine::Washer { .. }, _) => {
+                if transition == Some(EventKind::CycleFinished) {
+                    self.state = State::Unlocking { since: now };
+                    None
+                } else {
+                    transition
+                }
+            }
+
+            (Machine::Dryer { .. }, State::AntiWrinkle { until }) if now < *until => {
+                if transition == Some(EventKind::CycleStarted)
+                    && time_remaining >= Some(MIN_DRYER_CYCLE_SECS)
+                {
+                    self.state = State::Idle;
+                    transition
+                } else {
+                    None
+                }
+            }
+            (Machine::Dryer { anti_wrinkle_mins }, _) => {
+                self.state = State::Idle;
+                if transition == Some(EventKind::CycleFinished) {
+                    self.state = State::AntiWrinkle {
+                        until: now + Duration::from_secs(anti_wrinkle_mins * 60),
+                    };
+                }
+                transition
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use laundry_machine_core::{Marker, MarkerType};
+    use serde_json::json;
+
+    use super::*;
+
+    fn point(name: &str) -> Marker {
+        let mut marker = Marker::new(MarkerType::Point {
+            pos: epaint::pos2(0.5, 0.5),
+            size: 1.,
+        });
+        marker.name = name.to_owned();
+        marker
+    }
+
+    fn profile(machine: Machine) -> Profile {
+        Profile {
+            markers: vec![point("hour"), point("minute"), point("lock")],
+            machine,
+            ..Default::default()
+        }
+    }
+
+    /// The values for a display showing the given minutes, and the door lock
+    /// light.
+    fn values(minutes: Option<u64>, locked: bool) -> Vec<Value> {
+        match minutes {
+            Some(minutes) => vec![json!(minutes / 60), json!(minutes % 60), json!(locked)],
+            None => vec![Value::Null, Value::Null, json!(locked)],
+        }
+    }
+
+    /// The transitions after each of the minutes shown, starting from a
+    /// display showing zero.
+    fn run(
+        tracker: &mut Tracker,
+        profile: &Profile,
+        minutes: &[(Option<u64>, bool)],
+    ) -> Vec<Option<EventKind>> {
+        tracker.update(profile, &values(Some(0), false));
+        minutes
+            .iter()
+            .map(|&(minutes, locked)| tracker.update(profile, &values(minutes, locked)))
+            .collect()
+    }
+
+    fn washer(door_lock: Option<&str>, door_lock_secs: u64) -> Profile {
+        profile(Machine::Washer {
+            door_lock: door_lock.map(str::to_owned),
+            door_lock_secs,
+        })
+    }
+
+    #[test]
+    fn generic_finishes_when_time_runs_out() {
+        let profile = profile(Machine::Generic);
+
+        assert_eq!(
+            run(
+                &mut Tracker::default(),
+                &profile,
+                &[(Some(90), false), (Some(1), false), (Some(0), false)],
+            ),
+            [
+                Some(EventKind::CycleStarted),
+                None,
+                Some(EventKind::CycleFinished)
+            ],
+        );
+    }
+
+    #[test]
+    fn washer_waits_for_the_door_lock() {
+        let profile = washer(Some("lock"), 3600);
+
+        assert_eq!(
+            run(
+                &mut Tracker::default(),
+                &profile,
+                &[
+                    (Some(1), true),
+                    (Some(0), true),
+                    (Some(0), true),
+                    (Some(0), false),
+                    (Some(0), false),
+                ],
+            ),
+            [
+                Some(EventKind::CycleStarted),
+                None,
+                None,
+                Some(EventKind::CycleFinished),
+                None,
+            ],
+        );
+    }
+
+    #[test]
+    fn washer_without_door_lock_waits_its_time() {
+        let profile = washer(None, 3600);
+        assert_eq!(
+            run(
+                &mut Tracker::default(),
+                &profile,
+                &[(Some(1), true), (Some(0), true)]
+            ),
+            [Some(EventKind::CycleStarted), None],
+        );
+
+        let profile = washer(None, 0);
+        assert_eq!(
+            run(
+                &mut Tracker::default(),
+                &profile,
+                &[(Some(1), true), (Some(0), true), (Some(0), true)],
+            ),
+            [
+                Some(EventKind::CycleStarted),
+                None,
+                Some(EventKind::CycleFinished)
+            ],
+        );
+    }
+
+    #[test]
+    fn washer_starts_again_while_unlocking() {
+        let profile = washer(Some("lock"), 3600);
+
+        assert_eq!(
+            run(
+                &mut Tracker::default(),
+                &profile,
+                &[(Some(1), true), (Some(0), true), (Some(45), true)],
+            ),
+            [
+                Some(EventKind::CycleStarted),
+                None,
+                Some(EventKind::CycleStarted)
+            ],
+        );
+    }
+
+    #[test]
+    fn dryer_ignores_anti_wrinkle_tumbles() {
+        let profile = profile(Machine::Dryer {
+            anti_wrinkle_mins: 120,
+        });
+
+        assert_eq!(
+            run(
+                &mut Tracker::default(),
+                &profile,
+                &[
+                    (Some(1), false),
+                    (Some(0), false),
+                    (Some(5), false),
+                    (Some(0), false),
+                    (Some(90), false),
+                ],
+            ),
+            [
+                Some(EventKind::CycleStarted),
+                Some(EventKind::CycleFinished),
+                None,
+                None,
+                Some(EventKind::CycleStarted),
+            ],
+        );
+    }
+
+    #[test]
+    fn dryer_starts_normally_after_anti_wrinkle() {
+        let profile = profile(Machine::Dryer {
+            anti_wrinkle_mins: 0,
+        });
+
+        assert_eq!(
+            run(
+                &mut Tracker::default(),
+                &profile,
+                &[(Some(1), false), (Some(0), false), (Some(5), false)],
+            ),
+            [
+                Some(EventKind::CycleStarted),
+                Some(EventKind::CycleFinished),
+                Some(EventKind::CycleStarted),
+            ],
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Push notifications sent directly to ntfy, Pushover or Telegram, for events
+//! matching a rule, optionally only to the people who are home.
+
+use std::{error::Error, time::Duration};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    config::Profile,
+    event::{self, Event, EventKind},
+    lighting,
+};
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Notifications {
+    #[serde(default)]
+    pub providers: Vec<Provider>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Notified along with `providers`, except while they're away for
+    /// profiles with [`Presence`].
+    #[serde(default)]
+    pub people: Vec<Person>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Person {
+    /// As it appears in presence topics.
+    pub name: String,
+    #[serde(default)]
+    pub providers: Vec<Provider>,
+}
+
+/// Where to find out who's home, so that only they're notified.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Presence {
+    /// An MQTT topic with a `+` for the person's name, such as
+    /// `home/person/+/state`.
+    pub topic: String,
+    /// The payload meaning the person is home. Anything else means they're
+    /// away.
+    #[serde(default = "default_home_payload")]
+    pub home_payload: String,
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self {
+            topic: "home/person/+/state".to_owned(),
+            home_payload: default_home_payload(),
+        }
+    }
+}
+
+fn default_home_payload() -> String {
+    "home".to_owned()
+}
+
+impl Presence {
+    /// The name of the person a message on `topic` is about, if it's a
+    /// presence topic.
+    pub fn person<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        let mut levels = topic.split('/');
+        let mut person = None;
+
+        for pattern in self.topic.split('/') {
+            let level = levels.next()?;
+            match pattern {
+                "+" => person = Some(level),
+                pattern if pattern == level => {}
+                _ => return None,
+            }
+        }
+
+        levels.next().is_none().then_some(person).flatten()
+    }
+}
+
+/// A time of day during which notifications are held back.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// Local time as `HH:MM`, wrapping around midnight if it's after
+    /// `until`.
+    pub from: String,
+    pub until: String,
+    /// Send the notifications that were held back once quiet hours end,
+    /// rather than dropping them.
+    #[serde(default)]
+    pub defer: bool,
+    /// Also skip webhooks and the alarm sound, which are never deferred.
+    #[serde(default)]
+    pub non_essential: bool,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self {
+            from: "22:00".to_owned(),
+            until: "07:00".to_owned(),
+            defer: true,
+            non_essential: false,
+        }
+    }
+}
+
+impl QuietHours {
+    /// Whether it's quiet right now. Never, if either time is invalid.
+    pub fn is_quiet(&self) -> bool {
+        match (
+            lighting::parse_time(&self.from),
+            lighting::parse_time(&self.until),
+        ) {
+            (Some(from), Some(until)) => {
+                lighting::in_window(Local::now().time(), Some(from), Some(until))
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Provider {
+    Ntfy {
+        /// Such as `https://ntfy.sh`.
+        server: String,
+        topic: String,
+        token: Option<String>,
+    },
+    Pushover {
+        app_token: String,
+        user_key: String,
+    },
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+    },
+}
+
+/// When to notify, and with what message.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub event: EventKind,
+    /// The marker to watch for value changes. Value change rules only fire
+    /// when the marker shows something, so that an error indicator turning
+    /// off or a display going blank doesn't notify.
+    #[serde(default)]
+    pub marker: Option<String>,
+    /// The message, with the same `{{name}}` variables as webhook bodies.
+    pub message: String,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            event: EventKind::CycleFinished,
+            marker: None,
+            message: "The laundry is done".to_owned(),
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, event: &Event) -> bool {
+        if self.event != event.kind {
+            return false;
+        }
+
+        match self.event {
+            EventKind::ValueChanged => {
+                event.marker.is_some()
+                    && event.marker == self.marker
+                    && !matches!(event.value, Value::Null | Value::Bool(false))
+            }
+            EventKind::CycleStarted | EventKind::CycleFinished | EventKind::Reminder => true,
+        }
+    }
+
+    pub fn message(&self, profile: &Profile, event: &Event, time_remaining: Option<u64>) -> String {
+        event::fill_text(&self.message, &event.variables(profile, time_remaining))
+    }
+}
+
+impl Provider {
+    #[cfg(feature = "gui")]
+    pub const NAMES: [&'static str; 3] = ["ntfy", "Pushover", "Telegram"];
+
+    #[cfg(feature = "gui")]
+    pub fn new(name: &str) -> Self {
+        match name {
+            "Pushover" => Self::Pushover {
+                app_token: String::new(),
+                user_key: String::new(),
+            },
+            "Telegram" => Self::Telegram {
+                bot_token: String::new(),
+                chat_id: String::new(),
+            },
+            _ => Self::Ntfy {
+                server: "https://ntfy.sh".to_owned(),
+                topic: String::new(),
+                token: None,
+            },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ntfy { .. } => "ntfy",
+            Self::Pushover { .. } => "Pushover",
+            Self::Telegram { .. } => "Telegram",
+        }
+    }
+
+    /// Sends a notification. Blocks until done.
+    pub fn send(&self, title: &str, message: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Ntfy {
+                server,
+                topic,
+                token,
+            } => {
+                let mut request =
+                    ureq::post(&format!("{}/{}", server.trim_end_matches('/'), topic))
+                        .timeout(Duration::from_secs(10))
+                        .set("Title", title);
+
+                if let Some(token) = token {
+                    request = request.set("Authorization", &format!("Bearer {}", token));
+                }
+
+                request.send_string(message)?;
+            }
+            Self::Pushover {
+                app_token,
+                user_key,
+            } => {
+                ureq::post("https://api.pushover.net/1/messages.json")
+                    .timeout(Duration::from_secs(10))
+                    .send_form(&[
+                        ("token", app_token),
+                        ("user", user_key),
+                        ("title", title),
+                        ("message", message),
+                    ])?;
+            }
+            Self::Telegram { bot_token, chat_id } => {
+                ureq::post(&format!(
+                    "https://api.telegram.org/bot{}/sendMessage",
+                    bot_token
+                ))
+                .timeout(Duration::from_secs(10))
+                .send_form(&[("chat_id", chat_id), ("text", message)])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes tokens and keys, for sharing the config.
+    pub fn clear_secrets(&mut self) {
+        match self {
+            Self::Ntfy { token, .. } => *token = None,
+            Self::Pushover { app_token, .. } => app_token.clear(),
+            Self::Telegram { bot_token, .. } => bot_token.clear(),
+        }
+    }
+
+    /// Takes the secrets of `other` if this provider has none, such as after
+    /// importing a config exported without them.
+    pub fn keep_secrets(&mut self, other: &mut Provider) {
+        match (self, other) {
+            (Self::Ntfy { token, .. }, Self::Ntfy { token: other, .. }) if token.is_none() => {
+                *token = other.take();
+            }
+            (
+                Self::Pushover { app_token, .. },
+                Self::Pushover {
+                    app_token: other, ..
+                },
+            ) if app_token.is_empty() => {
+                *app_token = std::mem::take(other);
+            }
+            (
+                Self::Telegram { bot_token, .. },
+                Self::Telegram {
+                    bot_token: other, ..
+                },
+            ) if bot_token.is_empty() => {
+                *bot_token = std::mem::take(other);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Subcommands that do a single thing and exit, for cron jobs, debugging and
+//! testing marker layouts.
+
+use std::{collections::BTreeMap, error::Error, fs, path::Path, time::Duration};
+
+use epaint::ColorImage;
+use laundry_machine_core::Marker;
+use rayon::prelude::*;
+use rumqttc::{AsyncClient, Event, Packet, QoS};
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::Args,
+    config::{Config, Profile},
+    influx, lighting, recording,
+    sampler::{self, decode_frame, fetch_frame},
+    validate,
+};
+
+pub fn sample_once(args: &Args) -> Result<(), Box<dyn Error>> {
+    let config = args.effective_config();
+    let image = fetch_frame(&config.profile().webcam)?;
+    print_values(&config, &image)
+}
+
+pub fn decode(args: &Args, path: &Path) -> Result<(), Box<dyn Error>> {
+    let config = args.effective_config();
+    let image = decode_frame(&fs::read(path)?)?;
+    print_values(&config, &image)
+}
+
+pub fn validate(args: &Args) -> Result<(), Box<dyn Error>> {
+    let config = args.effective_config();
+    let problems = validate::validate(&config);
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    match problems.len() {
+        0 => Ok(()),
+        n => Err(format!("found {} problems", n).into()),
+    }
+}
+
+pub fn publish(args: &Args) -> Result<(), Box<dyn Error>> {
+    let config = args.effective_config();
+    let image = fetch_frame(&config.profile().webcam)?;
+    let values = sample(config.profile(), &image);
+
+    let profile = config.profile();
+    // The ambient reference isn't published.
+    let ambient_reference = profile
+        .ambient_reference
+        .as_ref()
+        .and_then(|reference| reference.find(&profile.markers));
+    let indices = (0..values.len())
+        .filter(|&idx| Some(idx) != ambient_reference)
+        .collect::<Vec<_>>();
+    let messages = laundry_machine_core::messages(
+        &profile.topic_prefix,
+        &profile.markers,
+        &values,
+        &indices,
+        config.mqtt.payload_format,
+        &profile.time_remaining,
+    );
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async {
+        let (client, mut event_loop) =
+            AsyncClient::new(config.mqtt.options("laundry-machine-mqtt-oneshot"), 100);
+
+        for message in &messages {
+            client
+                .publish(
+                    &message.topic,
+                    QoS::AtLeastOnce,
+                    false,
+                    message.payload.clone(),
+                )
+                .await?;
+        }
+
+        // Drive the connection until the broker acknowledged everything.
+        let mut unacked = messages.len();
+        let acked = tokio::time::timeout(Duration::from_secs(10), async {
+            while unacked > 0 {
+                if let Event::Incoming(Packet::PubAck(_)) = event_loop.poll().await? {
+                    unacked -= 1;
+                }
+            }
+            Ok::<_, rumqttc::ConnectionError>(())
+        })
+        .await;
+
+        match acked {
+            Ok(result) => result?,
+            Err(_) => return Err("timed out waiting for the broker".into()),
+        }
+
+        for message in &messages {
+            println!("Published {}: {}", message.topic, message.payload);
+        }
+
+        client.disconnect().await.ok();
+        Ok::<_, Box<dyn Error>>(())
+    })?;
+
+    if let Some(influxdb) = &config.influxdb {
+        influx::write(influxdb, profile, &values, &indices)?;
+    }
+
+    Ok(())
+}
+
+pub fn healthcheck(args: &Args, max_age: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let addr = args
+        .http
+        .as_deref()
+        .ok_or("--http is needed to find the instance")?;
+
+    let mut url = format!("http://{}/healthz", addr);
+    if let Some(max_age) = max_age {
+        url += &format!("?max_age={}", max_age);
+    }
+
+    // Unhealthy responses are errors as well.
+    ureq::get(&url).timeout(Duration::from_secs(5)).call()?;
+    println!("ok");
+    Ok(())
+}
+
+/// Decodes each recorded frame under `dir` with the profile it was recorded
+/// for, or the active profile if there's no profile by that name.
+pub fn replay(args: &Args, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let config = args.effective_config();
+
+    // Marker name to (correct, total).
+    let mut results = BTreeMap::<String, (usize, usize)>::new();
+    let mut differences = 0;
+
+    for frame_dir in recording::find(dir)? {
+        let profile_name = frame_dir
+            .parent()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str());
+        let profile = config
+            .profiles
+            .iter()
+            .find(|profile| Some(profile.name.as_str()) == profile_name)
+            .unwrap_or(config.profile());
+
+        let labels = recording::load_labels(&frame_dir)?;
+        let image = decode_frame(&fs::read(frame_dir.join(recording::FRAME_FILE))?)?;
+        let values = sample(profile, &image);
+
+        for (marker, value) in profile.markers.iter().zip(values) {
+            let Some(label) = labels.get(&marker.name) else {
+                continue;
+            };
+
+            let (correct, total) = results.entry(marker.name.clone()).or_default();
+            *total += 1;
+
+            if &value == label.expected() {
+                *correct += 1;
+            } else {
+                differences += 1;
+                println!(
+                    "{} {}: expected {}, decoded {}",
+                    frame_dir.display(),
+                    marker.name,
+                    label.expected(),
+                    value
+                );
+            }
+        }
+    }
+
+    for (marker, (correct, total)) in &results {
+        println!(
+            "{}: {}/{} correct ({:.1}%)",
+            marker,
+            correct,
+            total,
+            *correct as f32 / *total as f32 * 100.
+        );
+    }
+
+    match differences {
+        0 => Ok(()),
+        n => Err(format!("{} values differ from their labels", n).into()),
+    }
+}
+
+/// Decoded values of all markers in a profile.
+fn sample(profile: &Profile, image: &ColorImage) -> Vec<Value> {
+    let threshold = lighting::current(&profile.lighting, image, &profile.markers)
+        .0
+        .map_or(profile.luminance_threshold, |lighting| {
+            lighting.luminance_threshold
+        });
+
+    let samples = |marker: &Marker| {
+        sampler::sample(
+            &marker.ty,
+            image,
+            profile.peak_search,
+            profile.interpolation,
+        )
+    };
+
+    let ambient = profile.ambient_reference.as_ref().and_then(|reference| {
+        let idx = reference.find(&profile.markers)?;
+        Some((reference, samples(&profile.markers[idx])[0]))
+    });
+
+    profile
+        .markers
+        .par_iter()
+        .map(|marker| {
+            let threshold = marker.threshold_or(threshold);
+            let threshold = match ambient {
+                Some((reference, ambient)) => reference.adjust(threshold, ambient),
+                None => threshold,
+            };
+            marker
+                .decode(&samples(marker), threshold, &profile.segment_patterns)
+                .0
+        })
+        .collect()
+}
+
+fn print_values(config: &Config, image: &ColorImage) -> Result<(), Box<dyn Error>> {
+    let values = config
+        .profile()
+        .markers
+        .iter()
+        .zip(sample(config.profile(), image))
+        .map(|(marker, value)| (marker.name.clone(), value))
+        .collect::<Map<_, _>>();
+
+    println!("{}", serde_json::to_string_pretty(&values)?);
+    Ok(())
+}
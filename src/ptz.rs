@@ -0,0 +1,180 @@
+//! Moving ONVIF PTZ cameras to a preset before sampling, for cameras that
+//! are pointed elsewhere the rest of the time.
+
+use std::{
+    error::Error,
+    fmt::Write,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tracing::warn;
+
+use crate::config::WebcamConfig;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PtzConfig {
+    /// The camera's ONVIF PTZ service, such as
+    /// `http://camera/onvif/ptz_service`.
+    pub url: String,
+    /// The ONVIF media profile to move.
+    pub profile_token: String,
+    /// The preset to move to before each sample.
+    pub preset: String,
+    /// The preset to move back to afterwards, if any.
+    #[serde(default)]
+    pub return_preset: Option<String>,
+    /// How long the camera takes to get to the preset and focus.
+    #[serde(default = "default_settle_secs")]
+    pub settle_secs: f32,
+}
+
+impl Default for PtzConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            profile_token: "Profile_1".to_owned(),
+            preset: "1".to_owned(),
+            return_preset: None,
+            settle_secs: default_settle_secs(),
+        }
+    }
+}
+
+fn default_settle_secs() -> f32 {
+    3.
+}
+
+/// Calls `f` with the webcam moved to its preset, if it has one, and moves
+/// it back afterwards if configured. Blocks while the camera moves.
+pub fn at_preset<T>(
+    webcam: &WebcamConfig,
+    f: impl FnOnce() -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let Some(ptz) = &webcam.ptz else {
+        return f();
+    };
+
+    let credentials = webcam.credentials();
+
+    goto_preset(ptz, &ptz.preset, credentials.as_ref())
+        .map_err(|e| format!("error moving to preset {}: {}", ptz.preset, e))?;
+    thread::sleep(Duration::from_secs_f32(ptz.settle_secs.max(0.)));
+
+    let result = f();
+
+    if let Some(preset) = &ptz.return_preset {
+        if let Err(e) = goto_preset(ptz, preset, credentials.as_ref()) {
+            warn!("Error moving back to preset {}: {}", preset, e);
+        }
+    }
+
+    result
+}
+
+fn goto_preset(
+    ptz: &PtzConfig,
+    preset: &str,
+    credentials: Option<&(String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    let header = credentials
+        .map(|(username, password)| security_header(username, password))
+        .unwrap_or_default();
+
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:tptz="http://www.onvif.org/ver20/ptz/wsdl">
+<s:Header>{header}</s:Header>
+<s:Body><tptz:GotoPreset><tptz:ProfileToken>{}</tptz:ProfileToken><tptz:PresetToken>{}</tptz:PresetToken></tptz:GotoPreset></s:Body>
+</s:Envelope>"#,
+        escape(&ptz.profile_token),
+        escape(preset),
+    );
+
+    ureq::post(&ptz.url)
+        .timeout(Duration::from_secs(10))
+        .set("Content-Type", "application/soap+xml; charset=utf-8")
+        .send_string(&body)?;
+
+    Ok(())
+}
+
+/// A WS-Security UsernameToken with a password digest, as ONVIF expects.
+fn security_header(username: &str, password: &str) -> String {
+    let nonce = nonce();
+    let created = timestamp(SystemTime::now());
+
+    let mut hasher = Sha1::new();
+    hasher.update(nonce);
+    hasher.update(created.as_bytes());
+    hasher.update(password.as_bytes());
+    let digest = BASE64.encode(hasher.finalize());
+
+    format!(
+        r#"<Security s:mustUnderstand="1" xmlns="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd"><UsernameToken><Username>{}</Username><Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest">{}</Password><Nonce EncodingType="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0#Base64Binary">{}</Nonce><Created xmlns="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd">{}</Created></UsernameToken></Security>"#,
+        escape(username),
+        digest,
+        BASE64.encode(nonce),
+        created,
+    )
+}
+
+/// A value that's unique per request, which is all the nonce needs to be.
+fn nonce() -> [u8; 20] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = Sha1::new();
+    hasher.update(process::id().to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.update(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+            .to_le_bytes(),
+    );
+    hasher.finalize().into()
+}
+
+/// An RFC 3339 UTC timestamp, such as `2024-05-20T12:34:56Z`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Days since the epoch to a civil date, from Howard Hinnant's algorithm.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let mut s = String::new();
+    write!(
+        s,
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+    .ok();
+    s
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
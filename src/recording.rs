@@ -0,0 +1,147 @@
+//! Recording frames with their decoded values as a labeled dataset, for
+//! evaluating decoder and threshold changes later.
+//!
+//! Each sampled frame is stored as
+//! `<dir>/<profile>/<millis>/frame.png`, with a crop around every sampled
+//! marker in `<marker>.png` and the values in `labels.json`. Each label has a
+//! `correction` that can be filled in by hand when the decoded value is
+//! wrong.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use epaint::ColorImage;
+use image::RgbaImage;
+use laundry_machine_core::Marker;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Profile;
+
+pub const FRAME_FILE: &str = "frame.png";
+pub const LABELS_FILE: &str = "labels.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct Label {
+    pub value: Value,
+    /// The value the marker actually showed, if `value` is wrong.
+    #[serde(default)]
+    pub correction: Option<Value>,
+    pub samples: Vec<f32>,
+}
+
+impl Label {
+    /// The value the marker should decode to.
+    pub fn expected(&self) -> &Value {
+        self.correction.as_ref().unwrap_or(&self.value)
+    }
+}
+
+pub fn record(
+    dir: &Path,
+    profile: &Profile,
+    image: &ColorImage,
+    time: SystemTime,
+    sampled: &[(usize, Vec<f32>, Value)],
+) -> Result<(), Box<dyn Error>> {
+    let millis = time.duration_since(UNIX_EPOCH)?.as_millis();
+    let dir = dir.join(&profile.name).join(millis.to_string());
+    fs::create_dir_all(&dir)?;
+
+    let [width, height] = image.size;
+    let rgba = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_array())
+        .collect();
+    let frame =
+        RgbaImage::from_raw(width as u32, height as u32, rgba).ok_or("frame has the wrong size")?;
+    frame.save(dir.join(FRAME_FILE))?;
+
+    let mut labels = BTreeMap::new();
+
+    for (idx, samples, value) in sampled {
+        let Some(marker) = profile.markers.get(*idx) else {
+            continue;
+        };
+
+        if let Some((x, y, w, h)) = crop_rect(marker, width as u32, height as u32) {
+            image::imageops::crop_imm(&frame, x, y, w, h)
+                .to_image()
+                .save(dir.join(format!("{}.png", marker.name)))?;
+        }
+
+        labels.insert(
+            marker.name.clone(),
+            Label {
+                value: value.clone(),
+                correction: None,
+                samples: samples.clone(),
+            },
+        );
+    }
+
+    fs::write(
+        dir.join(LABELS_FILE),
+        serde_json::to_string_pretty(&labels)?,
+    )?;
+
+    Ok(())
+}
+
+/// The recorded frame directories under `dir`, at any depth, in order.
+pub fn find(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = vec![];
+
+    if dir.join(LABELS_FILE).exists() {
+        found.push(dir.to_owned());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            found.extend(find(&path)?);
+        }
+    }
+
+    found.sort();
+    Ok(found)
+}
+
+pub fn load_labels(dir: &Path) -> Result<BTreeMap<String, Label>, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(
+        dir.join(LABELS_FILE),
+    )?)?)
+}
+
+/// The bounding box of a marker's points, with some margin around it, if it
+/// overlaps the frame.
+pub fn crop_rect(marker: &Marker, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let points = marker.ty.get_points();
+    let margin = points
+        .iter()
+        .map(|point| point.size * width as f32)
+        .fold(8., f32::max);
+
+    let (mut left, mut top, mut right, mut bottom) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for point in &points {
+        let x = point.pos.x * width as f32;
+        let y = point.pos.y * height as f32;
+        left = left.min(x - margin);
+        top = top.min(y - margin);
+        right = right.max(x + margin);
+        bottom = bottom.max(y + margin);
+    }
+
+    let left = left.clamp(0., width as f32) as u32;
+    let top = top.clamp(0., height as f32) as u32;
+    let right = right.clamp(0., width as f32) as u32;
+    let bottom = bottom.clamp(0., height as f32) as u32;
+
+    (right > left && bottom > top).then(|| (left, top, right - left, bottom - top))
+}
@@ -0,0 +1,102 @@
+//! Rectangular regions of the frame, summed up as a grid of cells that can
+//! be compared between frames.
+
+#[cfg(feature = "gui")]
+use egui::{DragValue, Ui, Widget};
+use epaint::{ColorImage, Pos2};
+use serde::{Deserialize, Serialize};
+
+use laundry_machine_core::stats;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    /// Normalized corners.
+    pub min: Pos2,
+    pub max: Pos2,
+}
+
+impl Region {
+    pub fn is_empty(&self) -> bool {
+        self.min.x >= self.max.x || self.min.y >= self.max.y
+    }
+}
+
+/// Cells along each side of the region, which evens out noise and frames of
+/// different sizes.
+const GRID: usize = 16;
+
+/// The average luminance and sharpness of each cell of the region, which
+/// shifting and blurring both change.
+pub fn cells(image: &ColorImage, region: &Region) -> Vec<f32> {
+    let [width, height] = image.size;
+    let to_pixels = |min: f32, max: f32, size: usize| {
+        let [min, max] = [min, max].map(|n| (n.clamp(0., 1.) * size as f32) as usize);
+        (min, max.max(min))
+    };
+    let (x0, x1) = to_pixels(region.min.x, region.max.x, width);
+    let (y0, y1) = to_pixels(region.min.y, region.max.y, height);
+    let (cols, rows) = (GRID.min(x1 - x0), GRID.min(y1 - y0));
+
+    let mut cells = Vec::with_capacity(cols * rows * 2);
+    let mut line = Vec::with_capacity(x1 - x0);
+
+    for row in 0..rows {
+        let (top, bottom) = (
+            y0 + (y1 - y0) * row / rows,
+            y0 + (y1 - y0) * (row + 1) / rows,
+        );
+        // The sum, edges and pixel count of each cell in this row.
+        let mut totals = vec![(0., 0., 0); cols];
+
+        for y in top..bottom {
+            stats::luminances(&image.pixels[y * width + x0..y * width + x1], &mut line);
+
+            for (col, (sum, edges, count)) in totals.iter_mut().enumerate() {
+                let (left, right) = ((x1 - x0) * col / cols, (x1 - x0) * (col + 1) / cols);
+                let cell = &line[left..right];
+                *sum += stats::sum(cell);
+                *edges += stats::edges(cell);
+                *count += cell.len();
+            }
+        }
+
+        for (sum, edges, count) in totals {
+            cells.push(sum / count as f32);
+            cells.push(edges / count as f32);
+        }
+    }
+
+    cells
+}
+
+/// The average difference between the cells of two frames, or `None` if
+/// they're of different sizes.
+pub fn difference(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.is_empty() || a.len() != b.len() {
+        return None;
+    }
+
+    Some(a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum::<f32>() / a.len() as f32)
+}
+
+/// Grid rows for editing a region's corners.
+#[cfg(feature = "gui")]
+pub fn edit(ui: &mut Ui, region: &mut Region) {
+    for (label, corner) in [
+        ("Top left", &mut region.min),
+        ("Bottom right", &mut region.max),
+    ] {
+        ui.label(label);
+        ui.horizontal(|ui| {
+            DragValue::new(&mut corner.x)
+                .speed(0.005)
+                .clamp_range(0.0..=1.0)
+                .ui(ui);
+            DragValue::new(&mut corner.y)
+                .speed(0.005)
+                .clamp_range(0.0..=1.0)
+                .ui(ui);
+        });
+        ui.end_row();
+    }
+}
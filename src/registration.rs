@@ -0,0 +1,144 @@
+//! Following the camera when it's bumped, by finding how far the frame has
+//! shifted compared to a reference frame from when the markers were placed.
+
+use std::{error::Error, path::Path};
+
+use epaint::{Color32, ColorImage};
+use image::GrayImage;
+#[cfg(feature = "gui")]
+use image::Luma;
+use laundry_machine_core::Marker;
+
+use crate::recording::crop_rect;
+
+/// A reference frame, as luminance.
+pub struct Reference {
+    image: GrayImage,
+}
+
+impl Reference {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            image: image::open(path)?.to_luma8(),
+        })
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn to_color_image(&self) -> ColorImage {
+        ColorImage::from_gray(self.size(), self.image.as_raw())
+    }
+
+    /// The mean difference in luminance around each marker, from 0 to 1, or
+    /// `None` for markers outside the frame or if the sizes don't match.
+    #[cfg(feature = "gui")]
+    pub fn differences(&self, frame: &ColorImage, markers: &[Marker]) -> Vec<Option<f32>> {
+        let [width, height] = frame.size;
+
+        markers
+            .iter()
+            .map(|marker| {
+                if self.size() != frame.size {
+                    return None;
+                }
+
+                let (left, top, w, h) = crop_rect(marker, width as u32, height as u32)?;
+                let mut total = 0u64;
+
+                for y in top..top + h {
+                    for x in left..left + w {
+                        let reference = self.image.get_pixel(x, y).0[0];
+                        let current = luma(frame.pixels[y as usize * width + x as usize]);
+                        total += reference.abs_diff(current) as u64;
+                    }
+                }
+
+                Some(total as f32 / (w * h) as f32 / 255.)
+            })
+            .collect()
+    }
+
+    /// The size as `[width, height]`, like [`ColorImage::size`].
+    pub fn size(&self) -> [usize; 2] {
+        [self.image.width() as usize, self.image.height() as usize]
+    }
+
+    /// The translation in pixels of `frame` compared to this reference, of at
+    /// most `max_shift` in each direction, found by minimizing the difference
+    /// around the markers. `None` if the sizes don't match.
+    pub fn find_shift(
+        &self,
+        frame: &ColorImage,
+        markers: &[Marker],
+        max_shift: usize,
+    ) -> Option<[i64; 2]> {
+        let [width, height] = frame.size;
+        if self.size() != frame.size {
+            return None;
+        }
+
+        let max_shift = max_shift as i64;
+        let regions = markers
+            .iter()
+            .filter_map(|marker| crop_rect(marker, width as u32, height as u32))
+            .collect::<Vec<_>>();
+
+        let difference = |dx: i64, dy: i64| {
+            let (mut total, mut count) = (0u64, 0u64);
+
+            for &(left, top, w, h) in &regions {
+                // Every other pixel is plenty to line up on.
+                for y in (top..top + h).step_by(2) {
+                    for x in (left..left + w).step_by(2) {
+                        let (fx, fy) = (x as i64 + dx, y as i64 + dy);
+                        if !(0..width as i64).contains(&fx) || !(0..height as i64).contains(&fy) {
+                            continue;
+                        }
+
+                        let reference = self.image.get_pixel(x, y).0[0];
+                        let current = luma(frame.pixels[fy as usize * width + fx as usize]);
+                        total += reference.abs_diff(current) as u64;
+                        count += 1;
+                    }
+                }
+            }
+
+            (count > 0).then(|| total as f64 / count as f64)
+        };
+
+        // Staying put wins ties, so noise doesn't move the markers.
+        let mut best = ([0, 0], difference(0, 0)?);
+
+        for dy in -max_shift..=max_shift {
+            for dx in -max_shift..=max_shift {
+                if let Some(score) = difference(dx, dy) {
+                    if score < best.1 {
+                        best = ([dx, dy], score);
+                    }
+                }
+            }
+        }
+
+        Some(best.0)
+    }
+}
+
+/// Saves a frame to use as the reference.
+#[cfg(feature = "gui")]
+pub fn save(frame: &ColorImage, path: &Path) -> Result<(), Box<dyn Error>> {
+    let [width, height] = frame.size;
+    let image = GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        Luma([luma(frame.pixels[y as usize * width + x as usize])])
+    });
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    image.save(path)?;
+    Ok(())
+}
+
+fn luma(color: Color32) -> u8 {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    ((r as u32 * 54 + g as u32 * 183 + b as u32 * 19) >> 8) as u8
+}
@@ -0,0 +1,161 @@
+//! Reminding people of laundry left in after a cycle finished, more and more
+//! often, until the door opens or the reminders are acknowledged.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminders {
+    /// Minutes from the cycle finishing to the first reminder, then between
+    /// each one and the next. The last one repeats until acknowledged.
+    pub intervals_mins: Vec<u64>,
+}
+
+impl Default for Reminders {
+    fn default() -> Self {
+        Self {
+            intervals_mins: vec![30, 15, 10],
+        }
+    }
+}
+
+impl Reminders {
+    /// How long after the cycle finished the reminder with the given index
+    /// is due.
+    fn due_after(&self, idx: usize) -> Option<Duration> {
+        let last = *self.intervals_mins.last()?;
+        let mins = (0..=idx)
+            .map(|idx| self.intervals_mins.get(idx).copied().unwrap_or(last).max(1))
+            .sum::<u64>();
+
+        Some(Duration::from_secs(mins * 60))
+    }
+}
+
+/// Follows a single profile's reminders.
+#[derive(Default)]
+pub struct Tracker {
+    /// When the last cycle finished, until it's acknowledged.
+    finished: Option<Instant>,
+    sent: usize,
+}
+
+impl Tracker {
+    pub fn start(&mut self) {
+        self.finished = Some(Instant::now());
+        self.sent = 0;
+    }
+
+    pub fn stop(&mut self) {
+        self.finished = None;
+    }
+
+    /// The reminder that's due now, if any, as how many there have been
+    /// including this one, and minutes since the cycle finished.
+    pub fn due(&mut self, reminders: &Reminders) -> Option<(usize, u64)> {
+        self.due_at(reminders, Instant::now())
+    }
+
+    fn due_at(&mut self, reminders: &Reminders, now: Instant) -> Option<(usize, u64)> {
+        let waited = now.saturating_duration_since(self.finished?);
+        if waited < reminders.due_after(self.sent)? {
+            return None;
+        }
+
+        self.sent += 1;
+        Some((self.sent, waited.as_secs() / 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Duration = Duration::from_secs(60);
+
+    fn reminders(intervals_mins: &[u64]) -> Reminders {
+        Reminders {
+            intervals_mins: intervals_mins.to_vec(),
+        }
+    }
+
+    #[test]
+    fn intervals_add_up_and_the_last_repeats() {
+        let reminders = reminders(&[30, 15, 10]);
+
+        assert_eq!(reminders.due_after(0), Some(MIN * 30));
+        assert_eq!(reminders.due_after(1), Some(MIN * 45));
+        assert_eq!(reminders.due_after(2), Some(MIN * 55));
+        assert_eq!(reminders.due_after(3), Some(MIN * 65));
+    }
+
+    #[test]
+    fn intervals_are_at_least_a_minute() {
+        assert_eq!(reminders(&[0, 0]).due_after(2), Some(MIN * 3));
+    }
+
+    #[test]
+    fn no_intervals_means_no_reminders() {
+        assert_eq!(reminders(&[]).due_after(0), None);
+
+        let mut tracker = Tracker::default();
+        tracker.start();
+        assert_eq!(
+            tracker.due_at(&reminders(&[]), Instant::now() + MIN * 60),
+            None
+        );
+    }
+
+    #[test]
+    fn reminds_once_each_interval() {
+        let reminders = reminders(&[30, 15]);
+        let mut tracker = Tracker::default();
+        tracker.start();
+        let finished = tracker.finished.unwrap();
+
+        assert_eq!(tracker.due_at(&reminders, finished + MIN * 29), None);
+        assert_eq!(
+            tracker.due_at(&reminders, finished + MIN * 30),
+            Some((1, 30))
+        );
+        assert_eq!(tracker.due_at(&reminders, finished + MIN * 31), None);
+        assert_eq!(tracker.due_at(&reminders, finished + MIN * 44), None);
+        assert_eq!(
+            tracker.due_at(&reminders, finished + MIN * 46),
+            Some((2, 46))
+        );
+        assert_eq!(
+            tracker.due_at(&reminders, finished + MIN * 60),
+            Some((3, 60))
+        );
+    }
+
+    #[test]
+    fn stopping_ends_reminders() {
+        let reminders = reminders(&[30]);
+        let mut tracker = Tracker::default();
+
+        assert_eq!(tracker.due_at(&reminders, Instant::now() + MIN * 60), None);
+
+        tracker.start();
+        tracker.stop();
+        assert_eq!(tracker.due_at(&reminders, Instant::now() + MIN * 60), None);
+    }
+
+    #[test]
+    fn starting_again_starts_over() {
+        let reminders = reminders(&[30]);
+        let mut tracker = Tracker::default();
+        tracker.start();
+        let finished = tracker.finished.unwrap();
+        assert!(tracker.due_at(&reminders, finished + MIN * 30).is_some());
+
+        tracker.start();
+        let finished = tracker.finished.unwrap();
+        assert_eq!(
+            tracker.due_at(&reminders, finished + MIN * 30),
+            Some((1, 30))
+        );
+    }
+}
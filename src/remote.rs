@@ -0,0 +1,204 @@
+//! Config changes over MQTT: changes are accepted on `{prefix}/config/set`,
+//! and the active config is published to `{prefix}/config/state`.
+
+use laundry_machine_core::Marker;
+use serde_json::Value;
+use tracing::error;
+
+use crate::config::Config;
+
+/// The topic to receive config changes on, if enabled.
+pub fn set_topic(config: &Config) -> Option<String> {
+    config
+        .mqtt
+        .remote_config
+        .then(|| format!("{}/config/set", config.profile().topic_prefix))
+}
+
+/// The topic and payload to publish the current config with.
+pub fn state_message(config: &Config) -> Option<(String, String)> {
+    let payload = match serde_json::to_string(&config.without_secrets()) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Error serializing config: {}", e);
+            return None;
+        }
+    };
+
+    Some((
+        format!("{}/config/state", config.profile().topic_prefix),
+        payload,
+    ))
+}
+
+/// Applies a config change, which is either a full config document, or a
+/// patch for a single marker of the active profile in the form
+/// `{"marker": "<name>", "patch": { ... }}`, using JSON merge patch semantics.
+pub fn apply(config: &mut Config, payload: &[u8]) -> Result<(), String> {
+    let value: Value = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+
+    if let (Some(Value::String(name)), Some(patch)) = (value.get("marker"), value.get("patch")) {
+        let marker = config
+            .profile_mut()
+            .markers
+            .iter_mut()
+            .find(|marker| &marker.name == name)
+            .ok_or_else(|| format!("no marker named {}", name))?;
+
+        let mut current = serde_json::to_value(&*marker).map_err(|e| e.to_string())?;
+        merge_patch(&mut current, patch);
+        *marker = serde_json::from_value::<Marker>(current).map_err(|e| e.to_string())?;
+
+        return Ok(());
+    }
+
+    let imported = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    config.import(imported);
+    Ok(())
+}
+
+/// RFC 7396 JSON merge patch.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+
+    let Value::Object(target) = target else {
+        unreachable!()
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use epaint::pos2;
+    use laundry_machine_core::MarkerType;
+    use serde_json::json;
+
+    use super::*;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.migrate();
+        config.mqtt.remote_config = true;
+
+        let mut marker = Marker::new(MarkerType::Point {
+            pos: pos2(0.5, 0.5),
+            size: 1.,
+        });
+        marker.name = "door".to_owned();
+        marker.unit = Some("open".to_owned());
+        config.profile_mut().markers.push(marker);
+        config
+    }
+
+    fn apply_json(config: &mut Config, payload: Value) -> Result<(), String> {
+        apply(config, payload.to_string().as_bytes())
+    }
+
+    #[test]
+    fn merges_patches() {
+        let mut target = json!({ "a": "b", "c": { "d": "e", "f": "g" } });
+        merge_patch(&mut target, &json!({ "a": "z", "c": { "f": null } }));
+        assert_eq!(target, json!({ "a": "z", "c": { "d": "e" } }));
+
+        let mut target = json!({ "a": ["b"] });
+        merge_patch(&mut target, &json!({ "a": "c" }));
+        assert_eq!(target, json!({ "a": "c" }));
+
+        let mut target = json!({ "a": "b" });
+        merge_patch(&mut target, &json!({ "a": { "b": "c" } }));
+        assert_eq!(target, json!({ "a": { "b": "c" } }));
+
+        let mut target = json!(["a", "b"]);
+        merge_patch(&mut target, &json!({ "a": "b", "c": null }));
+        assert_eq!(target, json!({ "a": "b" }));
+    }
+
+    #[test]
+    fn patches_a_marker() {
+        let mut config = config();
+
+        apply_json(
+            &mut config,
+            json!({ "marker": "door", "patch": { "threshold": 0.25, "unit": null } }),
+        )
+        .unwrap();
+
+        let marker = &config.profile().markers[0];
+        assert_eq!(marker.threshold, Some(0.25));
+        assert_eq!(marker.unit, None);
+        assert_eq!(marker.name, "door");
+    }
+
+    #[test]
+    fn rejects_patches_for_missing_markers() {
+        let mut config = config();
+
+        assert_eq!(
+            apply_json(
+                &mut config,
+                json!({ "marker": "lid", "patch": { "threshold": 0.25 } })
+            ),
+            Err("no marker named lid".to_owned()),
+        );
+    }
+
+    #[test]
+    fn keeps_the_marker_if_the_patch_breaks_it() {
+        let mut config = config();
+
+        assert!(apply_json(
+            &mut config,
+            json!({ "marker": "door", "patch": { "threshold": "high" } })
+        )
+        .is_err());
+        assert_eq!(config.profile().markers[0].threshold, None);
+    }
+
+    #[test]
+    fn rejects_payloads_that_arent_json() {
+        let mut config = config();
+
+        assert!(apply(&mut config, b"threshold = 0.25").is_err());
+        assert_eq!(config.profile().markers.len(), 1);
+    }
+
+    #[test]
+    fn imports_the_published_state_keeping_secrets() {
+        let mut config = config();
+        config.mqtt.password = Some("hunter2".to_owned());
+
+        let mut changed = config.clone();
+        changed.profile_mut().markers.clear();
+        let (_, payload) = state_message(&changed).unwrap();
+
+        apply(&mut config, payload.as_bytes()).unwrap();
+        assert!(config.profile().markers.is_empty());
+        assert_eq!(config.mqtt.password.as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn topics_follow_the_active_profile() {
+        let mut config = config();
+        config.profile_mut().topic_prefix = "washer".to_owned();
+
+        assert_eq!(set_topic(&config).as_deref(), Some("washer/config/set"));
+        assert_eq!(state_message(&config).unwrap().0, "washer/config/state");
+
+        config.mqtt.remote_config = false;
+        assert_eq!(set_topic(&config), None);
+    }
+}
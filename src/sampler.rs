@@ -0,0 +1,90 @@
+use std::error::Error;
+
+use epaint::{Color32, ColorImage};
+use image::GenericImageView;
+use laundry_machine_core::{Interpolation, Marker, MarkerType};
+use tracing::warn;
+
+use crate::{config::WebcamConfig, gpio, recording::crop_rect, source};
+
+/// Frames with more pixels than this only have the regions covered by
+/// markers converted, when that's all that's needed.
+const LARGE_FRAME: u32 = 1280 * 720;
+
+/// Fetches a single frame from a webcam.
+pub fn fetch_frame(webcam: &WebcamConfig) -> Result<ColorImage, Box<dyn Error>> {
+    Ok(source::open(webcam, None)?.latest(None)?.image)
+}
+
+/// The luminance at each point of a marker, searching `search` pixels around
+/// each for the brightest. GPIO inputs are read instead, with no samples if
+/// that fails.
+pub fn sample(
+    ty: &MarkerType,
+    image: &ColorImage,
+    search: usize,
+    interpolation: Interpolation,
+) -> Vec<f32> {
+    match *ty {
+        MarkerType::Gpio {
+            pin,
+            active_low,
+            pull_up,
+        } => match gpio::read(pin, active_low, pull_up) {
+            Ok(active) => vec![if active { 1. } else { 0. }],
+            Err(e) => {
+                warn!("Error reading GPIO pin {}: {}", pin, e);
+                vec![]
+            }
+        },
+        _ => ty.sample_with_search(
+            &image.pixels,
+            image.width(),
+            image.height(),
+            search,
+            interpolation,
+        ),
+    }
+}
+
+/// Decodes an encoded image, such as a JPEG snapshot.
+pub fn decode_frame(bytes: &[u8]) -> Result<ColorImage, Box<dyn Error>> {
+    decode_regions(bytes, None)
+}
+
+/// Decodes an image like [`decode_frame`], but if it's large and `markers`
+/// are given, only the regions around those are kept, with the rest of the
+/// frame left black. This saves converting and copying the whole frame when
+/// only the markers are sampled.
+pub fn decode_regions(
+    bytes: &[u8],
+    markers: Option<&[Marker]>,
+) -> Result<ColorImage, Box<dyn Error>> {
+    let image = image::load_from_memory(bytes)?;
+    let (width, height) = image.dimensions();
+
+    let Some(markers) = markers.filter(|_| width * height > LARGE_FRAME) else {
+        let image = image.to_rgba8();
+        return Ok(ColorImage::from_rgba_unmultiplied(
+            [width as usize, height as usize],
+            image.as_raw(),
+        ));
+    };
+
+    let mut frame = ColorImage::new([width as usize, height as usize], Color32::BLACK);
+
+    for (left, top, w, h) in markers
+        .iter()
+        .filter_map(|marker| crop_rect(marker, width, height))
+    {
+        for y in top..top + h {
+            for x in left..left + w {
+                let [r, g, b, a] = image.get_pixel(x, y).0;
+                frame.pixels[(y * width + x) as usize] =
+                    Color32::from_rgba_unmultiplied(r, g, b, a);
+            }
+        }
+    }
+
+    Ok(frame)
+}
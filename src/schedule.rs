@@ -0,0 +1,163 @@
+//! Limiting sampling to certain hours, and lining samples up with the clock.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::lighting::{in_window, parse_time};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Local time of day from which to sample, as `HH:MM`. Wraps around
+    /// midnight if it's after `until`.
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub until: Option<String>,
+    /// Sample at whole multiples of the interval, such as every minute on
+    /// the minute, rather than counting from the previous sample.
+    #[serde(default)]
+    pub on_the_clock: bool,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            from: Some("06:00".to_owned()),
+            until: Some("23:00".to_owned()),
+            on_the_clock: false,
+        }
+    }
+}
+
+impl Schedule {
+    /// Whether to sample right now.
+    pub fn is_active(&self) -> bool {
+        self.is_active_at(Local::now().time())
+    }
+
+    fn is_active_at(&self, time: NaiveTime) -> bool {
+        in_window(
+            time,
+            self.from.as_deref().and_then(parse_time),
+            self.until.as_deref().and_then(parse_time),
+        )
+    }
+
+    /// How long to wait for the next sample after one just now.
+    pub fn wait(&self, interval: Duration) -> Duration {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        self.wait_since(interval, since_epoch)
+    }
+
+    fn wait_since(&self, interval: Duration, since_epoch: Duration) -> Duration {
+        if !self.on_the_clock || interval.is_zero() {
+            return interval;
+        }
+
+        // Whole multiples since the epoch, which for intervals that divide
+        // an hour are also whole multiples in every time zone.
+        let into_interval =
+            Duration::from_nanos((since_epoch.as_nanos() % interval.as_nanos()) as u64);
+
+        interval - into_interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(from: Option<&str>, until: Option<&str>) -> Schedule {
+        Schedule {
+            from: from.map(str::to_owned),
+            until: until.map(str::to_owned),
+            on_the_clock: false,
+        }
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        parse_time(time).unwrap()
+    }
+
+    #[test]
+    fn active_from_start_until_end() {
+        let schedule = schedule(Some("06:00"), Some("23:00"));
+
+        assert!(!schedule.is_active_at(at("05:59")));
+        assert!(schedule.is_active_at(at("06:00")));
+        assert!(schedule.is_active_at(at("12:00")));
+        assert!(schedule.is_active_at(at("22:59")));
+        assert!(!schedule.is_active_at(at("23:00")));
+    }
+
+    #[test]
+    fn wraps_around_midnight() {
+        let schedule = schedule(Some("22:00"), Some("02:00"));
+
+        assert!(!schedule.is_active_at(at("21:59")));
+        assert!(schedule.is_active_at(at("22:00")));
+        assert!(schedule.is_active_at(at("00:00")));
+        assert!(schedule.is_active_at(at("01:59")));
+        assert!(!schedule.is_active_at(at("02:00")));
+        assert!(!schedule.is_active_at(at("12:00")));
+    }
+
+    #[test]
+    fn open_ends() {
+        assert!(!schedule(Some("06:00"), None).is_active_at(at("05:59")));
+        assert!(schedule(Some("06:00"), None).is_active_at(at("23:59")));
+        assert!(schedule(None, Some("23:00")).is_active_at(at("00:00")));
+        assert!(!schedule(None, Some("23:00")).is_active_at(at("23:00")));
+        assert!(schedule(None, None).is_active_at(at("03:00")));
+    }
+
+    #[test]
+    fn unparsable_times_leave_that_end_open() {
+        let schedule = schedule(Some("6 am"), Some("23:00"));
+
+        assert!(schedule.is_active_at(at("05:00")));
+        assert!(!schedule.is_active_at(at("23:00")));
+    }
+
+    #[test]
+    fn waits_until_the_next_multiple_on_the_clock() {
+        let schedule = Schedule {
+            on_the_clock: true,
+            ..schedule(None, None)
+        };
+        let minute = Duration::from_secs(60);
+
+        assert_eq!(
+            schedule.wait_since(minute, Duration::from_secs(600)),
+            minute
+        );
+        assert_eq!(
+            schedule.wait_since(minute, Duration::from_secs(615)),
+            Duration::from_secs(45),
+        );
+        assert_eq!(
+            schedule.wait_since(minute, Duration::from_millis(659_999)),
+            Duration::from_millis(1),
+        );
+        assert_eq!(
+            schedule.wait_since(Duration::ZERO, Duration::from_secs(615)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn waits_the_interval_off_the_clock() {
+        let schedule = schedule(None, None);
+        let minute = Duration::from_secs(60);
+
+        assert_eq!(
+            schedule.wait_since(minute, Duration::from_secs(615)),
+            minute
+        );
+    }
+}
@@ -0,0 +1,638 @@
+//! Where decoded values go after each sample. MQTT is always a sink, the
+//! others are enabled through their sections of the config.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rumqttc::{AsyncClient, QoS};
+use serde_json::{json, Value};
+use tokio::time::{self, Instant};
+use tracing::debug;
+
+use laundry_machine_core::{MarkerType, Message, PayloadFormat, Quality};
+
+use crate::{
+    api::SharedStatus,
+    bridge,
+    config::{Config, InfluxConfig, Profile},
+    event::{self, Event, EventKind},
+    influx,
+    notification::{Notifications, QuietHours},
+    webhook::Webhook,
+};
+
+/// Reports an error from a sink.
+pub type Report = Arc<dyn Fn(String) + Send + Sync>;
+
+/// The values of a profile after a sample.
+pub struct Batch<'a> {
+    pub profile: &'a Profile,
+    /// The values before this sample, to detect changes with.
+    pub previous: &'a [Value],
+    pub values: &'a [Value],
+    /// How each value was decoded.
+    pub quality: &'a [Quality],
+    /// The markers that were sampled.
+    pub indices: &'a [usize],
+    /// A cycle starting or finishing with this sample.
+    pub transition: Option<EventKind>,
+    /// Minutes since the cycle finished, for a reminder instead of a sample.
+    pub reminder: Option<u64>,
+}
+
+impl Batch<'_> {
+    fn events(&self) -> Vec<Event> {
+        let mut events = event::events(
+            self.profile,
+            self.previous,
+            self.values,
+            self.indices,
+            self.transition,
+        );
+
+        if let Some(mins) = self.reminder {
+            events.push(Event {
+                kind: EventKind::Reminder,
+                marker: None,
+                value: json!(mins),
+            });
+        }
+
+        events
+    }
+
+    fn time_remaining(&self) -> Option<u64> {
+        laundry_machine_core::time_remaining(
+            &self.profile.markers,
+            self.values,
+            &self.profile.time_remaining,
+        )
+    }
+}
+
+pub trait OutputSink: Send {
+    /// Handles a batch of values. This shouldn't block, so anything slow
+    /// should be moved to a background task.
+    fn write(&mut self, batch: &Batch);
+}
+
+/// All sinks for a config.
+pub struct Sinks {
+    sinks: Vec<Box<dyn OutputSink>>,
+}
+
+impl Sinks {
+    /// `flash` is called with the profile name when an alarm should flash
+    /// the window.
+    pub fn new(
+        config: &Config,
+        mqtt: AsyncClient,
+        status: SharedStatus,
+        report: Report,
+        flash: Report,
+    ) -> Self {
+        let quiet_hours = config
+            .notifications
+            .quiet_hours
+            .clone()
+            .filter(|quiet_hours| quiet_hours.non_essential);
+
+        let publisher = Publisher {
+            client: mqtt,
+            status: status.clone(),
+            report: report.clone(),
+        };
+
+        let mut sinks: Vec<Box<dyn OutputSink>> = vec![Box::new(MqttSink {
+            publisher: publisher.clone(),
+            payload_format: config.mqtt.payload_format,
+            throttle: config.throttle_secs.map(|secs| Throttle {
+                window: Duration::from_secs(secs),
+                topics: Default::default(),
+            }),
+        })];
+
+        if let Some(base) = &config.mqtt.bridge_topic {
+            sinks.push(Box::new(BridgeSink {
+                base: base.clone(),
+                publisher,
+            }));
+        }
+
+        if let Some(influxdb) = &config.influxdb {
+            sinks.push(Box::new(InfluxSink {
+                config: influxdb.clone(),
+                report: report.clone(),
+            }));
+        }
+
+        if !config.webhooks.is_empty() {
+            sinks.push(Box::new(WebhookSink {
+                webhooks: config.webhooks.clone(),
+                quiet_hours: quiet_hours.clone(),
+                report: report.clone(),
+            }));
+        }
+
+        if !config.notifications.rules.is_empty() {
+            sinks.push(Box::new(NotificationSink {
+                notifications: config.notifications.clone(),
+                deferred: vec![],
+                status,
+                report: report.clone(),
+            }));
+        }
+
+        if config
+            .profiles
+            .iter()
+            .any(|profile| profile.alarm.is_some())
+        {
+            sinks.push(Box::new(AlarmSink {
+                quiet_hours,
+                report,
+                flash,
+            }));
+        }
+
+        Self { sinks }
+    }
+
+    /// Only the given sinks, instead of those of a config.
+    pub fn only(sinks: Vec<Box<dyn OutputSink>>) -> Self {
+        Self { sinks }
+    }
+
+    pub fn write(&mut self, batch: &Batch) {
+        for sink in &mut self.sinks {
+            sink.write(batch);
+        }
+    }
+}
+
+pub struct MqttSink {
+    publisher: Publisher,
+    payload_format: PayloadFormat,
+    throttle: Option<Throttle>,
+}
+
+#[derive(Clone)]
+struct Publisher {
+    client: AsyncClient,
+    status: SharedStatus,
+    report: Report,
+}
+
+/// Where a [`Throttle`] publishes to.
+trait Publish: Clone + Send + 'static {
+    fn publish(&self, message: &Message);
+}
+
+impl Publish for Publisher {
+    fn publish(&self, message: &Message) {
+        // Queues the message for the event loop, in order.
+        match self.client.try_publish(
+            &message.topic,
+            QoS::AtLeastOnce,
+            false,
+            message.payload.clone(),
+        ) {
+            Ok(()) => {
+                debug!("Published {}: {}", message.topic, message.payload);
+                self.status.lock().unwrap().publish_time = Some(std::time::Instant::now());
+            }
+            Err(e) => (self.report)(format!("Error publishing {}: {}", message.topic, e)),
+        }
+    }
+}
+
+/// Publishes to each topic at most once per window. What comes in during a
+/// window is held back, and only the latest of it is published once the
+/// window closes.
+struct Throttle {
+    window: Duration,
+    topics: Arc<Mutex<HashMap<String, Window>>>,
+}
+
+struct Window {
+    start: Instant,
+    held: Option<String>,
+}
+
+impl Throttle {
+    fn publish(&self, publisher: &impl Publish, message: Message) {
+        let now = Instant::now();
+        let mut topics = self.topics.lock().unwrap();
+
+        let window = match topics.get_mut(&message.topic) {
+            Some(window) if now < window.start + self.window => window,
+            _ => {
+                publisher.publish(&message);
+                topics.insert(
+                    message.topic,
+                    Window {
+                        start: now,
+                        held: None,
+                    },
+                );
+                return;
+            }
+        };
+
+        // Only the first message held back needs to schedule a publish.
+        if window.held.replace(message.payload).is_some() {
+            return;
+        }
+
+        let end = window.start + self.window;
+        let topics = self.topics.clone();
+        let publisher = publisher.clone();
+
+        tokio::spawn(async move {
+            time::sleep_until(end).await;
+
+            let mut topics = topics.lock().unwrap();
+            let Some(window) = topics.get_mut(&message.topic) else {
+                return;
+            };
+
+            if let Some(payload) = window.held.take() {
+                window.start = Instant::now();
+                publisher.publish(&Message {
+                    topic: message.topic,
+                    payload,
+                });
+            }
+        });
+    }
+}
+
+impl OutputSink for MqttSink {
+    fn write(&mut self, batch: &Batch) {
+        for message in messages(batch, self.payload_format) {
+            match &self.throttle {
+                Some(throttle) => throttle.publish(&self.publisher, message),
+                None => self.publisher.publish(&message),
+            }
+        }
+    }
+}
+
+/// The messages the MQTT sink publishes for a batch.
+fn messages(batch: &Batch, payload_format: PayloadFormat) -> Vec<Message> {
+    let profile = batch.profile;
+
+    let mut messages = laundry_machine_core::messages(
+        &profile.topic_prefix,
+        &profile.markers,
+        batch.values,
+        batch.indices,
+        payload_format,
+        &profile.time_remaining,
+    );
+
+    // Only seven segment displays are retried.
+    for &idx in batch.indices {
+        if let (Some(marker), Some(quality)) = (profile.markers.get(idx), batch.quality.get(idx)) {
+            if matches!(marker.ty, MarkerType::SevenSegment { .. }) {
+                messages.push(Message {
+                    topic: format!("{}/{}/quality", profile.topic_prefix, marker.name),
+                    payload: serde_json::to_string(quality).unwrap(),
+                });
+            }
+        }
+    }
+
+    messages
+}
+
+/// Builds the MQTT sink's messages and the batch's events, and drops them,
+/// for timing the pipeline without sending anything.
+pub struct NullSink {
+    pub payload_format: PayloadFormat,
+}
+
+impl OutputSink for NullSink {
+    fn write(&mut self, batch: &Batch) {
+        drop(messages(batch, self.payload_format));
+        drop(batch.events());
+    }
+}
+
+/// Publishes each profile's whole state to its device topic, following
+/// Zigbee2MQTT's conventions.
+pub struct BridgeSink {
+    base: String,
+    publisher: Publisher,
+}
+
+impl OutputSink for BridgeSink {
+    fn write(&mut self, batch: &Batch) {
+        // Reminders don't change the state.
+        if batch.reminder.is_some() {
+            return;
+        }
+
+        self.publisher.publish(&Message {
+            topic: bridge::device_topic(&self.base, batch.profile),
+            payload: bridge::device_state(batch.profile, batch.values).to_string(),
+        });
+    }
+}
+
+pub struct InfluxSink {
+    config: InfluxConfig,
+    report: Report,
+}
+
+impl OutputSink for InfluxSink {
+    fn write(&mut self, batch: &Batch) {
+        let config = self.config.clone();
+        let profile = batch.profile.clone();
+        let values = batch.values.to_vec();
+        let indices = batch.indices.to_vec();
+        let report = self.report.clone();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = influx::write(&config, &profile, &values, &indices) {
+                report(format!("Error writing to InfluxDB: {}", e));
+            }
+        });
+    }
+}
+
+pub struct WebhookSink {
+    webhooks: Vec<Webhook>,
+    /// Skips all webhooks while it's quiet.
+    quiet_hours: Option<QuietHours>,
+    report: Report,
+}
+
+impl OutputSink for WebhookSink {
+    fn write(&mut self, batch: &Batch) {
+        if self.quiet_hours.as_ref().is_some_and(QuietHours::is_quiet) {
+            return;
+        }
+
+        let time_remaining = batch.time_remaining();
+
+        for event in batch.events() {
+            for webhook in self.webhooks.iter().filter(|webhook| webhook.wants(&event)) {
+                let body = match webhook.render(batch.profile, &event, time_remaining) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        (self.report)(format!("Error in webhook body for {}: {}", webhook.url, e));
+                        continue;
+                    }
+                };
+
+                let webhook = webhook.clone();
+                let report = self.report.clone();
+
+                // Retries can take a while.
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = webhook.send(&body) {
+                        report(format!("Error sending webhook to {}: {}", webhook.url, e));
+                    }
+                });
+            }
+        }
+    }
+}
+
+pub struct NotificationSink {
+    notifications: Notifications,
+    /// Held back during quiet hours, and sent with the first batch after they
+    /// end.
+    deferred: Vec<Pending>,
+    status: SharedStatus,
+    report: Report,
+}
+
+struct Pending {
+    title: String,
+    message: String,
+    /// Only send to the people who are home.
+    present_only: bool,
+}
+
+impl NotificationSink {
+    /// Sends a notification to everyone it should go to. People whose
+    /// presence isn't known yet are assumed to be home.
+    fn send(&self, pending: &Pending) {
+        let home = self.status.lock().unwrap().home.clone();
+
+        let people = self
+            .notifications
+            .people
+            .iter()
+            .filter(|person| !pending.present_only || home.get(&person.name) != Some(&false))
+            .flat_map(|person| &person.providers);
+
+        for provider in self.notifications.providers.iter().chain(people) {
+            let provider = provider.clone();
+            let title = pending.title.clone();
+            let message = pending.message.clone();
+            let report = self.report.clone();
+
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = provider.send(&title, &message) {
+                    report(format!(
+                        "Error sending {} notification: {}",
+                        provider.name(),
+                        e
+                    ));
+                }
+            });
+        }
+    }
+}
+
+impl OutputSink for NotificationSink {
+    fn write(&mut self, batch: &Batch) {
+        let quiet_hours = self
+            .notifications
+            .quiet_hours
+            .as_ref()
+            .filter(|quiet_hours| quiet_hours.is_quiet());
+
+        if quiet_hours.is_none() {
+            for pending in std::mem::take(&mut self.deferred) {
+                self.send(&pending);
+            }
+        }
+
+        let time_remaining = batch.time_remaining();
+        let title = format!("Laundry machine: {}", batch.profile.name);
+
+        for event in batch.events() {
+            for rule in self
+                .notifications
+                .rules
+                .iter()
+                .filter(|rule| rule.matches(&event))
+            {
+                let pending = Pending {
+                    title: title.clone(),
+                    message: rule.message(batch.profile, &event, time_remaining),
+                    present_only: batch.profile.presence.is_some(),
+                };
+
+                match quiet_hours {
+                    Some(quiet_hours) if quiet_hours.defer => self.deferred.push(pending),
+                    Some(_) => debug!(
+                        "Dropped notification during quiet hours: {}",
+                        pending.message
+                    ),
+                    None => self.send(&pending),
+                }
+            }
+        }
+    }
+}
+
+pub struct AlarmSink {
+    /// Keeps the alarm silent while it's quiet, though it still flashes.
+    quiet_hours: Option<QuietHours>,
+    report: Report,
+    flash: Report,
+}
+
+impl OutputSink for AlarmSink {
+    fn write(&mut self, batch: &Batch) {
+        let Some(alarm) = &batch.profile.alarm else {
+            return;
+        };
+
+        if !batch
+            .events()
+            .iter()
+            .any(|event| event.kind == EventKind::CycleFinished)
+        {
+            return;
+        }
+
+        if alarm.flash {
+            (self.flash)(batch.profile.name.clone());
+        }
+
+        if self.quiet_hours.as_ref().is_some_and(QuietHours::is_quiet) {
+            return;
+        }
+
+        let alarm = alarm.clone();
+        let report = self.report.clone();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = alarm.play() {
+                report(format!("Error playing alarm: {}", e));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps what's published, as `(topic, payload)`.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl Publish for Recorder {
+        fn publish(&self, message: &Message) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((message.topic.clone(), message.payload.clone()));
+        }
+    }
+
+    impl Recorder {
+        fn take(&self) -> Vec<(String, String)> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    const WINDOW: Duration = Duration::from_secs(10);
+
+    fn throttle() -> Throttle {
+        Throttle {
+            window: WINDOW,
+            topics: Default::default(),
+        }
+    }
+
+    fn message(topic: &str, payload: &str) -> Message {
+        Message {
+            topic: topic.to_owned(),
+            payload: payload.to_owned(),
+        }
+    }
+
+    fn published(topic: &str, payload: &str) -> (String, String) {
+        (topic.to_owned(), payload.to_owned())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn publishes_the_latest_held_back_once_the_window_closes() {
+        let (throttle, recorder) = (throttle(), Recorder::default());
+
+        throttle.publish(&recorder, message("a", "1"));
+        assert_eq!(recorder.take(), [published("a", "1")]);
+
+        time::sleep(Duration::from_secs(1)).await;
+        throttle.publish(&recorder, message("a", "2"));
+        throttle.publish(&recorder, message("a", "3"));
+        assert_eq!(recorder.take(), []);
+
+        time::sleep(WINDOW).await;
+        assert_eq!(recorder.take(), [published("a", "3")]);
+
+        time::sleep(WINDOW * 2).await;
+        assert_eq!(recorder.take(), []);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn starts_a_window_with_the_trailing_publish() {
+        let (throttle, recorder) = (throttle(), Recorder::default());
+
+        throttle.publish(&recorder, message("a", "1"));
+        throttle.publish(&recorder, message("a", "2"));
+        time::sleep(WINDOW + Duration::from_secs(1)).await;
+        assert_eq!(recorder.take(), [published("a", "1"), published("a", "2")]);
+
+        // Still within the window the trailing publish started.
+        throttle.publish(&recorder, message("a", "3"));
+        assert_eq!(recorder.take(), []);
+
+        time::sleep(WINDOW).await;
+        assert_eq!(recorder.take(), [published("a", "3")]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn publishes_right_away_after_a_quiet_window() {
+        let (throttle, recorder) = (throttle(), Recorder::default());
+
+        throttle.publish(&recorder, message("a", "1"));
+        time::sleep(WINDOW).await;
+        throttle.publish(&recorder, message("a", "2"));
+        assert_eq!(recorder.take(), [published("a", "1"), published("a", "2")]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttles_topics_separately() {
+        let (throttle, recorder) = (throttle(), Recorder::default());
+
+        throttle.publish(&recorder, message("a", "1"));
+        throttle.publish(&recorder, message("b", "1"));
+        throttle.publish(&recorder, message("a", "2"));
+        assert_eq!(recorder.take(), [published("a", "1"), published("b", "1")]);
+
+        time::sleep(WINDOW + Duration::from_secs(1)).await;
+        assert_eq!(recorder.take(), [published("a", "2")]);
+    }
+}
@@ -0,0 +1,110 @@
+//! Snapping marker points to the middle of the nearest lit or unlit spot
+//! while placing them.
+
+use egui::{pos2, ColorImage, Pos2};
+
+use laundry_machine_core::{luminance, pixel_at};
+
+/// How far from a point to look for a spot, in pixels.
+const RADIUS: usize = 12;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Snap {
+    Bright,
+    /// For inverted displays, which show dark segments on a lit background.
+    Dark,
+}
+
+impl Snap {
+    pub const ALL: [Self; 2] = [Self::Bright, Self::Dark];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bright => "bright spots",
+            Self::Dark => "dark spots",
+        }
+    }
+
+    /// The center of the spot nearest to the normalized `pos`, if there's
+    /// one nearby that stands out from its surroundings.
+    pub fn snap(self, image: &ColorImage, pos: Pos2) -> Option<Pos2> {
+        let [width, height] = image.size;
+        let (x, y) = pixel_at(pos, width, height);
+
+        let left = (x - RADIUS as i64).max(0) as usize;
+        let top = (y - RADIUS as i64).max(0) as usize;
+        let right = ((x + RADIUS as i64 + 1).max(0) as usize).min(width);
+        let bottom = ((y + RADIUS as i64 + 1).max(0) as usize).min(height);
+
+        if left >= right || top >= bottom {
+            return None;
+        }
+
+        let w = right - left;
+        let h = bottom - top;
+
+        let lum = (top..bottom)
+            .flat_map(|py| (left..right).map(move |px| (px, py)))
+            .map(|(px, py)| luminance(image.pixels[py * width + px]))
+            .collect::<Vec<_>>();
+
+        let (min, max) = lum.iter().fold((f32::MAX, f32::MIN), |(min, max), &l| {
+            (min.min(l), max.max(l))
+        });
+
+        // Nothing stands out.
+        if max - min < 0.1 {
+            return None;
+        }
+
+        let threshold = (min + max) / 2.;
+        let on = lum
+            .iter()
+            .map(|&l| match self {
+                Self::Bright => l > threshold,
+                Self::Dark => l < threshold,
+            })
+            .collect::<Vec<_>>();
+
+        let (cx, cy) = (x - left as i64, y - top as i64);
+        let nearest = (0..w * h).filter(|&i| on[i]).min_by_key(|&i| {
+            let dx = (i % w) as i64 - cx;
+            let dy = (i / w) as i64 - cy;
+            dx * dx + dy * dy
+        })?;
+
+        // Flood fill the spot to find its centroid.
+        let mut seen = vec![false; w * h];
+        let mut stack = vec![nearest];
+        let (mut sum_x, mut sum_y, mut count) = (0, 0, 0);
+        seen[nearest] = true;
+
+        while let Some(i) = stack.pop() {
+            let (px, py) = (i % w, i / w);
+            sum_x += px;
+            sum_y += py;
+            count += 1;
+
+            let neighbors = [
+                (px > 0).then(|| i - 1),
+                (px + 1 < w).then(|| i + 1),
+                (py > 0).then(|| i - w),
+                (py + 1 < h).then(|| i + w),
+            ];
+
+            for n in neighbors.into_iter().flatten() {
+                if on[n] && !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+
+        // The middle of the pixels, so that the point reads the pixel the
+        // centroid is in.
+        let center_x = left as f32 + sum_x as f32 / count as f32 + 0.5;
+        let center_y = top as f32 + sum_y as f32 / count as f32 + 0.5;
+
+        Some(pos2(center_x / width as f32, center_y / height as f32))
+    }
+}
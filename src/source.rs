@@ -0,0 +1,510 @@
+//! Where frames come from. The kind of source follows from the webcam URL:
+//!
+//! - `http://` and `https://`: a snapshot per frame, or an MJPEG stream if
+//!   the camera answers with `multipart/x-mixed-replace`
+//! - `rtsp://`: a stream, decoded by `ffmpeg`
+//! - `v4l2:///dev/video0`: a local camera, also through `ffmpeg`
+//! - `file://`: a single image, or a directory of images that are replayed
+//!   in order, such as a recording
+//! - `sim://` or `sim://<width>x<height>`: a generated test pattern
+//!
+//! A Home Assistant camera entity or a [`Preset`], if set, is used instead of
+//! the URL.
+
+use std::{
+    error::Error,
+    fs, io,
+    io::Read,
+    path::{Path, PathBuf},
+    process::Child,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+#[cfg(feature = "ffmpeg")]
+use std::process::{Command, Stdio};
+
+use epaint::{Color32, ColorImage};
+use serde::{Deserialize, Serialize};
+
+use laundry_machine_core::Marker;
+
+use crate::{
+    config::{percent_encode, WebcamConfig},
+    sampler::decode_regions,
+};
+
+/// How long to wait for a stream's first frame.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// JPEGs larger than this are assumed to be garbage.
+const MAX_JPEG_SIZE: usize = 16 * 1024 * 1024;
+
+/// A camera of a service that many already run, whose URLs follow from the
+/// service's base URL and the camera's name there.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub service: Service,
+    /// Such as `http://frigate.local:5000`.
+    pub url: String,
+    /// The camera's name in the service's config.
+    pub camera: String,
+    /// Stream instead of fetching a snapshot per frame. Frigate's stream is
+    /// its RTSP restream, which needs `ffmpeg`.
+    #[serde(default)]
+    pub stream: bool,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Service {
+    #[default]
+    Frigate,
+    Go2rtc,
+}
+
+impl Service {
+    #[cfg(feature = "gui")]
+    pub const ALL: [Self; 2] = [Self::Frigate, Self::Go2rtc];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Frigate => "Frigate",
+            Self::Go2rtc => "go2rtc",
+        }
+    }
+}
+
+impl Preset {
+    pub fn url(&self) -> String {
+        let base = self.url.trim_end_matches('/');
+        let camera = percent_encode(&self.camera);
+
+        match (self.service, self.stream) {
+            // Without the boxes and timestamp Frigate can draw, and less
+            // compressed than by default so that segment edges stay sharp.
+            (Service::Frigate, false) => {
+                format!("{base}/api/{camera}/latest.jpg?bbox=0&timestamp=0&quality=95")
+            }
+            // Restreamed by the go2rtc bundled with Frigate, on its own port.
+            (Service::Frigate, true) => {
+                let host = base.split_once("://").map_or(base, |(_, rest)| rest);
+                let host = host.split(['/', ':']).next().unwrap_or_default();
+                format!("rtsp://{host}:8554/{camera}")
+            }
+            (Service::Go2rtc, false) => format!("{base}/api/frame.jpeg?src={camera}"),
+            (Service::Go2rtc, true) => format!("{base}/api/stream.mjpeg?src={camera}"),
+        }
+    }
+}
+
+pub struct Frame {
+    pub image: ColorImage,
+    /// When the frame was captured, or received if that isn't known.
+    pub time: SystemTime,
+    /// How much of fetching the frame went to decoding it.
+    pub decode_time: Duration,
+}
+
+impl Frame {
+    /// Decodes an encoded image, like [`decode_regions`], timing that.
+    pub fn decode(
+        bytes: &[u8],
+        markers: Option<&[Marker]>,
+        time: SystemTime,
+    ) -> Result<Self, Box<dyn Error>> {
+        let start = Instant::now();
+        let image = decode_regions(bytes, markers)?;
+
+        Ok(Frame {
+            image,
+            time,
+            decode_time: start.elapsed(),
+        })
+    }
+}
+
+pub trait FrameSource: Send {
+    /// The most recent frame, blocking until there is one. With `markers`,
+    /// large frames only have the regions those cover decoded.
+    fn latest(&mut self, markers: Option<&[Marker]>) -> Result<Frame, Box<dyn Error>>;
+}
+
+/// Opens the source for a webcam. Streams are only connected to once the
+/// first frame is requested. Frames that `ffmpeg` decodes are scaled down to
+/// `max_width`, if set.
+#[cfg_attr(not(feature = "ffmpeg"), allow(unused_variables))]
+pub fn open(
+    webcam: &WebcamConfig,
+    max_width: Option<u32>,
+) -> Result<Box<dyn FrameSource>, Box<dyn Error>> {
+    if let Some(camera) = &webcam.home_assistant {
+        return Ok(Box::new(Http {
+            url: camera.snapshot_url(),
+            authorization: camera.token().map(|token| format!("Bearer {}", token)),
+            stream: None,
+        }));
+    }
+
+    let url = webcam.image_url();
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", &url));
+
+    Ok(match scheme {
+        "http" | "https" => Box::new(Http {
+            url: url.clone(),
+            authorization: None,
+            stream: None,
+        }),
+        #[cfg(feature = "ffmpeg")]
+        "rtsp" | "rtsps" => Box::new(Ffmpeg {
+            input: vec![
+                "-rtsp_transport".into(),
+                "tcp".into(),
+                "-i".into(),
+                url.clone(),
+            ],
+            max_width,
+            stream: None,
+        }),
+        #[cfg(feature = "ffmpeg")]
+        "v4l2" => Box::new(Ffmpeg {
+            input: vec!["-f".into(), "v4l2".into(), "-i".into(), rest.to_owned()],
+            max_width,
+            stream: None,
+        }),
+        #[cfg(not(feature = "ffmpeg"))]
+        "rtsp" | "rtsps" | "v4l2" => {
+            return Err(format!("{} webcams need the ffmpeg feature", scheme).into())
+        }
+        "file" => Box::new(Files::new(Path::new(rest))?),
+        "sim" => Box::new(Simulated::new(rest)?),
+        // Don't show the URL, which may contain credentials.
+        _ => return Err(format!("unsupported webcam URL scheme: {:?}", scheme).into()),
+    })
+}
+
+/// Fetches a snapshot per frame, switching to streaming if the server sends
+/// MJPEG.
+struct Http {
+    url: String,
+    /// An `Authorization` header to send, for APIs that take a token.
+    authorization: Option<String>,
+    stream: Option<Stream>,
+}
+
+impl Http {
+    /// Reads a frame from a stream, keeping it open unless that failed.
+    fn keep(
+        &mut self,
+        stream: Stream,
+        markers: Option<&[Marker]>,
+    ) -> Result<Frame, Box<dyn Error>> {
+        let result = stream.latest(markers);
+        if result.is_ok() {
+            self.stream = Some(stream);
+        }
+        result
+    }
+}
+
+impl FrameSource for Http {
+    fn latest(&mut self, markers: Option<&[Marker]>) -> Result<Frame, Box<dyn Error>> {
+        if let Some(stream) = self.stream.take() {
+            return self.keep(stream, markers);
+        }
+
+        // A read timeout rather than a total one, so that streams stay open.
+        let mut request = ureq::AgentBuilder::new()
+            .timeout_connect(TIMEOUT)
+            .timeout_read(TIMEOUT)
+            .build()
+            .get(&self.url);
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        let response = request.call()?;
+
+        if response.content_type().starts_with("multipart/") {
+            return self.keep(Stream::spawn(response.into_reader(), None), markers);
+        }
+
+        let mut bytes = vec![];
+        response.into_reader().read_to_end(&mut bytes)?;
+
+        Frame::decode(&bytes, markers, SystemTime::now())
+    }
+}
+
+/// Decodes a stream with `ffmpeg`, which is started on the first frame and
+/// restarted if it exits.
+#[cfg(feature = "ffmpeg")]
+struct Ffmpeg {
+    input: Vec<String>,
+    max_width: Option<u32>,
+    stream: Option<Stream>,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl FrameSource for Ffmpeg {
+    fn latest(&mut self, markers: Option<&[Marker]>) -> Result<Frame, Box<dyn Error>> {
+        let stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => {
+                // Never scaled up, and kept at an even height for the encoder.
+                let scale = self
+                    .max_width
+                    .map(|width| vec!["-vf".to_owned(), format!("scale=min({}\\,iw):-2", width)])
+                    .unwrap_or_default();
+
+                let mut child = Command::new("ffmpeg")
+                    .args(["-loglevel", "error"])
+                    .args(&self.input)
+                    .args(scale)
+                    .args(["-r", "1", "-f", "image2pipe", "-c:v", "mjpeg", "-"])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|e| format!("error starting ffmpeg: {}", e))?;
+
+                let stdout = child.stdout.take().ok_or("ffmpeg has no stdout")?;
+                Stream::spawn(stdout, Some(child))
+            }
+        };
+
+        // Restarted next time if it failed.
+        let result = stream.latest(markers);
+        if result.is_ok() {
+            self.stream = Some(stream);
+        }
+        result
+    }
+}
+
+/// JPEGs read from a stream on a background thread, keeping only the latest.
+struct Stream {
+    shared: Arc<Shared>,
+    child: Option<Child>,
+}
+
+#[derive(Default)]
+struct Shared {
+    latest: Mutex<Latest>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Latest {
+    jpeg: Option<(Vec<u8>, SystemTime)>,
+    error: Option<String>,
+}
+
+impl Stream {
+    fn spawn(reader: impl Read + Send + 'static, child: Option<Child>) -> Self {
+        let shared = Arc::new(Shared::default());
+        let weak = Arc::downgrade(&shared);
+
+        thread::spawn(move || {
+            // Stops once the stream is dropped.
+            let result = read_jpegs(reader, |jpeg| {
+                let Some(shared) = weak.upgrade() else {
+                    return false;
+                };
+
+                shared.latest.lock().unwrap().jpeg = Some((jpeg, SystemTime::now()));
+                shared.changed.notify_all();
+                true
+            });
+
+            if let Some(shared) = weak.upgrade() {
+                let error = match result {
+                    Ok(()) => "stream ended".to_owned(),
+                    Err(e) => e.to_string(),
+                };
+
+                shared.latest.lock().unwrap().error = Some(error);
+                shared.changed.notify_all();
+            }
+        });
+
+        Self { shared, child }
+    }
+
+    fn latest(&self, markers: Option<&[Marker]>) -> Result<Frame, Box<dyn Error>> {
+        let (latest, timeout) = self
+            .shared
+            .changed
+            .wait_timeout_while(self.shared.latest.lock().unwrap(), TIMEOUT, |latest| {
+                latest.jpeg.is_none() && latest.error.is_none()
+            })
+            .unwrap();
+
+        if let Some(error) = &latest.error {
+            return Err(error.clone().into());
+        }
+
+        if timeout.timed_out() {
+            return Err("timed out waiting for a frame".into());
+        }
+
+        let (jpeg, time) = latest.jpeg.clone().ok_or("no frame")?;
+        drop(latest);
+
+        Frame::decode(&jpeg, markers, time)
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
+
+/// Splits a byte stream into JPEGs by their start and end markers, which
+/// works for both multipart MJPEG and ffmpeg's output. Stops when `frame`
+/// returns false.
+fn read_jpegs(mut reader: impl Read, mut frame: impl FnMut(Vec<u8>) -> bool) -> io::Result<()> {
+    const START: [u8; 2] = [0xff, 0xd8];
+    const END: [u8; 2] = [0xff, 0xd9];
+
+    let find = |bytes: &[u8], marker: [u8; 2]| bytes.windows(2).position(|w| w == marker);
+
+    let mut buffer = vec![];
+    let mut chunk = vec![0; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        loop {
+            let Some(start) = find(&buffer, START) else {
+                // Keep the last byte, which may be the first half of a marker.
+                buffer.drain(..buffer.len().saturating_sub(1));
+                break;
+            };
+
+            let Some(end) = find(&buffer[start + 2..], END) else {
+                buffer.drain(..start);
+                break;
+            };
+
+            let end = start + 2 + end + 2;
+            if !frame(buffer[start..end].to_vec()) {
+                return Ok(());
+            }
+            buffer.drain(..end);
+        }
+
+        if buffer.len() > MAX_JPEG_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no complete JPEG in stream",
+            ));
+        }
+    }
+}
+
+/// A single image, or the images in a directory one after another, looping
+/// at the end.
+struct Files {
+    paths: Vec<PathBuf>,
+    next: usize,
+}
+
+impl Files {
+    fn new(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut paths = vec![];
+
+        if path.is_dir() {
+            find_images(path, &mut paths)?;
+            paths.sort();
+        } else {
+            paths.push(path.to_owned());
+        }
+
+        if paths.is_empty() {
+            return Err(format!("no images in {}", path.display()).into());
+        }
+
+        Ok(Self { paths, next: 0 })
+    }
+}
+
+fn find_images(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_images(&path, paths)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ["jpg", "jpeg", "png"].contains(&ext.to_lowercase().as_str()))
+        {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+impl FrameSource for Files {
+    fn latest(&mut self, markers: Option<&[Marker]>) -> Result<Frame, Box<dyn Error>> {
+        let path = &self.paths[self.next % self.paths.len()];
+        self.next += 1;
+
+        Frame::decode(&fs::read(path)?, markers, SystemTime::now())
+    }
+}
+
+/// A moving gradient, for trying things out without a camera.
+struct Simulated {
+    size: [usize; 2],
+    start: Instant,
+}
+
+impl Simulated {
+    fn new(size: &str) -> Result<Self, Box<dyn Error>> {
+        let size = match size.split_once('x') {
+            Some((width, height)) => [width.parse()?, height.parse()?],
+            None if size.is_empty() => [640, 480],
+            None => return Err(format!("invalid size {:?}, expected WIDTHxHEIGHT", size).into()),
+        };
+
+        Ok(Self {
+            size,
+            start: Instant::now(),
+        })
+    }
+}
+
+impl FrameSource for Simulated {
+    fn latest(&mut self, _markers: Option<&[Marker]>) -> Result<Frame, Box<dyn Error>> {
+        let [width, height] = self.size;
+        let offset = self.start.elapsed().as_secs_f32() * 16.;
+
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let gray = ((x + y) as f32 / (width + height) as f32 * 255. + offset) % 256.;
+                Color32::from_gray(gray as u8)
+            })
+            .collect();
+
+        Ok(Frame {
+            image: ColorImage {
+                size: self.size,
+                pixels,
+            },
+            time: SystemTime::now(),
+            decode_time: Duration::ZERO,
+        })
+    }
+}
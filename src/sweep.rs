@@ -0,0 +1,159 @@
+use std::ops::Range;
+
+use egui::{ecolor::Hsva, vec2, Color32, Rect, Sense, Stroke, Ui};
+
+use laundry_machine_core::{read_seven_segment, Marker, MarkerType, SegmentPattern};
+
+pub const STEPS: usize = 100;
+
+pub fn threshold_at(step: usize) -> f32 {
+    step as f32 / STEPS as f32
+}
+
+/// The values every seven segment marker decodes to across the whole
+/// threshold range, for a single sampled frame.
+pub struct Sweep {
+    markers: Vec<MarkerSweep>,
+}
+
+struct MarkerSweep {
+    name: String,
+    values: Vec<Option<String>>,
+    /// Longest run of steps that all decode to the same value.
+    plateau: Option<Range<usize>>,
+}
+
+impl Sweep {
+    pub fn new(markers: &[Marker], sampled: &[Vec<f32>], patterns: &[SegmentPattern]) -> Self {
+        let markers = markers
+            .iter()
+            .zip(sampled)
+            .filter(|(marker, _)| matches!(marker.ty, MarkerType::SevenSegment { .. }))
+            .map(|(marker, samples)| {
+                let values = (0..=STEPS)
+                    .map(|step| {
+                        read_seven_segment(&marker.oriented(samples), threshold_at(step), patterns)
+                    })
+                    .collect::<Vec<_>>();
+
+                MarkerSweep {
+                    name: marker.name.clone(),
+                    plateau: longest_plateau(&values),
+                    values,
+                }
+            })
+            .collect();
+
+        Self { markers }
+    }
+
+    /// The threshold in the middle of the plateau all markers share, if any.
+    pub fn best_threshold(&self) -> Option<f32> {
+        let mut common = 0..STEPS + 1;
+        for marker in &self.markers {
+            let plateau = marker.plateau.clone()?;
+            common = common.start.max(plateau.start)..common.end.min(plateau.end);
+        }
+
+        (!common.is_empty()).then(|| threshold_at((common.start + common.end - 1) / 2))
+    }
+
+    pub fn show(&self, ui: &mut Ui, threshold: &mut f32) {
+        if self.markers.is_empty() {
+            ui.label("No seven segment markers have been sampled yet.");
+            return;
+        }
+
+        for marker in &self.markers {
+            ui.label(&marker.name);
+
+            let (response, painter) =
+                ui.allocate_painter(vec2(ui.available_width().max(200.), 16.), Sense::click());
+            let rect = response.rect;
+            let step_width = rect.width() / marker.values.len() as f32;
+
+            let step_rect = |step: usize| {
+                Rect::from_min_size(
+                    rect.left_top() + vec2(step as f32 * step_width, 0.),
+                    vec2(step_width, rect.height()),
+                )
+            };
+
+            for (step, value) in marker.values.iter().enumerate() {
+                painter.rect_filled(step_rect(step), 0., value_color(value.as_deref()));
+            }
+
+            if let Some(plateau) = &marker.plateau {
+                painter.rect_stroke(
+                    step_rect(plateau.start).union(step_rect(plateau.end - 1)),
+                    0.,
+                    Stroke::new(2., Color32::WHITE),
+                );
+            }
+
+            let current = (*threshold * STEPS as f32).round() as usize;
+            painter.vline(
+                step_rect(current.min(STEPS)).center().x,
+                rect.y_range(),
+                Stroke::new(1., Color32::RED),
+            );
+
+            let hovered_step = response.hover_pos().map(|pos| {
+                (((pos.x - rect.left()) / step_width) as usize).min(marker.values.len() - 1)
+            });
+
+            if response.clicked() {
+                if let Some(step) = hovered_step {
+                    *threshold = threshold_at(step);
+                }
+            }
+
+            if let Some(step) = hovered_step {
+                response.on_hover_text(format!(
+                    "{:.2}: {}",
+                    threshold_at(step),
+                    marker.values[step].as_deref().unwrap_or("-")
+                ));
+            }
+        }
+
+        match self.best_threshold() {
+            Some(best) => {
+                if ui.button(format!("Use {best:.2}")).clicked() {
+                    *threshold = best;
+                }
+            }
+            None => {
+                ui.label("No threshold decodes all markers stably.");
+            }
+        }
+    }
+}
+
+fn longest_plateau(values: &[Option<String>]) -> Option<Range<usize>> {
+    let mut best: Option<Range<usize>> = None;
+    let mut start = 0;
+
+    for end in 1..=values.len() {
+        if end == values.len() || values[end] != values[start] {
+            if values[start].is_some() && best.as_ref().is_none_or(|b| b.len() < end - start) {
+                best = Some(start..end);
+            }
+            start = end;
+        }
+    }
+
+    best
+}
+
+fn value_color(value: Option<&str>) -> Color32 {
+    match value {
+        Some(value) => {
+            let hue = value
+                .parse::<i32>()
+                .unwrap_or_else(|_| value.chars().map(|c| c as i32).sum());
+            Hsva::new((hue as f32 * 0.618_034).fract(), 0.6, 0.8, 1.).into()
+        }
+        None => Color32::from_gray(30),
+    }
+}
@@ -0,0 +1,75 @@
+use std::{env, error::Error, fs, time::Duration};
+
+use directories::BaseDirs;
+use sd_notify::NotifyState;
+
+use crate::cli::Args;
+
+const UNIT_NAME: &str = "laundry-machine-mqtt.service";
+
+/// Tells systemd the daemon is ready, if it was started as a notify service.
+pub fn notify_ready() {
+    sd_notify::notify(false, &[NotifyState::Ready]).ok();
+}
+
+pub fn notify_stopping() {
+    sd_notify::notify(false, &[NotifyState::Stopping]).ok();
+}
+
+/// Pings systemd's watchdog, if enabled.
+pub fn ping_watchdog() {
+    sd_notify::notify(false, &[NotifyState::Watchdog]).ok();
+}
+
+/// Writes a user service unit that runs the current executable headless
+/// with the same config.
+pub fn install(args: &Args) -> Result<(), Box<dyn Error>> {
+    let dirs = BaseDirs::new().ok_or("no home directory")?;
+    let unit_dir = dirs.config_dir().join("systemd").join("user");
+    fs::create_dir_all(&unit_dir)?;
+
+    let mut exec_start = format!(
+        "{} --headless --refresh-secs {}",
+        env::current_exe()?.display(),
+        args.refresh_secs
+    );
+
+    if let Some(http) = &args.http {
+        exec_start += &format!(" --http {}", http);
+    }
+
+    if let Some(config) = args.config_path() {
+        exec_start += &format!(" --config {}", config.display());
+    }
+
+    // Leave the watchdog enough slack for a few slow frame fetches.
+    let refresh_rate = args
+        .effective_config()
+        .refresh_rate(Duration::from_secs(args.refresh_secs));
+    let watchdog = (refresh_rate * 4).max(Duration::from_secs(60));
+
+    let unit = format!(
+        "[Unit]
+Description=Laundry machine MQTT publisher
+After=network-online.target
+
+[Service]
+Type=notify
+ExecStart={exec_start}
+Restart=on-failure
+WatchdogSec={}
+
+[Install]
+WantedBy=default.target
+",
+        watchdog.as_secs()
+    );
+
+    let path = unit_dir.join(UNIT_NAME);
+    fs::write(&path, unit)?;
+
+    println!("Wrote {}", path.display());
+    println!("Enable it with: systemctl --user daemon-reload && systemctl --user enable --now {UNIT_NAME}");
+
+    Ok(())
+}
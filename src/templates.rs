@@ -0,0 +1,69 @@
+use egui::{pos2, Pos2};
+
+use laundry_machine_core::{Marker, MarkerType};
+
+/// A preset marker layout for a common kind of machine display, to be
+/// fine-tuned after adding it.
+pub struct Template {
+    pub name: &'static str,
+    pub markers: fn() -> Vec<Marker>,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "4-digit clock (hh:mm)",
+        markers: || {
+            vec![
+                seven_segment("hour", pos2(0.3, 0.45), pos2(0.47, 0.45), 2),
+                seven_segment("minute", pos2(0.53, 0.45), pos2(0.7, 0.45), 2),
+            ]
+        },
+    },
+    Template {
+        name: "3-digit countdown (h:mm)",
+        markers: || {
+            vec![
+                seven_segment("hour", pos2(0.35, 0.45), pos2(0.43, 0.45), 1),
+                seven_segment("minute", pos2(0.49, 0.45), pos2(0.65, 0.45), 2),
+            ]
+        },
+    },
+    Template {
+        name: "Camera clock (hh:mm:ss)",
+        markers: || {
+            vec![
+                seven_segment("clock_hour", pos2(0.72, 0.04), pos2(0.78, 0.04), 2),
+                seven_segment("clock_minute", pos2(0.8, 0.04), pos2(0.86, 0.04), 2),
+                seven_segment("clock_second", pos2(0.88, 0.04), pos2(0.94, 0.04), 2),
+            ]
+        },
+    },
+    Template {
+        name: "LED row (5 indicators)",
+        markers: || {
+            (0..5)
+                .map(|n| {
+                    let mut marker = Marker::new(MarkerType::Point {
+                        pos: pos2(0.3 + n as f32 * 0.1, 0.6),
+                        size: 0.01,
+                    });
+                    marker.name = format!("led_{}", n + 1);
+                    marker
+                })
+                .collect()
+        },
+    },
+];
+
+fn seven_segment(name: &str, start: Pos2, end: Pos2, digits: usize) -> Marker {
+    let mut marker = Marker::new(MarkerType::SevenSegment {
+        start,
+        end,
+        bottom: start + (end - start).rot90() * 0.5 / digits as f32,
+        digits,
+        spacing: 0.01,
+        size: 0.01,
+    });
+    marker.name = name.to_owned();
+    marker
+}
@@ -0,0 +1,194 @@
+//! A terminal UI for headless mode, showing the active profile's values, the
+//! connection state and recent logs, for checking on it over SSH.
+
+use std::{
+    io::{self, Stdout},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table},
+    Frame, Terminal,
+};
+use serde_json::Value;
+use tracing::{error, Level};
+
+use crate::{
+    api::{SharedStatus, Status},
+    logging,
+};
+
+/// How often the screen is redrawn, and keys are checked for.
+const REFRESH: Duration = Duration::from_millis(250);
+
+/// Draws the UI on a background thread until it's dropped, or stops the
+/// backend with `shutdown` when quit.
+pub struct Tui {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Tui {
+    pub fn start(status: SharedStatus, shutdown: impl Fn() + Send + 'static) -> io::Result<Self> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            if let Err(e) = run(&mut terminal, &status, &thread_stop, shutdown) {
+                error!("Error drawing terminal UI: {}", e);
+            }
+        });
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+
+        disable_raw_mode().ok();
+        execute!(io::stdout(), LeaveAlternateScreen).ok();
+    }
+}
+
+fn run(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    status: &SharedStatus,
+    stop: &AtomicBool,
+    shutdown: impl Fn(),
+) -> io::Result<()> {
+    while !stop.load(Ordering::Relaxed) {
+        terminal.draw(|frame| draw(frame, &status.lock().unwrap()))?;
+
+        if !event::poll(REFRESH)? {
+            continue;
+        }
+
+        // Raw mode keeps Ctrl-C from reaching the signal handler.
+        if let Event::Key(key) = event::read()? {
+            let ctrl_c =
+                key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+            if key.kind == KeyEventKind::Press
+                && (ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc))
+            {
+                shutdown();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, status: &Status) {
+    let profile = status.config.profile();
+    let [header, timings, values, logs] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(profile.markers.len() as u16 + 2),
+        Constraint::Min(3),
+    ])
+    .areas(frame.size());
+
+    let age = |time: Option<Instant>| {
+        time.map_or("never".to_owned(), |time| {
+            format!("{}s ago", time.elapsed().as_secs())
+        })
+    };
+
+    let mqtt = if status.mqtt_connected {
+        Span::styled("connected", Style::new().fg(Color::Green))
+    } else {
+        Span::styled("disconnected", Style::new().fg(Color::Red))
+    };
+
+    let mut spans = vec![
+        Span::raw(format!("{}  MQTT ", profile.name)),
+        mqtt,
+        Span::raw(format!(
+            "  frame {}  published {}",
+            age(status.frame_time),
+            age(status.publish_time)
+        )),
+    ];
+    if status.paused.contains(&profile.topic_prefix) {
+        spans.push(Span::styled("  paused", Style::new().fg(Color::Yellow)));
+    }
+    spans.push(Span::raw("  (q to quit)"));
+    frame.render_widget(Paragraph::new(Line::from(spans)), header);
+
+    if let Some(stages) = status.timings {
+        let ms = |time: Duration| format!("{:.1} ms", time.as_secs_f64() * 1000.);
+        let text = format!(
+            "fetch {}  decode {}  sample {}  publish {}",
+            ms(stages.fetch),
+            ms(stages.decode),
+            ms(stages.sample),
+            ms(stages.publish)
+        );
+        frame.render_widget(
+            Paragraph::new(text).style(Style::new().fg(Color::DarkGray)),
+            timings,
+        );
+    }
+
+    let rows = profile.markers.iter().enumerate().map(|(idx, marker)| {
+        let value = match status.values.get(idx) {
+            Some(Value::Null) | None => "-".to_owned(),
+            Some(Value::String(text)) => text.clone(),
+            Some(value) => value.to_string(),
+        };
+        Row::new([Cell::from(marker.name.clone()), Cell::from(value)])
+    });
+    let table = Table::new(
+        rows,
+        [Constraint::Percentage(40), Constraint::Percentage(60)],
+    )
+    .block(Block::default().borders(Borders::ALL).title("Values"));
+    frame.render_widget(table, values);
+
+    // The most recent lines that fit, inside the border.
+    let height = logs.height.saturating_sub(2) as usize;
+    let lines = logging::with_recent(|lines| {
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(height))
+            .map(|line| {
+                let color = match line.level {
+                    Level::ERROR => Color::Red,
+                    Level::WARN => Color::Yellow,
+                    Level::INFO => Color::Reset,
+                    _ => Color::DarkGray,
+                };
+                Line::styled(line.to_string(), Style::new().fg(color))
+            })
+            .collect::<Vec<_>>()
+    });
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Log")),
+        logs,
+    );
+}
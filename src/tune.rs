@@ -0,0 +1,211 @@
+//! Finding thresholds that decode exactly what the display is known to show.
+
+use egui::{Grid, Ui};
+use serde_json::Value;
+
+use laundry_machine_core::{read_seven_segment, Marker, MarkerType, Output, SegmentPattern};
+
+use crate::{
+    config::Profile,
+    sweep::{threshold_at, STEPS},
+};
+
+/// What each marker currently shows, as typed in by the user.
+pub struct Tune {
+    /// Left empty for markers that shouldn't be tuned.
+    shown: Vec<String>,
+    result: Option<Result<String, String>>,
+}
+
+impl Tune {
+    /// Starts out with the values the markers currently decode to.
+    pub fn new(markers: &[Marker], values: &[Value]) -> Self {
+        let shown = markers
+            .iter()
+            .enumerate()
+            .map(|(idx, marker)| match (&marker.ty, values.get(idx)) {
+                (MarkerType::Point { .. }, Some(Value::Bool(lit))) => {
+                    if *lit { "on" } else { "off" }.to_owned()
+                }
+                (MarkerType::SevenSegment { .. }, Some(Value::Number(number))) => {
+                    number.to_string()
+                }
+                (MarkerType::SevenSegment { .. }, Some(Value::String(text))) => text.clone(),
+                _ => String::new(),
+            })
+            .collect();
+
+        Self {
+            shown,
+            result: None,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, profile: &mut Profile, sampled: &[Vec<f32>]) {
+        ui.label("Type in what the display shows right now.");
+
+        self.shown.resize(profile.markers.len(), String::new());
+
+        Grid::new("tune").num_columns(2).show(ui, |ui| {
+            for (marker, shown) in profile.markers.iter().zip(&mut self.shown) {
+                ui.label(&marker.name);
+                ui.text_edit_singleline(shown)
+                    .on_hover_text(match marker.ty {
+                        MarkerType::Point { .. } => "on or off, or empty to leave it alone",
+                        MarkerType::SevenSegment { .. } => {
+                            "The characters shown, or empty to leave it alone"
+                        }
+                        MarkerType::Gpio { .. } => "Not tuned, as it has no threshold",
+                    });
+                ui.end_row();
+            }
+        });
+
+        if ui.button("Tune").clicked() {
+            self.result = Some(self.tune(profile, sampled));
+        }
+
+        match &self.result {
+            Some(Ok(message)) => {
+                ui.label(message);
+            }
+            Some(Err(message)) => {
+                ui.colored_label(ui.visuals().warn_fg_color, message);
+            }
+            None => {}
+        }
+    }
+
+    /// Picks a single threshold for the whole profile that decodes every
+    /// marker to what it shows, or one per marker if there's no such
+    /// threshold. Nothing is changed if some marker can't be decoded to what
+    /// it shows at all.
+    fn tune(&self, profile: &mut Profile, sampled: &[Vec<f32>]) -> Result<String, String> {
+        let mut matching = vec![];
+
+        for (idx, (marker, shown)) in profile.markers.iter().zip(&self.shown).enumerate() {
+            if shown.trim().is_empty() {
+                continue;
+            }
+
+            let shown = parse_shown(&marker.ty, shown.trim())
+                .ok_or_else(|| format!("{} can't show {}", marker.name, shown.trim()))?;
+
+            let samples = sampled
+                .get(idx)
+                .filter(|samples| !samples.is_empty())
+                .ok_or_else(|| format!("{} hasn't been sampled yet", marker.name))?;
+
+            let decodes = (0..=STEPS)
+                .map(|step| {
+                    decode(
+                        marker,
+                        samples,
+                        threshold_at(step),
+                        &profile.segment_patterns,
+                    ) == shown
+                })
+                .collect::<Vec<_>>();
+
+            matching.push((idx, decodes));
+        }
+
+        if matching.is_empty() {
+            return Err("Type in what at least one marker shows.".to_owned());
+        }
+
+        let common = (0..=STEPS)
+            .map(|step| matching.iter().all(|(_, decodes)| decodes[step]))
+            .collect::<Vec<_>>();
+
+        if let Some(threshold) = middle_of_longest_run(&common) {
+            profile.luminance_threshold = threshold;
+            for (idx, _) in &matching {
+                profile.markers[*idx].threshold = None;
+            }
+
+            return Ok(format!("Using {:.2} for all markers.", threshold));
+        }
+
+        let thresholds = matching
+            .iter()
+            .map(|(idx, decodes)| {
+                middle_of_longest_run(decodes)
+                    .map(|threshold| (*idx, threshold))
+                    .ok_or_else(|| profile.markers[*idx].name.clone())
+            })
+            .collect::<Vec<_>>();
+
+        let unmatched = thresholds
+            .iter()
+            .filter_map(|result| result.as_ref().err().cloned())
+            .collect::<Vec<_>>();
+
+        if !unmatched.is_empty() {
+            return Err(format!(
+                "No threshold decodes {} to what it shows.",
+                unmatched.join(", ")
+            ));
+        }
+
+        for (idx, threshold) in thresholds.into_iter().flatten() {
+            profile.markers[idx].threshold = Some(threshold);
+        }
+
+        Ok("No single threshold works, so each marker got its own.".to_owned())
+    }
+}
+
+fn parse_shown(ty: &MarkerType, shown: &str) -> Option<Value> {
+    match ty {
+        MarkerType::Point { .. } => match shown.to_lowercase().as_str() {
+            "on" | "true" | "1" => Some(Value::Bool(true)),
+            "off" | "false" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        MarkerType::Gpio { .. } => None,
+        MarkerType::SevenSegment { .. } => {
+            Some(Output::Integer.format(Value::String(shown.to_owned())))
+        }
+    }
+}
+
+/// Decodes a marker's samples at exactly the given threshold, unlike
+/// [`Marker::decode`], which retries nearby thresholds.
+fn decode(marker: &Marker, samples: &[f32], threshold: f32, patterns: &[SegmentPattern]) -> Value {
+    match marker.ty {
+        MarkerType::Point { .. } | MarkerType::Gpio { .. } => samples
+            .first()
+            .map_or(Value::Null, |&sample| Value::Bool(sample > threshold)),
+        MarkerType::SevenSegment { .. } => {
+            read_seven_segment(&marker.oriented(samples), threshold, patterns)
+                .map_or(Value::Null, |text| {
+                    Output::Integer.format(Value::String(text))
+                })
+        }
+    }
+}
+
+/// The threshold in the middle of the longest run of matching steps, which
+/// leaves the most room for the lighting to change.
+fn middle_of_longest_run(matches: &[bool]) -> Option<f32> {
+    let mut best: Option<(usize, usize)> = None;
+    let mut start = None;
+
+    for (step, &matched) in matches.iter().chain([&false]).enumerate() {
+        match (matched, start) {
+            (true, None) => start = Some(step),
+            (false, Some(run_start)) => {
+                if best
+                    .is_none_or(|(best_start, best_end)| best_end - best_start < step - run_start)
+                {
+                    best = Some((run_start, step));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    best.map(|(start, end)| threshold_at((start + end - 1) / 2))
+}
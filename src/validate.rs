@@ -0,0 +1,683 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use laundry_machine_core::{marker_topic, Marker, MarkerType, Output};
+
+use crate::{config::Config, lighting, machine::Machine};
+
+/// Something wrong with the config that will cause sampling or publishing to
+/// misbehave.
+pub struct Problem {
+    /// The profile the problem is in, or the config section for global
+    /// settings.
+    pub scope: String,
+    pub marker: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.scope)?;
+        if let Some(marker) = &self.marker {
+            write!(f, " / {}", marker)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+pub fn validate(config: &Config) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    let mut problem = |scope: &str, marker: Option<&str>, message: String| {
+        problems.push(Problem {
+            scope: scope.to_owned(),
+            marker: marker.map(str::to_owned),
+            message,
+        })
+    };
+
+    if config.mqtt.host.trim().is_empty() {
+        problem("MQTT", None, "host is empty".to_owned());
+    }
+
+    if config.mqtt.port == 0 {
+        problem("MQTT", None, "port is 0".to_owned());
+    }
+
+    for webhook in &config.webhooks {
+        if let Err(e) = check_url(&webhook.url, &["http", "https", "file"]) {
+            problem("Webhooks", None, format!("URL {}", e));
+        }
+
+        if let Err(e) = serde_json::from_str::<serde_json::Value>(&webhook.body) {
+            problem(
+                "Webhooks",
+                None,
+                format!("body of {} isn't JSON: {}", webhook.url, e),
+            );
+        }
+    }
+
+    if let Some(quiet_hours) = &config.notifications.quiet_hours {
+        for time in [&quiet_hours.from, &quiet_hours.until] {
+            if lighting::parse_time(time).is_none() {
+                problem(
+                    "Notifications",
+                    None,
+                    format!("quiet hours time {} isn't HH:MM", time),
+                );
+            }
+        }
+    }
+
+    // Topics are checked across profiles, which may share a prefix.
+    let mut topics = HashMap::new();
+
+    for profile in &config.profiles {
+        let profile_name = profile.name.as_str();
+
+        if let Some(camera) = &profile.webcam.home_assistant {
+            if let Err(e) = check_url(&camera.api_url(), &["http", "https"]) {
+                problem(profile_name, None, format!("Home Assistant URL {}", e));
+            }
+            if !camera.entity_id.starts_with("camera.") {
+                problem(
+                    profile_name,
+                    None,
+                    format!(
+                        "Home Assistant entity {:?} isn't a camera",
+                        camera.entity_id
+                    ),
+                );
+            }
+            if camera.token().is_none() {
+                problem(profile_name, None, "Home Assistant token is missing".into());
+            }
+        } else if let Some(preset) = &profile.webcam.preset {
+            if let Err(e) = check_url(&preset.url, &["http", "https"]) {
+                problem(
+                    profile_name,
+                    None,
+                    format!("{} URL {}", preset.service.name(), e),
+                );
+            }
+            if preset.camera.is_empty() {
+                problem(
+                    profile_name,
+                    None,
+                    format!("{} camera name is empty", preset.service.name()),
+                );
+            }
+        } else if let Err(e) = check_url(&profile.webcam.url, WEBCAM_SCHEMES) {
+            problem(profile_name, None, format!("webcam URL {}", e));
+        }
+
+        if let Some(ptz) = &profile.webcam.ptz {
+            if let Err(e) = check_url(&ptz.url, &["http", "https"]) {
+                problem(profile_name, None, format!("PTZ URL {}", e));
+            }
+        }
+
+        if let Err(e) = profile
+            .topic_prefix
+            .split('/')
+            .try_for_each(check_topic_level)
+        {
+            problem(profile_name, None, format!("topic prefix {}", e));
+        }
+
+        let composite = &profile.time_remaining;
+        if let Err(e) = composite.topic.split('/').try_for_each(check_topic_level) {
+            problem(profile_name, None, format!("time remaining topic {}", e));
+        }
+        if composite.hour == composite.minute {
+            problem(
+                profile_name,
+                None,
+                "time remaining reads hours and minutes from the same value".to_owned(),
+            );
+        }
+
+        if let Some(schedule) = &profile.schedule {
+            for time in [&schedule.from, &schedule.until].into_iter().flatten() {
+                if lighting::parse_time(time).is_none() {
+                    problem(
+                        profile_name,
+                        None,
+                        format!("schedule time {} isn't HH:MM", time),
+                    );
+                }
+            }
+        }
+
+        if let Machine::Washer {
+            door_lock: Some(door_lock),
+            ..
+        } = &profile.machine
+        {
+            if !profile.markers.iter().any(|marker| {
+                &marker.name == door_lock && matches!(marker.ty, MarkerType::Point { .. })
+            }) {
+                problem(
+                    profile_name,
+                    None,
+                    format!("door lock {} isn't a point marker", door_lock),
+                );
+            }
+        }
+
+        if let Some(vibration) = &profile.vibration {
+            if vibration.region.is_empty() {
+                problem(profile_name, None, "vibration region is empty".to_owned());
+            }
+        }
+
+        if let Some(clock) = &profile.frame_clock {
+            for name in [&clock.hour, &clock.minute]
+                .into_iter()
+                .chain(clock.second.as_ref())
+            {
+                if !profile.markers.iter().any(|marker| {
+                    &marker.name == name && matches!(marker.ty, MarkerType::SevenSegment { .. })
+                }) {
+                    problem(
+                        profile_name,
+                        None,
+                        format!("camera clock marker {} isn't a seven segment marker", name),
+                    );
+                }
+            }
+        }
+
+        if let Some(reminders) = &profile.reminders {
+            if reminders.intervals_mins.is_empty() {
+                problem(profile_name, None, "reminders have no intervals".to_owned());
+            }
+
+            if reminders.intervals_mins.contains(&0) {
+                problem(
+                    profile_name,
+                    None,
+                    "reminder intervals need to be at least a minute".to_owned(),
+                );
+            }
+        }
+
+        if let Some(door) = &profile.door {
+            if door.region.is_empty() {
+                problem(profile_name, None, "door region is empty".to_owned());
+            }
+        }
+
+        if let Some(audio) = &profile.audio {
+            if audio.low_hz >= audio.high_hz {
+                problem(
+                    profile_name,
+                    None,
+                    "audio frequencies are out of order".to_owned(),
+                );
+            }
+
+            if audio.pattern.len() % 2 == 0 && !audio.pattern.is_empty() {
+                problem(
+                    profile_name,
+                    None,
+                    "beep pattern needs to start and end with a beep".to_owned(),
+                );
+            }
+        }
+
+        if let Some(presence) = &profile.presence {
+            let levels = presence.topic.split('/').collect::<Vec<_>>();
+            if levels.iter().filter(|&&level| level == "+").count() != 1
+                || levels
+                    .iter()
+                    .any(|level| level.contains('#') || level.len() > 1 && level.contains('+'))
+            {
+                problem(
+                    profile_name,
+                    None,
+                    format!(
+                        "presence topic {} needs a single + level for the person",
+                        presence.topic
+                    ),
+                );
+            }
+        }
+
+        let mut patterns = HashMap::new();
+
+        for pattern in &profile.segment_patterns {
+            let Some(mask) = pattern.mask() else {
+                problem(
+                    profile_name,
+                    None,
+                    format!(
+                        "segment pattern {:?} has segments other than a to g",
+                        pattern.segments
+                    ),
+                );
+                continue;
+            };
+
+            match patterns.insert(mask, pattern.character) {
+                Some(other) if other != pattern.character => problem(
+                    profile_name,
+                    None,
+                    format!(
+                        "segment pattern {:?} is both {} and {}",
+                        pattern.segments, other, pattern.character
+                    ),
+                ),
+                _ => {}
+            }
+        }
+
+        for lighting in &profile.lighting {
+            for time in [&lighting.from, &lighting.until].into_iter().flatten() {
+                if lighting::parse_time(time).is_none() {
+                    problem(
+                        profile_name,
+                        None,
+                        format!("time {} of lighting {} isn't HH:MM", time, lighting.name),
+                    );
+                }
+            }
+        }
+
+        if let Some(reference) = &profile.ambient_reference {
+            if reference.find(&profile.markers).is_none() {
+                problem(
+                    profile_name,
+                    None,
+                    format!(
+                        "ambient reference {} isn't a point marker",
+                        reference.marker
+                    ),
+                );
+            }
+        }
+
+        let mut names = HashSet::new();
+
+        for marker in &profile.markers {
+            let name = Some(marker.name.as_str());
+
+            if let Err(e) = check_marker_name(marker) {
+                problem(profile_name, name, format!("name {}", e));
+            }
+
+            if !names.insert(&marker.name) {
+                problem(
+                    profile_name,
+                    name,
+                    "name is used by another marker".to_owned(),
+                );
+            } else {
+                for value_name in marker.value_names() {
+                    let topic =
+                        marker_topic(&profile.topic_prefix, value_name, &profile.time_remaining);
+                    let owner = (profile_name, value_name);
+
+                    match topics.get(&topic) {
+                        // Combined into a single value.
+                        Some(&(other_profile, other))
+                            if other_profile == profile_name
+                                && other != owner.1
+                                && profile.time_remaining.reads(other)
+                                && profile.time_remaining.reads(owner.1) => {}
+                        Some(&(other_profile, other)) => problem(
+                            profile_name,
+                            name,
+                            format!("publishes to {}, like {} / {}", topic, other_profile, other),
+                        ),
+                        None => {
+                            topics.insert(topic, owner);
+                        }
+                    }
+                }
+            }
+
+            if let MarkerType::SevenSegment {
+                start,
+                end,
+                digits,
+                spacing,
+                ..
+            } = marker.ty
+            {
+                if !(1..=10).contains(&digits) {
+                    problem(
+                        profile_name,
+                        name,
+                        format!("{} digits is out of range", digits),
+                    );
+                }
+
+                if spacing * digits as f32 >= (end - start).length() {
+                    problem(
+                        profile_name,
+                        name,
+                        "spacing between digits is larger than the display".to_owned(),
+                    );
+                }
+
+                if let Output::Split { groups } = &marker.output {
+                    for group in groups {
+                        if let Err(e) = check_topic_level(&group.name) {
+                            problem(profile_name, name, format!("digit group name {}", e));
+                        }
+                    }
+
+                    let grouped = groups.iter().map(|group| group.digits).sum::<usize>();
+                    if grouped != digits {
+                        problem(
+                            profile_name,
+                            name,
+                            format!("digit groups cover {} of {} digits", grouped, digits),
+                        );
+                    }
+                }
+
+                let retry = &marker.retry;
+                if retry.max_retries > 0 {
+                    if retry.step <= 0. {
+                        problem(profile_name, name, "retry step isn't positive".to_owned());
+                    }
+
+                    if retry.min_threshold >= retry.max_threshold {
+                        problem(
+                            profile_name,
+                            name,
+                            "retry thresholds are out of order".to_owned(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+/// The schemes [`crate::source::open`] handles.
+const WEBCAM_SCHEMES: &[&str] = &["http", "https", "rtsp", "rtsps", "v4l2", "file", "sim"];
+
+fn check_url(url: &str, schemes: &[&str]) -> Result<(), &'static str> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err("is missing a scheme");
+    };
+
+    if !schemes.contains(&scheme) {
+        return Err("has an unsupported scheme");
+    }
+
+    if !matches!(scheme, "file" | "v4l2" | "sim")
+        && rest.split(['/', '?']).next().unwrap_or_default().is_empty()
+    {
+        return Err("is missing a host");
+    }
+
+    Ok(())
+}
+
+/// Checks a marker's name like [`check_topic_level`], except for what
+/// [`Marker::raw_name`] allows.
+pub fn check_marker_name(marker: &Marker) -> Result<(), &'static str> {
+    if marker.raw_name {
+        check_publishable(&marker.name)
+    } else {
+        check_topic_level(&marker.name)
+    }
+}
+
+/// Checks that a name can be used as a single level of an MQTT topic.
+pub fn check_topic_level(name: &str) -> Result<(), &'static str> {
+    check_publishable(name)?;
+
+    if name.contains('/') {
+        return Err("contains a topic separator");
+    }
+
+    if name.chars().any(char::is_whitespace) {
+        return Err("contains whitespace");
+    }
+
+    Ok(())
+}
+
+/// Checks that a name can be published to at all.
+fn check_publishable(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("is empty");
+    }
+
+    if name.contains(['#', '+']) {
+        return Err("contains an MQTT wildcard");
+    }
+
+    Ok(())
+}
+
+/// Replaces what [`check_marker_name`] would reject in a marker's name with
+/// dashes. Wildcards are always replaced.
+#[cfg(feature = "gui")]
+pub fn sanitize_marker_name(marker: &mut Marker) {
+    let raw = marker.raw_name;
+    marker.name = marker
+        .name
+        .chars()
+        .map(|c| match c {
+            '#' | '+' => '-',
+            '/' if !raw => '-',
+            c if c.is_whitespace() && !raw => '-',
+            c => c,
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use epaint::pos2;
+    use laundry_machine_core::{DigitGroup, SegmentPattern};
+
+    use super::*;
+
+    /// A config with a single profile and nothing wrong with it.
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.migrate();
+        config.mqtt.host = "localhost".to_owned();
+        config.mqtt.port = 1883;
+        config.profiles[0].webcam.url = "http://camera/snapshot.jpg".to_owned();
+        config
+    }
+
+    fn problems(config: &Config) -> Vec<String> {
+        validate(config).iter().map(ToString::to_string).collect()
+    }
+
+    fn point(name: &str) -> Marker {
+        let mut marker = Marker::new(MarkerType::Point {
+            pos: pos2(0.5, 0.5),
+            size: 1.,
+        });
+        marker.name = name.to_owned();
+        marker
+    }
+
+    fn clock(name: &str, groups: &[(&str, usize)]) -> Marker {
+        let mut marker = Marker::new(MarkerType::SevenSegment {
+            start: pos2(0.1, 0.5),
+            end: pos2(0.9, 0.5),
+            bottom: pos2(0.1, 0.6),
+            digits: 3,
+            spacing: 0.01,
+            size: 1.,
+        });
+        marker.name = name.to_owned();
+        marker.output = Output::Split {
+            groups: groups
+                .iter()
+                .map(|&(name, digits)| DigitGroup {
+                    name: name.to_owned(),
+                    digits,
+                })
+                .collect(),
+        };
+        marker
+    }
+
+    #[test]
+    fn valid_config_has_no_problems() {
+        let mut config = config();
+        config.profiles[0].markers =
+            vec![point("door"), clock("clock", &[("hour", 1), ("minute", 2)])];
+
+        assert_eq!(problems(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn checks_urls() {
+        assert_eq!(check_url("http://camera/a.jpg", WEBCAM_SCHEMES), Ok(()));
+        assert_eq!(check_url("file:///tmp/a.jpg", WEBCAM_SCHEMES), Ok(()));
+        assert_eq!(
+            check_url("camera/a.jpg", WEBCAM_SCHEMES),
+            Err("is missing a scheme")
+        );
+        assert_eq!(
+            check_url("ftp://camera/a.jpg", WEBCAM_SCHEMES),
+            Err("has an unsupported scheme")
+        );
+        assert_eq!(
+            check_url("http:///a.jpg", WEBCAM_SCHEMES),
+            Err("is missing a host")
+        );
+        assert_eq!(
+            check_url("http://?a", WEBCAM_SCHEMES),
+            Err("is missing a host")
+        );
+    }
+
+    #[test]
+    fn checks_topic_levels() {
+        assert_eq!(check_topic_level("time-remaining"), Ok(()));
+        assert_eq!(check_topic_level(""), Err("is empty"));
+        assert_eq!(check_topic_level("a/b"), Err("contains a topic separator"));
+        assert_eq!(check_topic_level("a b"), Err("contains whitespace"));
+        assert_eq!(check_topic_level("a+"), Err("contains an MQTT wildcard"));
+        assert_eq!(check_topic_level("#"), Err("contains an MQTT wildcard"));
+    }
+
+    #[test]
+    fn raw_names_may_have_separators_but_not_wildcards() {
+        let mut marker = point("door/lock light");
+        assert!(check_marker_name(&marker).is_err());
+
+        marker.raw_name = true;
+        assert_eq!(check_marker_name(&marker), Ok(()));
+
+        marker.name = "door/#".to_owned();
+        assert_eq!(check_marker_name(&marker), Err("contains an MQTT wildcard"));
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn sanitizes_names() {
+        let mut marker = point("door/lock light+");
+        sanitize_marker_name(&mut marker);
+        assert_eq!(marker.name, "door-lock-light-");
+
+        let mut marker = point("door/lock light+");
+        marker.raw_name = true;
+        sanitize_marker_name(&mut marker);
+        assert_eq!(marker.name, "door/lock light-");
+    }
+
+    #[test]
+    fn finds_duplicate_names() {
+        let mut config = config();
+        config.profiles[0].markers = vec![point("door"), point("door")];
+
+        assert_eq!(
+            problems(&config),
+            ["default / door: name is used by another marker"],
+        );
+    }
+
+    #[test]
+    fn finds_topics_shared_across_profiles() {
+        let mut config = config();
+        config.profiles[0].markers = vec![point("door")];
+        let mut other = config.profiles[0].clone();
+        other.name = "dryer".to_owned();
+        config.profiles.push(other);
+
+        assert_eq!(
+            problems(&config),
+            ["dryer / door: publishes to laundry-machine/door, like default / door"],
+        );
+
+        config.profiles[1].topic_prefix = "dryer".to_owned();
+        assert_eq!(problems(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn finds_values_sharing_the_time_remaining_topic() {
+        let mut config = config();
+        config.profiles[0].markers = vec![point("hour"), point("minute"), point("time-remaining")];
+
+        assert_eq!(
+            problems(&config),
+            ["default / time-remaining: publishes to laundry-machine/time-remaining, like default / hour"],
+        );
+    }
+
+    #[test]
+    fn finds_digit_groups_not_covering_the_display() {
+        let mut config = config();
+        config.profiles[0].markers = vec![clock("clock", &[("hour", 1), ("minute", 1)])];
+
+        assert_eq!(
+            problems(&config),
+            ["default / clock: digit groups cover 2 of 3 digits"],
+        );
+    }
+
+    #[test]
+    fn finds_conflicting_segment_patterns() {
+        let mut config = config();
+        config.profiles[0].segment_patterns.push(SegmentPattern {
+            segments: "bc".to_owned(),
+            character: 'I',
+        });
+        config.profiles[0].segment_patterns.push(SegmentPattern {
+            segments: "xyz".to_owned(),
+            character: 'X',
+        });
+
+        assert_eq!(
+            problems(&config),
+            [
+                "default: segment pattern \"bc\" is both 1 and I",
+                "default: segment pattern \"xyz\" has segments other than a to g",
+            ],
+        );
+    }
+
+    #[test]
+    fn finds_times_that_arent_times() {
+        let mut config = config();
+        config.profiles[0].schedule = Some(crate::schedule::Schedule {
+            from: Some("6 am".to_owned()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            problems(&config),
+            ["default: schedule time 6 am isn't HH:MM"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Telling whether the drum is running from how much a region of the frame
+//! blurs and shifts between consecutive frames.
+
+use epaint::pos2;
+use serde::{Deserialize, Serialize};
+
+use crate::region::{self, Region};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vibration {
+    /// A region that shakes along with the drum, such as the edge of the
+    /// door against the wall behind it.
+    #[serde(flatten)]
+    pub region: Region,
+    /// Change between frames above which the drum counts as vibrating.
+    pub threshold: f32,
+    /// Frames in a row that have to agree before the state flips.
+    pub frames: u32,
+}
+
+impl Default for Vibration {
+    fn default() -> Self {
+        Self {
+            region: Region {
+                min: pos2(0.4, 0.4),
+                max: pos2(0.6, 0.6),
+            },
+            threshold: 0.02,
+            frames: 3,
+        }
+    }
+}
+
+/// Follows a single profile's region from frame to frame.
+#[derive(Default)]
+pub struct Detector {
+    previous: Vec<f32>,
+    vibrating: Option<bool>,
+    /// Frames in a row that disagreed with [`Self::vibrating`].
+    streak: u32,
+}
+
+impl Detector {
+    /// Takes in the region's cells of the latest frame, returning how much
+    /// they changed since the previous one, and the new state if it flipped.
+    pub fn update(
+        &mut self,
+        vibration: &Vibration,
+        cells: Vec<f32>,
+    ) -> (Option<f32>, Option<bool>) {
+        let previous = std::mem::replace(&mut self.previous, cells);
+
+        // Nothing to compare the first frame to, or frames of another size.
+        let Some(change) = region::difference(&previous, &self.previous) else {
+            return (None, None);
+        };
+
+        let vibrating = change > vibration.threshold;
+        if self.vibrating == Some(vibrating) {
+            self.streak = 0;
+            return (Some(change), None);
+        }
+
+        self.streak += 1;
+        if self.streak < vibration.frames.max(1) {
+            return (Some(change), None);
+        }
+
+        self.streak = 0;
+        self.vibrating = Some(vibrating);
+        (Some(change), Some(vibrating))
+    }
+
+    pub fn vibrating(&self) -> Option<bool> {
+        self.vibrating
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use egui::Context;
+use notify::{EventKind, RecursiveMode, Watcher};
+use tracing::error;
+
+/// Watches the config file for changes made outside of the app.
+pub struct ConfigWatcher {
+    _watcher: notify::RecommendedWatcher,
+    rx: Receiver<notify::Event>,
+    path: PathBuf,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path, ctx: Context) -> notify::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+                Ok(event) => {
+                    tx.send(event).ok();
+                    ctx.request_repaint();
+                }
+                Err(e) => error!("Error watching config: {}", e),
+            })?;
+
+        // Watch the directory rather than the file itself, since editors
+        // tend to save by replacing the file.
+        let dir = path.parent().unwrap_or(Path::new("."));
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Whether the config file was modified since the last call.
+    pub fn changed(&self) -> bool {
+        self.rx
+            .try_iter()
+            .filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+            .filter(|event| event.paths.iter().any(|path| path == &self.path))
+            .count()
+            > 0
+    }
+}
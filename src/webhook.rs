@@ -0,0 +1,124 @@
+//! HTTP webhooks fired when values change and when a cycle starts or
+//! finishes.
+
+use std::{error::Error, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::Profile,
+    event::{self, Event, EventKind},
+};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    pub events: Vec<EventKind>,
+    /// Only fire value changes for these markers, or for all of them if
+    /// empty.
+    #[serde(default)]
+    pub markers: Vec<String>,
+    /// JSON body, where a string that is exactly `{{name}}` is replaced by
+    /// that variable's value, and `{{name}}` inside other strings by its
+    /// text. The variables are `event`, `profile`, `marker`, `value` and
+    /// `time_remaining`.
+    #[serde(default = "default_body")]
+    pub body: String,
+    /// How many times to retry a failed request, with exponential backoff.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            events: vec![EventKind::CycleFinished],
+            markers: vec![],
+            body: default_body(),
+            retries: default_retries(),
+        }
+    }
+}
+
+fn default_body() -> String {
+    r#"{"event": "{{event}}", "marker": "{{marker}}", "value": "{{value}}"}"#.to_owned()
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+impl Webhook {
+    pub fn wants(&self, event: &Event) -> bool {
+        self.events.contains(&event.kind)
+            && match (&event.marker, event.kind) {
+                (Some(marker), EventKind::ValueChanged) => {
+                    self.markers.iter().all(String::is_empty) || self.markers.contains(marker)
+                }
+                _ => true,
+            }
+    }
+
+    /// The body for an event, with the template variables filled in.
+    pub fn render(
+        &self,
+        profile: &Profile,
+        event: &Event,
+        time_remaining: Option<u64>,
+    ) -> Result<Value, serde_json::Error> {
+        let template: Value = serde_json::from_str(&self.body)?;
+        let variables = event.variables(profile, time_remaining);
+        Ok(fill(template, &variables))
+    }
+
+    /// Sends a body, retrying with exponential backoff. Blocks until done.
+    pub fn send(&self, body: &Value) -> Result<(), Box<dyn Error>> {
+        let mut delay = Duration::from_secs(1);
+        let mut attempt = 0;
+
+        loop {
+            let result = ureq::post(&self.url)
+                .timeout(Duration::from_secs(10))
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string());
+
+            match result {
+                Ok(_) => return Ok(()),
+                Err(_) if attempt < self.retries => {
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+fn fill(template: Value, variables: &Map<String, Value>) -> Value {
+    match template {
+        Value::String(s) => {
+            if let Some(value) = s
+                .strip_prefix("{{")
+                .and_then(|s| s.strip_suffix("}}"))
+                .and_then(|name| variables.get(name.trim()))
+            {
+                return value.clone();
+            }
+
+            Value::String(event::fill_text(&s, variables))
+        }
+        Value::Array(values) => {
+            Value::Array(values.into_iter().map(|v| fill(v, variables)).collect())
+        }
+        Value::Object(values) => Value::Object(
+            values
+                .into_iter()
+                .map(|(k, v)| (k, fill(v, variables)))
+                .collect(),
+        ),
+        value => value,
+    }
+}